@@ -1,4 +1,4 @@
-use super::parse::CallDef;
+use super::parse::{CallDef, CallReceiver};
 use quote::quote;
 
 /// See the `fn call` docs at the `lib.rs` of this crate for a high level definition.
@@ -9,7 +9,7 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 	let fn_name = methods.iter().map(|method| &method.name).collect::<Vec<_>>();
 
 	// This is a nested vector of all the arguments for each of the functions in `fn_name`. It does
-	// not include the `self` or `caller: T::AccountId` parameter, which we always assume are the
+	// not include the `self` or `origin: T::RuntimeOrigin` parameter, which we always assume are the
 	// first two parameters to these calls.
 	let args_name = methods
 		.iter()
@@ -23,8 +23,59 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		.map(|method| method.args.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>())
 		.collect::<Vec<_>>();
 
+	// The call index of each function is used to encode the call. It is its position in the `impl`
+	// block unless it is set with `#[call_index(N)]`, so it is best to set it explicitly to keep the
+	// encoding stable.
+	let call_index = methods.iter().map(|method| method.call_index).collect::<Vec<_>>();
+
+	// The weight expression of each function, from its `#[weight(..)]` attribute.
+	let weight = methods.iter().map(|method| &method.weight).collect::<Vec<_>>();
+
+	// How each function is called from the runtime: on its pallet, or with the whole runtime.
+	let fn_call = methods
+		.iter()
+		.map(|method| {
+			let fn_name = &method.name;
+			match method.receiver {
+				CallReceiver::Pallet => quote! {
+					AsMut::<#pallet_struct<T>>::as_mut(runtime).#fn_name
+				},
+				CallReceiver::Runtime => quote! {
+					#pallet_struct::<T>::#fn_name
+				},
+			}
+		})
+		.collect::<Vec<_>>();
+	// The runtime is the first argument of the functions which take it.
+	let runtime_arg = methods
+		.iter()
+		.map(|method| match method.receiver {
+			CallReceiver::Pallet => None,
+			CallReceiver::Runtime => Some(quote!(runtime,)),
+		})
+		.collect::<Vec<_>>();
+
+	// This is a vector of all the argument types of all the functions, which need to be encodable
+	// and decodable.
+	let all_args_type = args_type.iter().flatten().collect::<Vec<_>>();
+
+	// The `Call` enum is generic over `T`, which is usually the `Runtime`. We implement `Clone`,
+	// `PartialEq` and `Debug` only requiring the arguments to implement them.
+	let call_ident = syn::Ident::new("Call", proc_macro2::Span::call_site());
+	let call_generics: syn::Generics = syn::parse_quote!(<T: Config>);
+	let call_variants = methods
+		.iter()
+		.map(|method| {
+			let (args_name, args_type): (Vec<_>, Vec<_>) = method.args.iter().cloned().unzip();
+			let fields: syn::FieldsNamed = syn::parse_quote!({ #( #args_name: #args_type ),* });
+			(method.name.clone(), syn::Fields::Named(fields))
+		})
+		.collect::<Vec<_>>();
+	let no_bound_impl =
+		crate::no_bound::expand_no_bound_impls(&call_ident, &call_generics, &call_variants);
+
 	// This quote block creates an `enum Call` which contains all the calls exposed by our pallet,
-	// and the `Dispatch` trait logic to route a `caller` to access those functions.
+	// and the `Dispatch` trait logic to route an `origin` to access those functions.
 	let dispatch_impl = quote! {
 		// The callable functions exposed by this pallet.
 		//
@@ -36,25 +87,101 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 			)*
 		}
 
+		#no_bound_impl
+
+		// Encoding of a call: the call index, followed by all the arguments in order.
+		impl<T: Config> crate::support::Encode for Call<T>
+		where
+			#( #all_args_type: crate::support::Encode ),*
+		{
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							dest.push(#call_index);
+							#( crate::support::Encode::encode_to(#args_name, dest); )*
+						},
+					)*
+				}
+			}
+		}
+
+		// Decoding of a call, which reads back the encoding above.
+		impl<T: Config> crate::support::Decode for Call<T>
+		where
+			#( #all_args_type: crate::support::Decode ),*
+		{
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+				Self::decode_with_depth_limit(crate::support::codec::MAX_DEPTH, input)
+			}
+
+			// Calls can contain other calls, so each call is one level of nesting, like an enum.
+			#[allow(unused_variables)]
+			fn decode_with_depth_limit(
+				depth_limit: u32,
+				input: &mut &[u8],
+			) -> Result<Self, crate::support::codec::Error> {
+				let depth_limit = crate::support::codec::enter_nested(depth_limit)?;
+				match crate::support::codec::read_byte(input)? {
+					#(
+						#call_index => Ok(Call::#fn_name {
+							#( #args_name: crate::support::Decode::decode_with_depth_limit(
+								depth_limit,
+								input,
+							)? ),*
+						}),
+					)*
+					_ => Err(crate::support::codec::Error("Invalid call index")),
+				}
+			}
+		}
+
+		// The weight of each call is computed from its arguments, which are available by reference
+		// in the `#[weight(..)]` expression.
+		impl<T: Config> crate::support::GetWeight for Call<T> {
+			#[allow(unused_variables)]
+			fn weight(&self) -> crate::support::Weight {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							let weight: crate::support::Weight = #weight;
+							weight
+						},
+					)*
+				}
+			}
+		}
+
 		// Dispatch logic at the pallet level, mapping each of the items in the `Call` enum to the
-		// appropriate function call with all arguments, including the `caller`.
-		impl<T: Config> crate::support::Dispatch for #pallet_struct<T> {
-			type Caller = T::AccountId;
-			type Call = Call<T>;
+		// appropriate function call with all arguments, including the `origin`. The runtime `T`
+		// gives access to the pallet, and to the whole runtime for the functions which take it.
+		impl<T: Config> crate::support::Dispatchable<T> for Call<T>
+		where
+			T: AsMut<#pallet_struct<T>>,
+		{
+			type Origin = T::RuntimeOrigin;
 
-			fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> crate::support::DispatchResult {
-				match call {
+			fn dispatch(
+				self,
+				runtime: &mut T,
+				origin: Self::Origin,
+			) -> crate::support::DispatchResultWithPostInfo {
+				match self {
 					#(
 						Call::#fn_name { #( #args_name ),* } => {
-							self.#fn_name(
-								// Note that we assume the first argument of every call is the `caller`.
-								caller,
+							// Calls can return a `DispatchResult`, or a `DispatchResultWithPostInfo`
+							// if they report the weight they actually used.
+							#fn_call(
+								#runtime_arg
+								// Note that we assume the next argument of every call is the `origin`.
+								origin,
 								#( #args_name ),*
-							)?;
+							)
+							.map(Into::into)
+							.map_err(Into::into)
 						},
 					)*
 				}
-				Ok(())
 			}
 		}
 	};
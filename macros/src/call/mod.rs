@@ -6,19 +6,20 @@ pub fn call(
 	_attr: proc_macro::TokenStream,
 	item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-	// The final expanded code will be placed here.
-	// Since our macro only adds new code, our final product will contain all of our old code too,
-	// hence we clone `item`.
-	let mut finished = item.clone();
-	let item_mod = syn::parse_macro_input!(item as syn::Item);
+	let mut item_mod = syn::parse_macro_input!(item as syn::Item);
 
-	// First we parse the call functions implemented for the pallet...
-	let generated: proc_macro::TokenStream = match parse::CallDef::try_from(item_mod.clone()) {
+	// First we parse the call functions implemented for the pallet. This also removes the helper
+	// attributes like `#[call_index]` from the functions, since the compiler does not know them...
+	let generated: proc_macro::TokenStream = match parse::CallDef::try_from(&mut item_mod) {
 		// ..then we generate our new code.
 		Ok(def) => expand::expand_call(def).into(),
 		Err(e) => e.to_compile_error().into(),
 	};
 
+	// The final expanded code will be placed here. Our macro only adds new code, so our final
+	// product will contain all of our old code too, without the helper attributes.
+	let mut finished: proc_macro::TokenStream = quote::ToTokens::into_token_stream(item_mod).into();
+
 	// Add our generated code to the end, and return the final result.
 	finished.extend(generated);
 	return finished;
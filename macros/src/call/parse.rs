@@ -4,7 +4,8 @@ use syn::spanned::Spanned;
 // Custom keywords we match to when parsing the calls in a pallet.
 mod keyword {
 	syn::custom_keyword!(T);
-	syn::custom_keyword!(AccountId);
+	syn::custom_keyword!(RuntimeOrigin);
+	syn::custom_keyword!(runtime);
 }
 
 /// This object will collect all the information we need to keep while parsing the callable
@@ -23,12 +24,29 @@ pub struct CallDef {
 pub struct CallVariantDef {
 	/// The function name.
 	pub name: syn::Ident,
+	/// What the function is called on: its pallet, or the whole runtime.
+	pub receiver: CallReceiver,
 	/// Information on args of the function: `(name, type)`.
 	pub args: Vec<(syn::Ident, Box<syn::Type>)>,
+	/// The index of the call in the encoding. This is the position of the function in the `impl`
+	/// block, unless it is set explicitly with `#[call_index(N)]`.
+	pub call_index: u8,
+	/// The expression given in `#[weight(..)]`, which computes the weight of the call. It can use
+	/// the arguments of the function.
+	pub weight: syn::Expr,
+}
+
+/// What a callable function is called on, from its first argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallReceiver {
+	/// A variant of `self`: the function only uses its own pallet.
+	Pallet,
+	/// `runtime: &mut T`: the function uses the whole runtime, for example to dispatch other calls.
+	Runtime,
 }
 
 impl CallDef {
-	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
 		// First we check that we are parsing an `impl`.
 		let item_impl = if let syn::Item::Impl(item) = item {
 			item
@@ -44,37 +62,63 @@ impl CallDef {
 		};
 
 		// Here is where we will store all the callable functions.
-		let mut methods = vec![];
-		for item in item_impl.items {
+		let mut methods: Vec<CallVariantDef> = vec![];
+		for item in item_impl.items.iter_mut() {
 			if let syn::ImplItem::Fn(method) = item {
 				// Here is where we will store all the args for each callable functions.
 				let mut args = vec![];
 
-				// First argument should be some variant of `self`.
-				match method.sig.inputs.first() {
-					Some(syn::FnArg::Receiver(_)) => {},
+				// First argument should be some variant of `self`, or `runtime: &mut T`.
+				let receiver = match method.sig.inputs.first() {
+					Some(syn::FnArg::Receiver(_)) => CallReceiver::Pallet,
+					Some(syn::FnArg::Typed(arg)) => {
+						check_runtime_arg(arg)?;
+						CallReceiver::Runtime
+					},
 					_ => {
-						let msg = "Invalid call, first argument must be a variant of self";
+						let msg = "Invalid call, first argument must be a variant of self, or \
+							`runtime: &mut T`";
 						return Err(syn::Error::new(method.sig.span(), msg))
 					},
-				}
+				};
 
-				// The second argument should be the `caller: T::AccountId` argument.
+				// The second argument should be the `origin: T::RuntimeOrigin` argument.
 				match method.sig.inputs.iter().skip(1).next() {
 					Some(syn::FnArg::Typed(arg)) => {
 						// Here we specifically check that this argument is as we expect for
-						// `caller: T::AccountId`.
-						check_caller_arg(arg)?;
+						// `origin: T::RuntimeOrigin`.
+						check_origin_arg(arg)?;
 					},
 					_ => {
-						let msg = "Invalid call, second argument should be `caller: T::AccountId`";
+						let msg =
+							"Invalid call, second argument should be `origin: T::RuntimeOrigin`";
 						return Err(syn::Error::new(method.sig.span(), msg))
 					},
 				}
 
 				let fn_name = method.sig.ident.clone();
 
-				// Parsing the rest of the args. Skipping 2 for `self` and `caller`.
+				// The call index is stable when it is set explicitly, otherwise it depends on the
+				// order of the functions.
+				let call_index = match take_call_index(&mut method.attrs)? {
+					Some(index) => index,
+					None => u8::try_from(methods.len()).map_err(|_| {
+						let msg = "Invalid pallet::call, too many calls";
+						syn::Error::new(method.sig.span(), msg)
+					})?,
+				};
+				if methods.iter().any(|other| other.call_index == call_index) {
+					let msg = format!("Invalid pallet::call, call index {call_index} is used twice");
+					return Err(syn::Error::new(method.sig.span(), msg))
+				}
+
+				// Every call must declare its weight, so it can be charged for.
+				let weight = take_weight(&mut method.attrs)?.ok_or_else(|| {
+					let msg = "Invalid pallet::call, missing `#[weight(..)]` attribute";
+					syn::Error::new(method.sig.span(), msg)
+				})?;
+
+				// Parsing the rest of the args. Skipping 2 for `self` (or `runtime`) and `origin`.
 				for arg in method.sig.inputs.iter().skip(2) {
 					// All arguments should be typed.
 					let arg = if let syn::FnArg::Typed(arg) = arg {
@@ -96,7 +140,7 @@ impl CallDef {
 				}
 
 				// Store all the function name and the arg data for the function.
-				methods.push(CallVariantDef { name: fn_name, args });
+				methods.push(CallVariantDef { name: fn_name, receiver, args, call_index, weight });
 			}
 		}
 
@@ -105,25 +149,75 @@ impl CallDef {
 	}
 }
 
-/// Check caller arg is exactly: `caller: T::AccountId`.
+/// Remove the `#[call_index(N)]` attribute from a function, and return its index if it was set.
+fn take_call_index(attrs: &mut Vec<syn::Attribute>) -> syn::Result<Option<u8>> {
+	let mut call_index = None;
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("call_index")) {
+		if call_index.is_some() {
+			return Err(syn::Error::new(attr.span(), "Invalid pallet::call, duplicate call_index"))
+		}
+		call_index = Some(attr.parse_args::<syn::LitInt>()?.base10_parse::<u8>()?);
+	}
+	attrs.retain(|attr| !attr.path().is_ident("call_index"));
+	Ok(call_index)
+}
+
+/// Remove the `#[weight(..)]` attribute from a function, and return its expression if it was set.
+fn take_weight(attrs: &mut Vec<syn::Attribute>) -> syn::Result<Option<syn::Expr>> {
+	let mut weight = None;
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("weight")) {
+		if weight.is_some() {
+			return Err(syn::Error::new(attr.span(), "Invalid pallet::call, duplicate weight"))
+		}
+		weight = Some(attr.parse_args::<syn::Expr>()?);
+	}
+	attrs.retain(|attr| !attr.path().is_ident("weight"));
+	Ok(weight)
+}
+
+/// Check runtime arg is exactly: `runtime: &mut T`.
+pub fn check_runtime_arg(arg: &syn::PatType) -> syn::Result<()> {
+	pub struct CheckRuntimeArg;
+	impl syn::parse::Parse for CheckRuntimeArg {
+		fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+			input.parse::<syn::Token![&]>()?;
+			input.parse::<syn::Token![mut]>()?;
+			input.parse::<keyword::T>()?;
+			Ok(Self)
+		}
+	}
+
+	let msg = "Invalid first parameter: expected a variant of self, or `runtime: &mut T`";
+	syn::parse2::<keyword::runtime>(arg.pat.to_token_stream())
+		.and_then(|_| syn::parse2::<CheckRuntimeArg>(arg.ty.to_token_stream()))
+		.map_err(|e| {
+			let mut err = syn::Error::new(arg.span(), msg);
+			err.combine(e);
+			err
+		})?;
+
+	Ok(())
+}
+
+/// Check origin arg is exactly: `origin: T::RuntimeOrigin`.
 ///
 /// This is kept strict to keep the code simple.
-pub fn check_caller_arg(arg: &syn::PatType) -> syn::Result<()> {
+pub fn check_origin_arg(arg: &syn::PatType) -> syn::Result<()> {
 	pub struct CheckDispatchableFirstArg;
 	impl syn::parse::Parse for CheckDispatchableFirstArg {
 		fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
 			input.parse::<keyword::T>()?;
 			input.parse::<syn::Token![::]>()?;
-			input.parse::<keyword::AccountId>()?;
+			input.parse::<keyword::RuntimeOrigin>()?;
 			Ok(Self)
 		}
 	}
 
-	// This checks the arg name is `caller` or `_caller`.
+	// This checks the arg name is `origin` or `_origin`.
 	if let syn::Pat::Ident(ident) = &*arg.pat {
-		// We also support the name as `_caller` for when the variable is unused.
-		if &ident.ident != "caller" && &ident.ident != "_caller" {
-			let msg = "Invalid name for second parameter: expected `caller: T::AccountId`";
+		// We also support the name as `_origin` for when the variable is unused.
+		if &ident.ident != "origin" && &ident.ident != "_origin" {
+			let msg = "Invalid name for second parameter: expected `origin: T::RuntimeOrigin`";
 			return Err(syn::Error::new(ident.span(), msg))
 		}
 	}
@@ -131,7 +225,7 @@ pub fn check_caller_arg(arg: &syn::PatType) -> syn::Result<()> {
 	// This checks the type is `T::AccountId` with `CheckDispatchableFirstArg`
 	let ty = &arg.ty;
 	syn::parse2::<CheckDispatchableFirstArg>(ty.to_token_stream()).map_err(|e| {
-		let msg = "Invalid type for second parameter: expected `caller: T::AccountId`";
+		let msg = "Invalid type for second parameter: expected `origin: T::RuntimeOrigin`";
 		let mut err = syn::Error::new(ty.span(), msg);
 		err.combine(e);
 		err
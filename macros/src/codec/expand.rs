@@ -0,0 +1,146 @@
+use super::parse::{CodecData, CodecDef};
+use quote::{format_ident, quote};
+
+/// See the `fn derive_encode` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_encode(def: CodecDef) -> proc_macro2::TokenStream {
+	let CodecDef { name, generics, data } = def;
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+	let where_bounds = where_bounds(&generics, where_clause, &data, quote!(crate::support::Encode));
+
+	// Each struct or variant is encoded as the concatenation of its fields, in order. Enum variants
+	// are prefixed with their index.
+	let body = match &data {
+		CodecData::Struct(fields) => {
+			let (pattern, bindings) = fields_pattern(fields);
+			quote! {
+				let Self #pattern = self;
+				#( crate::support::Encode::encode_to(#bindings, dest); )*
+			}
+		},
+		CodecData::Enum(variants) => {
+			let arms = variants.iter().map(|variant| {
+				let (variant_name, index) = (&variant.name, variant.index);
+				let (pattern, bindings) = fields_pattern(&variant.fields);
+				quote! {
+					Self::#variant_name #pattern => {
+						dest.push(#index);
+						#( crate::support::Encode::encode_to(#bindings, dest); )*
+					},
+				}
+			});
+			quote! {
+				match self {
+					#( #arms )*
+				}
+			}
+		},
+	};
+
+	quote! {
+		impl #impl_generics crate::support::Encode for #name #ty_generics #where_bounds {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				#body
+			}
+		}
+	}
+}
+
+/// See the `fn derive_decode` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_decode(def: CodecDef) -> proc_macro2::TokenStream {
+	let CodecDef { name, generics, data } = def;
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+	let where_bounds = where_bounds(&generics, where_clause, &data, quote!(crate::support::Decode));
+
+	// Fields are decoded in the same order they are encoded.
+	let body = match &data {
+		CodecData::Struct(fields) => {
+			let construct = fields_construct(fields);
+			quote! { Ok(Self #construct) }
+		},
+		CodecData::Enum(variants) => {
+			let arms = variants.iter().map(|variant| {
+				let (variant_name, index) = (&variant.name, variant.index);
+				let construct = fields_construct(&variant.fields);
+				quote! { #index => Ok(Self::#variant_name #construct), }
+			});
+			quote! {
+				match crate::support::codec::read_byte(input)? {
+					#( #arms )*
+					_ => Err(crate::support::codec::Error("Invalid enum variant")),
+				}
+			}
+		},
+	};
+
+	quote! {
+		impl #impl_generics crate::support::Decode for #name #ty_generics #where_bounds {
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+				Self::decode_with_depth_limit(crate::support::codec::MAX_DEPTH, input)
+			}
+
+			// Each struct or enum is one level of nesting, and its fields are decoded one level
+			// deeper.
+			fn decode_with_depth_limit(
+				depth_limit: u32,
+				input: &mut &[u8],
+			) -> Result<Self, crate::support::codec::Error> {
+				let depth_limit = crate::support::codec::enter_nested(depth_limit)?;
+				#body
+			}
+		}
+	}
+}
+
+// We require the trait on the types of the fields, rather than on the generic parameters of the
+// type, the same way `no_bound` does. Types without generic parameters need no bounds at all, which
+// matters for recursive types like a `RuntimeCall` holding a boxed `RuntimeCall`: bounding on the
+// fields would make the compiler go around in circles.
+fn where_bounds(
+	generics: &syn::Generics,
+	where_clause: Option<&syn::WhereClause>,
+	data: &CodecData,
+	bound: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+	if generics.params.is_empty() {
+		return quote! { #where_clause }
+	}
+	let predicates = where_clause.map(|w| w.predicates.clone()).into_iter().flatten();
+	let field_types = match data {
+		CodecData::Struct(fields) => fields.iter().map(|field| field.ty.clone()).collect::<Vec<_>>(),
+		CodecData::Enum(variants) => variants
+			.iter()
+			.flat_map(|variant| variant.fields.iter().map(|field| field.ty.clone()))
+			.collect(),
+	};
+	quote! { where #( #predicates, )* #( #field_types: #bound ),* }
+}
+
+// A pattern binding all the fields, like `{ a: a0, b: a1 }` or `(a0, a1)`, and the bindings.
+fn fields_pattern(fields: &syn::Fields) -> (proc_macro2::TokenStream, Vec<syn::Ident>) {
+	let bindings = (0..fields.len()).map(|i| format_ident!("a{}", i)).collect::<Vec<_>>();
+	let pattern = match fields {
+		syn::Fields::Named(fields) => {
+			let field_names = fields.named.iter().map(|f| f.ident.clone().unwrap());
+			quote! { { #( #field_names: #bindings ),* } }
+		},
+		syn::Fields::Unnamed(_) => quote! { ( #( #bindings ),* ) },
+		syn::Fields::Unit => quote! {},
+	};
+	(pattern, bindings)
+}
+
+// An expression building the fields by decoding each of them, like `{ a: decode(input)? }`.
+fn fields_construct(fields: &syn::Fields) -> proc_macro2::TokenStream {
+	let decode = quote! { crate::support::Decode::decode_with_depth_limit(depth_limit, input)? };
+	match fields {
+		syn::Fields::Named(fields) => {
+			let field_names = fields.named.iter().map(|f| f.ident.clone().unwrap());
+			quote! { { #( #field_names: #decode ),* } }
+		},
+		syn::Fields::Unnamed(fields) => {
+			let decodes = fields.unnamed.iter().map(|_| &decode);
+			quote! { ( #( #decodes ),* ) }
+		},
+		syn::Fields::Unit => quote! {},
+	}
+}
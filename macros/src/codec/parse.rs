@@ -0,0 +1,84 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing a type deriving
+/// `Encode` or `Decode`.
+#[derive(Debug)]
+pub struct CodecDef {
+	/// The name of the type.
+	pub name: syn::Ident,
+	/// The generics of the type.
+	pub generics: syn::Generics,
+	/// The shape of the type. See `CodecData`.
+	pub data: CodecData,
+}
+
+/// The shape of a type deriving `Encode` or `Decode`.
+#[derive(Debug)]
+pub enum CodecData {
+	/// A struct, which is encoded as the concatenation of its fields.
+	Struct(syn::Fields),
+	/// An enum, where each variant is encoded as its index followed by its fields.
+	Enum(Vec<CodecVariantDef>),
+}
+
+/// This is the metadata we keep about each variant of an enum.
+#[derive(Debug)]
+pub struct CodecVariantDef {
+	/// The variant name.
+	pub name: syn::Ident,
+	/// The fields of the variant, which can be named, unnamed or unit.
+	pub fields: syn::Fields,
+	/// The index of the variant in the encoding. This is the position of the variant in the enum,
+	/// unless it is set explicitly with `#[codec(index = N)]`.
+	pub index: u8,
+}
+
+impl CodecDef {
+	pub fn try_from(input: syn::DeriveInput) -> syn::Result<Self> {
+		let data = match input.data {
+			syn::Data::Struct(data) => CodecData::Struct(data.fields),
+			syn::Data::Enum(data) => {
+				let mut variants: Vec<CodecVariantDef> = vec![];
+				for (position, variant) in data.variants.into_iter().enumerate() {
+					let index = match parse_index(&variant.attrs)? {
+						Some(index) => index,
+						None => u8::try_from(position).map_err(|_| {
+							let msg = "Invalid codec enum, too many variants";
+							syn::Error::new(variant.span(), msg)
+						})?,
+					};
+					// Two variants with the same index could not be told apart when decoding.
+					if variants.iter().any(|other| other.index == index) {
+						let msg = format!("Invalid codec enum, index {index} is used twice");
+						return Err(syn::Error::new(variant.span(), msg))
+					}
+					variants.push(CodecVariantDef { name: variant.ident, fields: variant.fields, index });
+				}
+				CodecData::Enum(variants)
+			},
+			syn::Data::Union(data) => {
+				let msg = "Invalid codec type, unions are not supported";
+				return Err(syn::Error::new(data.union_token.span(), msg))
+			},
+		};
+
+		Ok(Self { name: input.ident, generics: input.generics, data })
+	}
+}
+
+/// Parse the `#[codec(index = N)]` attribute of an enum variant, if any.
+fn parse_index(attrs: &[syn::Attribute]) -> syn::Result<Option<u8>> {
+	let mut index = None;
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("codec")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("index") {
+				let value: syn::LitInt = meta.value()?.parse()?;
+				index = Some(value.base10_parse::<u8>()?);
+				Ok(())
+			} else {
+				Err(meta.error("Invalid codec attribute, expected `index = N`"))
+			}
+		})?;
+	}
+	Ok(index)
+}
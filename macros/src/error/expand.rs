@@ -0,0 +1,32 @@
+use super::parse::ErrorDef;
+use quote::quote;
+
+/// See the `fn error` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_error(def: ErrorDef) -> proc_macro2::TokenStream {
+	let ErrorDef { error_enum, variants } = def;
+
+	// The error index of each variant is simply its position in the enum.
+	let error_index = (0..variants.len()).map(|i| i as u8).collect::<Vec<_>>();
+	// The name of each variant, used as a human readable message.
+	let error_message = variants.iter().map(|variant| variant.to_string()).collect::<Vec<_>>();
+
+	// This quote block converts the pallet error into a `DispatchError`. The pallet index is left
+	// empty, and is filled in by the runtime when it dispatches a call to this pallet.
+	let from_impl = quote! {
+		impl From<#error_enum> for crate::support::DispatchError {
+			fn from(error: #error_enum) -> Self {
+				let (error, message) = match error {
+					#( #error_enum::#variants => (#error_index, #error_message), )*
+				};
+				crate::support::DispatchError::Module(crate::support::ModuleError {
+					index: None,
+					error,
+					message: Some(message),
+				})
+			}
+		}
+	};
+
+	// Return the generated code.
+	from_impl
+}
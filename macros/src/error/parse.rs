@@ -0,0 +1,41 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the `Error` enum of
+/// a pallet.
+#[derive(Debug)]
+pub struct ErrorDef {
+	/// This is the name of the error enum. We mostly assume it is `Error`.
+	pub error_enum: syn::Ident,
+	/// This is the list of variants of the enum, in declaration order. The position of a variant in
+	/// this list is its error index.
+	pub variants: Vec<syn::Ident>,
+}
+
+impl ErrorDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::error, expected item enum"))
+		};
+
+		// The error index is stored in a `u8`, so we can't have more variants than that.
+		if item_enum.variants.len() > u8::MAX as usize {
+			let msg = "Invalid pallet::error, too many variants";
+			return Err(syn::Error::new(item_enum.span(), msg))
+		}
+
+		let mut variants = vec![];
+		for variant in item_enum.variants {
+			// We keep errors simple: they can't carry any data.
+			if !matches!(variant.fields, syn::Fields::Unit) {
+				let msg = "Invalid pallet::error, variants must not have fields";
+				return Err(syn::Error::new(variant.fields.span(), msg))
+			}
+			variants.push(variant.ident);
+		}
+
+		Ok(Self { error_enum: item_enum.ident, variants })
+	}
+}
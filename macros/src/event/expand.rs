@@ -0,0 +1,15 @@
+use super::parse::EventDef;
+
+/// See the `fn event` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_event(def: EventDef) -> proc_macro2::TokenStream {
+	let EventDef { event_enum, generics, variants } = def;
+
+	let variants = variants
+		.into_iter()
+		.map(|variant| (variant.name, variant.fields))
+		.collect::<Vec<_>>();
+
+	// Events are stored by the system pallet and compared in tests, so they need to implement
+	// `Clone`, `PartialEq` and `Debug`.
+	crate::no_bound::expand_no_bound_impls(&event_enum, &generics, &variants)
+}
@@ -0,0 +1,41 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the `Event` enum of
+/// a pallet.
+#[derive(Debug)]
+pub struct EventDef {
+	/// This is the name of the event enum. We mostly assume it is `Event`.
+	pub event_enum: syn::Ident,
+	/// The generics of the event enum, usually `<T: Config>`.
+	pub generics: syn::Generics,
+	/// This is the list of variants of the enum. See `EventVariantDef`.
+	pub variants: Vec<EventVariantDef>,
+}
+
+/// This is the metadata we keep about each variant of the event enum.
+#[derive(Debug)]
+pub struct EventVariantDef {
+	/// The variant name.
+	pub name: syn::Ident,
+	/// The fields of the variant, which can be named, unnamed or unit.
+	pub fields: syn::Fields,
+}
+
+impl EventDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::event, expected item enum"))
+		};
+
+		let variants = item_enum
+			.variants
+			.into_iter()
+			.map(|variant| EventVariantDef { name: variant.ident, fields: variant.fields })
+			.collect();
+
+		Ok(Self { event_enum: item_enum.ident, generics: item_enum.generics, variants })
+	}
+}
@@ -1,6 +1,32 @@
 mod call;
+mod codec;
+mod error;
+mod event;
+mod no_bound;
 mod runtime;
+mod storage;
 
+/// Expand the callable functions of a pallet.
+///
+/// Every callable function takes `&mut self` and `origin: T::RuntimeOrigin` as its first two
+/// arguments. Functions check who is calling with `system::ensure_signed` or `system::ensure_root`.
+/// A function which needs the whole runtime, for example to dispatch other calls, takes
+/// `runtime: &mut T` instead of `&mut self`.
+///
+/// This generates:
+/// - `enum Call` - an enum with one variant per callable function, holding the arguments of the
+///   function, except `self`, `runtime` and the `origin`. It implements `Clone`, `PartialEq` and
+///   `Debug`, and `support::Encode` and `support::Decode`, where each call is encoded as its call
+///   index followed by its arguments. The call index is the position of the function in the
+///   `impl` block, unless it is set with `#[call_index(N)]`, which keeps the encoding stable when
+///   calls are added or reordered.
+/// - implements the trait `support::GetWeight` for the `Call` enum. Every callable function must
+///   declare its weight with `#[weight(expr)]`, where the expression can use the arguments of the
+///   function by reference.
+/// - implements the trait `support::Dispatchable` for the `Call` enum, which routes each variant to
+///   the matching function, on the pallet or on the runtime holding it. Functions can return a
+///   `support::DispatchResult`, or a `support::DispatchResultWithPostInfo` to report that they
+///   used less than their declared weight.
 #[proc_macro_attribute]
 pub fn call(
 	attr: proc_macro::TokenStream,
@@ -9,22 +35,134 @@ pub fn call(
 	call::call(attr, item)
 }
 
+/// Derive `support::Encode` for a struct or an enum.
+///
+/// A struct is encoded as the concatenation of its fields, in declaration order. An enum variant is
+/// encoded as its index, as a single byte, followed by its fields. The index of a variant is its
+/// position in the enum, unless it is set with `#[codec(index = N)]`, which keeps the encoding
+/// stable when variants are added or reordered.
+///
+/// The implementation only requires the types of the fields to implement `Encode`, and not the
+/// generic parameters of the type.
+#[proc_macro_derive(Encode, attributes(codec))]
+pub fn derive_encode(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	codec::derive_encode(item)
+}
+
+/// Derive `support::Decode` for a struct or an enum.
+///
+/// This reads back the encoding generated by `#[derive(Encode)]`, with the same `#[codec(index)]`
+/// attributes. Decoding an enum fails if the index does not match any of its variants. Each struct
+/// or enum counts as one level towards the depth limit of `Decode::decode_with_depth_limit`.
+#[proc_macro_derive(Decode, attributes(codec))]
+pub fn derive_decode(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	codec::derive_decode(item)
+}
+
+/// Expand the `Error` enum of a pallet.
+///
+/// This generates a `From<Error> for support::DispatchError` implementation, so pallet calls can
+/// return their own errors with `Err(Error::SomeVariant.into())` or the `?` operator. Each variant
+/// becomes a `support::ModuleError`, where the error index is the position of the variant in the
+/// enum. The pallet index is filled in by the runtime when the call is dispatched.
+#[proc_macro_attribute]
+pub fn error(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	error::error(attr, item)
+}
+
+/// Expand the `Event` enum of a pallet.
+///
+/// This implements `Clone`, `PartialEq` and `Debug` for the event. Unlike the standard derives,
+/// these implementations only require the types of the fields to implement those traits, and not
+/// the generic `T: Config`, which is usually the `Runtime` itself.
+#[proc_macro_attribute]
+pub fn event(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	event::event(attr, item)
+}
+
+/// Declare a storage item of a pallet.
+///
+/// A storage item is a type alias of one of the storage types of `support`, whose first generic
+/// argument is `_`, for example:
+///
+/// ```ignore
+/// #[macros::storage]
+/// type Balances<T: Config> = StorageMap<_, T::AccountId, T::Balance>;
+/// ```
+///
+/// This generates a `{Name}Prefix` type, which replaces the `_`, and implements
+/// `support::StoragePrefix`. Every key of the item starts with the hash of the name of the pallet,
+/// which is the module the item is declared in, followed by the hash of the name of the alias. The
+/// pallet creates a handle to the item with `new(storage)`, which reads and writes the storage
+/// shared by all the pallets of the runtime.
+#[proc_macro_attribute]
+pub fn storage(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	storage::storage(attr, item)
+}
+
 /// Expand the `Runtime` definition.
 ///
 /// This generates function implementations on `Runtime`:
-/// - `fn new()` - which generates a new instance of the runtime, by instantiating all the pallets
-///   included in the runtime.
+/// - `fn new()` - which generates a new instance of the runtime, with an empty storage kept in
+///   memory.
+/// - `fn with_storage()` - which generates a new instance of the runtime on top of the state of a
+///   `support::Storage`, by instantiating all the pallets included in the runtime over it. Every
+///   pallet must declare a `fn new(storage: &Storage)`.
+/// - `fn from_genesis()` - which generates a new instance of the runtime, and builds the genesis
+///   state of every pallet from a `RuntimeGenesisConfig`. The hash of the genesis header is stored
+///   in the system pallet, as the genesis hash and as the parent of the first block. It fails if
+///   the genesis config of a pallet is invalid.
 /// - `fn execute_block()` - which handles basic logic for executing a block of extrinsics. It does
-///   basic actions like incrementing the block number and checking the block to be executed has a
-///   valid block number.
+///   basic actions like incrementing the block number and checking the header of the block: its
+///   block number, its parent hash, its extrinsics root, and the state root after execution.
+///   Every extrinsic is dispatched in its own storage transaction, which is reverted if the call
+///   fails. The signature and the nonce of each extrinsic are verified before it is dispatched,
+///   and the whole block is invalid, and reverted, if any signature or nonce is wrong or if any
+///   check on the header fails. Signed extrinsics are dispatched from the `Signed` origin of their
+///   signer, and unsigned extrinsics from the `None` origin. An unsigned extrinsic pays no fee, so
+///   the block is invalid if its call fails. The declared weight of each extrinsic must fit in the
+///   block, and its fee is charged through `support::ChargeTransaction`, which the runtime must
+///   implement. The fee for the weight the call did not use is refunded after it is dispatched.
+/// - `fn build_block()` - which creates the next block out of a list of extrinsics, with a header
+///   matching what `execute_block` expects. The state is left untouched.
+/// - `fn state_root()` - which is the root of the trie of the storage shared by all the pallets.
+///   The `Hash` of the system pallet must be `support::H256`, the output of the trie.
+/// - `impl support::Transactional` - which opens, commits and reverts transactions on the storage
+///   shared by all the pallets, through the system pallet.
+/// - `struct RuntimeGenesisConfig` - which holds the `GenesisConfig` of every pallet, including
+///   system. Every pallet must declare a `GenesisConfig<T>` which implements `Default` and
+///   `support::BuildGenesisConfig`.
 ///
 /// This also generates code needed for dispatching calls to the pallets:
 /// - Note: For simplicity, we assume that the system pallet is not callable.
 /// - `enum RuntimeCall` - an "outer"-enum representing the accumulation of all possible calls to
-///   all pallets. The system pallet is not included.
-/// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet. Basic
-///   logic like incrementing the nonce of the user is included in the generated code. The system
-///   pallet is not included.
+///   all pallets. The system pallet is not included. A runtime call is encoded as the index of the
+///   pallet, followed by the encoded pallet call. It implements `support::Encode` and
+///   `support::Decode`.
+/// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet, from
+///   any `RuntimeOrigin`, including `Root` for privileged calls. The system pallet is not included.
+///   Module errors returned by a pallet are tagged with the index of that pallet in the `Runtime`
+///   struct, `system` being `0`. After each call, the runtime cleans up the accounts reaped by the
+///   pallets through `support::OnReapedAccounts`, which the runtime must implement.
+/// - implements `support::Hooks` by calling the hooks of every pallet, including system, in the
+///   order of the `Runtime` struct. Every pallet must implement `support::Hooks`. `on_initialize`
+///   runs before the extrinsics of a block, and `on_idle` then `on_finalize` after them.
+///   `on_runtime_upgrade` is only called by whoever upgrades the runtime.
+/// - `enum RuntimeEvent` - an "outer"-enum representing the accumulation of all the events of all
+///   pallets, including system. After each call, the events deposited by the pallet are moved to
+///   the event log of the system pallet.
+/// - implements `AsMut` for every pallet, so the calls of a pallet can be dispatched on the
+///   runtime, and reach their pallet from there. The system pallet is only available through
+///   `AsRef`.
 #[proc_macro_attribute]
 pub fn runtime(
 	attr: proc_macro::TokenStream,
@@ -0,0 +1,114 @@
+use quote::{format_ident, quote};
+
+/// Implement `Clone`, `PartialEq` and `Debug` for an enum.
+///
+/// Unlike the standard derives, these implementations only require the types of the fields to
+/// implement those traits, and not the generic parameters of the enum. This matters for our pallet
+/// types, which are generic over `T: Config`, where `T` is usually the `Runtime` itself, which does
+/// not implement those traits.
+pub fn expand_no_bound_impls(
+	enum_name: &syn::Ident,
+	generics: &syn::Generics,
+	variants: &[(syn::Ident, syn::Fields)],
+) -> proc_macro2::TokenStream {
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// This is a vector of all the field types used in the enum. We require the traits we implement
+	// on these types, rather than on the generic `T`, which is usually the runtime itself.
+	let field_types = variants
+		.iter()
+		.flat_map(|(_, fields)| fields.iter().map(|field| field.ty.clone()))
+		.collect::<Vec<_>>();
+	let predicates = where_clause.map(|w| w.predicates.clone()).into_iter().flatten();
+	let where_bounds = |bound: proc_macro2::TokenStream| {
+		let predicates = predicates.clone();
+		quote! { where #( #predicates, )* #( #field_types: #bound ),* }
+	};
+	let clone_bounds = where_bounds(quote!(Clone));
+	let eq_bounds = where_bounds(quote!(PartialEq));
+	let debug_bounds = where_bounds(quote!(core::fmt::Debug));
+
+	// For each variant, we generate a pattern binding all of its fields, with two different set of
+	// names so we can compare two values together.
+	let mut clone_arms = vec![];
+	let mut eq_arms = vec![];
+	let mut debug_arms = vec![];
+	for (name, fields) in variants {
+		let name_str = name.to_string();
+		let bindings = (0..fields.len()).map(|i| format_ident!("a{}", i)).collect::<Vec<_>>();
+		let others = (0..fields.len()).map(|i| format_ident!("b{}", i)).collect::<Vec<_>>();
+
+		match fields {
+			syn::Fields::Named(fields) => {
+				let field_names =
+					fields.named.iter().map(|f| f.ident.clone().unwrap()).collect::<Vec<_>>();
+				let field_strs = field_names.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+				clone_arms.push(quote! {
+					Self::#name { #( #field_names: #bindings ),* } =>
+						Self::#name { #( #field_names: #bindings.clone() ),* },
+				});
+				eq_arms.push(quote! {
+					(Self::#name { #( #field_names: #bindings ),* }, Self::#name { #( #field_names: #others ),* }) =>
+						true #( && #bindings == #others )*,
+				});
+				debug_arms.push(quote! {
+					Self::#name { #( #field_names: #bindings ),* } =>
+						f.debug_struct(#name_str) #( .field(#field_strs, #bindings) )* .finish(),
+				});
+			},
+			syn::Fields::Unnamed(_) => {
+				clone_arms.push(quote! {
+					Self::#name( #( #bindings ),* ) => Self::#name( #( #bindings.clone() ),* ),
+				});
+				eq_arms.push(quote! {
+					(Self::#name( #( #bindings ),* ), Self::#name( #( #others ),* )) =>
+						true #( && #bindings == #others )*,
+				});
+				debug_arms.push(quote! {
+					Self::#name( #( #bindings ),* ) =>
+						f.debug_tuple(#name_str) #( .field(#bindings) )* .finish(),
+				});
+			},
+			syn::Fields::Unit => {
+				clone_arms.push(quote! { Self::#name => Self::#name, });
+				eq_arms.push(quote! { (Self::#name, Self::#name) => true, });
+				debug_arms.push(quote! { Self::#name => f.write_str(#name_str), });
+			},
+		}
+	}
+
+	// Two values of different variants are never equal. We only need this arm if there are at
+	// least two variants, otherwise the compiler complains about an unreachable pattern.
+	let eq_fallback = if variants.len() > 1 { quote! { _ => false, } } else { quote! {} };
+
+	// This quote block implements `Clone`, `PartialEq` and `Debug` for the enum.
+	let no_bound_impl = quote! {
+		impl #impl_generics Clone for #enum_name #ty_generics #clone_bounds {
+			fn clone(&self) -> Self {
+				match self {
+					#( #clone_arms )*
+				}
+			}
+		}
+
+		impl #impl_generics PartialEq for #enum_name #ty_generics #eq_bounds {
+			fn eq(&self, other: &Self) -> bool {
+				match (self, other) {
+					#( #eq_arms )*
+					#eq_fallback
+				}
+			}
+		}
+
+		impl #impl_generics core::fmt::Debug for #enum_name #ty_generics #debug_bounds {
+			fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+				match self {
+					#( #debug_arms )*
+				}
+			}
+		}
+	};
+
+	// Return the generated code.
+	no_bound_impl
+}
@@ -9,75 +9,454 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 	let pallet_names = pallets.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
 	// This is a vector of all the pallet types, not including system.
 	let pallet_types = pallets.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>();
+	// This is a vector of the index of each pallet in the `Runtime` struct. System is always the
+	// first pallet, so the other pallets start at `1`.
+	let pallet_indices = (1..=pallets.len()).map(|i| i as u8).collect::<Vec<_>>();
 
 	// This quote block implements functions on the `Runtime` struct.
 	let runtime_impl = quote! {
 		impl #runtime_struct {
-			// Create a new instance of the main Runtime, by creating a new instance of each pallet.
+			// Create a new instance of the main Runtime, with an empty storage kept in memory.
 			fn new() -> Self {
+				Self::with_storage(&crate::support::Storage::new())
+			}
+
+			// Create a new instance of the main Runtime on top of the state in `storage`, by
+			// creating a new instance of each pallet, all of them sharing the same storage.
+			fn with_storage(storage: &crate::support::Storage) -> Self {
 				Self {
 					// Since system is not included in the list of pallets, we manually add it here.
-					system: <system::Pallet::<Self>>::new(),
+					system: <system::Pallet::<Self>>::new(storage),
 					#(
-						#pallet_names: <#pallet_types>::new()
+						#pallet_names: <#pallet_types>::new(storage)
 					),*
 				}
 			}
 
+			// Create a new instance of the main Runtime, with the genesis state described by
+			// `config`. The genesis block is the header of this state, which the first block is
+			// built on top of.
+			//
+			// This fails if the configuration of a pallet is invalid. Module errors are tagged with
+			// the index of the pallet, like the errors of calls.
+			fn from_genesis(
+				config: &RuntimeGenesisConfig,
+			) -> Result<Self, crate::support::DispatchError> {
+				let mut runtime = Self::new();
+				let n = config.system.block_number;
+				crate::support::BuildGenesisConfig::build(&config.system, &mut runtime.system, n)
+					.map_err(|e| e.in_pallet(0))?;
+				#(
+					crate::support::BuildGenesisConfig::build(
+						&config.#pallet_names,
+						&mut runtime.#pallet_names,
+						n,
+					)
+					.map_err(|e| e.in_pallet(#pallet_indices))?;
+				)*
+				let genesis_header = runtime.genesis_header();
+				runtime.system.set_genesis_hash(
+					genesis_header.hash::<<Self as system::Config>::Hashing>(),
+				);
+				Ok(runtime)
+			}
+
+			// The header of the genesis block: the block with no extrinsics, which commits to the
+			// genesis state. It has no parent, so its parent hash is the default hash.
+			fn genesis_header(&self) -> types::Header {
+				support::Header {
+					parent_hash: Default::default(),
+					block_number: self.system.block_number(),
+					state_root: self.state_root(),
+					extrinsics_root: Self::extrinsics_root(&[]),
+				}
+			}
+
 			// Execute a block of extrinsics. Increments the block number.
+			//
+			// The block is executed in a transaction, so an invalid block leaves no changes behind.
 			fn execute_block(&mut self, block: types::Block) -> crate::support::DispatchResult {
-				self.system.inc_block_number();
-				if block.header.block_number != self.system.block_number() {
-					return Err(&"block number does not match what is expected")
+				crate::support::with_transaction(self, |runtime| runtime.apply_block(block))
+			}
+
+			// Build the next block out of some extrinsics, on top of the last executed block.
+			//
+			// The extrinsics are executed to find the resulting state root, and then reverted, so
+			// the block can be imported with `execute_block`. This fails if any of the extrinsics
+			// is invalid, for example if its signature is wrong.
+			fn build_block(
+				&mut self,
+				extrinsics: Vec<types::Extrinsic>,
+			) -> Result<types::Block, crate::support::DispatchError> {
+				let parent_hash = self.system.parent_hash();
+				let extrinsics_root = Self::extrinsics_root(&extrinsics);
+
+				crate::support::Transactional::start_transaction(self);
+				let res = self.system.inc_block_number().map_err(Self::system_error).and_then(|()| {
+					self.apply_extrinsics(extrinsics.clone())
+				});
+				let block_number = self.system.block_number();
+				let state_root = self.state_root();
+				crate::support::Transactional::rollback_transaction(self);
+				res?;
+
+				let header = support::Header { parent_hash, block_number, state_root, extrinsics_root };
+				Ok(support::Block { header, extrinsics })
+			}
+
+			// Check the header of the block against the current state, and apply its extrinsics.
+			fn apply_block(&mut self, block: types::Block) -> crate::support::DispatchResult {
+				let support::Block { header, extrinsics } = block;
+
+				self.system.inc_block_number().map_err(Self::system_error)?;
+				if header.block_number != self.system.block_number() {
+					return Err(crate::support::DispatchError::Other(
+						"block number does not match what is expected",
+					))
+				}
+				if header.parent_hash != self.system.parent_hash() {
+					return Err(crate::support::DispatchError::Other(
+						"parent hash does not match the last block",
+					))
+				}
+				if header.extrinsics_root != Self::extrinsics_root(&extrinsics) {
+					return Err(crate::support::DispatchError::Other(
+						"extrinsics root does not match the extrinsics of the block",
+					))
+				}
+
+				self.apply_extrinsics(extrinsics)?;
+
+				if header.state_root != self.state_root() {
+					return Err(crate::support::DispatchError::Other(
+						"state root does not match the state after the block",
+					))
 				}
-				for (i, support::Extrinsic { caller, call }) in block.extrinsics.into_iter().enumerate() {
-					self.system.inc_nonce(&caller);
-					let _res = self.dispatch(caller, call).map_err(|e| {
-						eprintln!(
-							"Extrinsic Error\n\tBlock Number: {}\n\tExtrinsic Number: {}\n\tError: {}",
-							block.header.block_number, i, e
-						)
+				let hash = header.hash::<<Self as system::Config>::Hashing>();
+				self.system.set_parent_hash(hash);
+				Ok(())
+			}
+
+			// Apply each extrinsic of a block, in order.
+			//
+			// A failed call does not make the block invalid, but an invalid extrinsic does.
+			fn apply_extrinsics(
+				&mut self,
+				extrinsics: Vec<types::Extrinsic>,
+			) -> crate::support::DispatchResult {
+				let genesis_hash = self.system.genesis_hash();
+
+				// The event log and the weight only cover the current block.
+				self.system.initialize_block();
+				// The start of block work of the pallets always runs, so its weight is taken from
+				// the block even if it is over the limit.
+				let block_number = self.system.block_number();
+				let weight =
+					<Self as crate::support::Hooks<_, _>>::on_initialize(self, block_number);
+				self.system.register_mandatory_weight(weight);
+				self.collect_events();
+				for (i, extrinsic) in extrinsics.into_iter().enumerate() {
+					self.system.note_extrinsic(i as u32);
+					// The signature is verified before anything is dispatched.
+					let crate::support::CheckedExtrinsic { signed, call } = extrinsic
+						.check(&genesis_hash)
+						.ok_or(Self::system_error(system::Error::BadSignature))?;
+					// Signed extrinsics are dispatched from the origin of their signer, and
+					// unsigned extrinsics from the `None` origin.
+					let origin = match &signed {
+						Some((caller, nonce)) => {
+							// The nonce must be the next one of the caller, so an extrinsic can't
+							// be replayed, or applied out of order.
+							self.system.check_nonce(caller, *nonce).map_err(Self::system_error)?;
+							self.system.inc_nonce(caller).map_err(Self::system_error)?;
+							system::RawOrigin::Signed(caller.clone())
+						},
+						None => system::RawOrigin::None,
+					};
+					// The block must have room for the declared weight of the call, and the signer
+					// must be able to pay for it.
+					let weight = crate::support::GetWeight::weight(&call);
+					self.system.register_weight(weight).map_err(Self::system_error)?;
+					if let Some((caller, _)) = &signed {
+						crate::support::ChargeTransaction::withdraw_fee(self, caller, weight)
+							.map_err(|_| Self::system_error(system::Error::CannotPayFees))?;
+					}
+					// Each extrinsic is dispatched in its own transaction, so a failed call leaves
+					// no changes behind. The nonce increment and the fee above are kept either way.
+					let res = crate::support::with_transaction(self, |runtime| {
+						runtime.dispatch(origin.into(), call)
 					});
+					// The weight the call did not use is given back to the block, and refunded to
+					// the signer.
+					let actual_weight = crate::support::extract_actual_weight(&res, weight);
+					self.system.refund_weight(weight - actual_weight);
+					match &signed {
+						Some((caller, _)) => crate::support::ChargeTransaction::refund_fee(
+							self,
+							caller,
+							weight,
+							actual_weight,
+						),
+						// Nobody pays for an unsigned extrinsic, so it can only be included if its
+						// call succeeds. Otherwise anyone could fill blocks for free.
+						None if res.is_err() => {
+							return Err(Self::system_error(system::Error::UnsignedNotAllowed))
+						},
+						None => {},
+					}
+					self.collect_events();
+					// The outcome is recorded in the events, rather than printed, since blocks are
+					// executed again whenever the node restarts.
+					match res {
+						Ok(_) => self.system.deposit_event(system::Event::ExtrinsicSuccess),
+						Err(error) => {
+							let error = error.error;
+							self.system.deposit_event(system::Event::ExtrinsicFailed { error });
+						},
+					}
 				}
+				self.system.note_finished_extrinsics();
+				// The end of block work of the pallets, which is part of the state root. The weight
+				// the extrinsics left is offered to the pallets first.
+				let remaining_weight = <Self as system::Config>::MAX_BLOCK_WEIGHT
+					.saturating_sub(self.system.block_weight());
+				let weight = <Self as crate::support::Hooks<_, _>>::on_idle(
+					self,
+					block_number,
+					remaining_weight,
+				);
+				self.system.register_mandatory_weight(weight);
+				<Self as crate::support::Hooks<_, _>>::on_finalize(self, block_number);
+				self.collect_events();
 				Ok(())
 			}
+
+			// Move the events deposited by the pallets outside of a call, like when fees are
+			// charged, to the event log of the system pallet.
+			fn collect_events(&mut self) {
+				#(
+					for event in self.#pallet_names.take_events() {
+						self.system.deposit_event(RuntimeEvent::#pallet_names(event));
+					}
+				)*
+			}
+
+			// Errors of the system pallet make the block invalid. System is always the first pallet
+			// of the runtime.
+			fn system_error(error: system::Error) -> crate::support::DispatchError {
+				crate::support::DispatchError::from(error).in_pallet(0)
+			}
+
+			// The root of the trie of the storage shared by all the pallets.
+			fn state_root(&self) -> <Self as system::Config>::Hash {
+				self.system.storage().root()
+			}
+
+			// The hash of all the extrinsics of a block.
+			fn extrinsics_root(extrinsics: &[types::Extrinsic]) -> <Self as system::Config>::Hash {
+				<<Self as system::Config>::Hashing as crate::support::Hasher>::hash_of(extrinsics)
+			}
 		}
 	};
 
-	// This quote block implements the `RuntimeCall` enum and implements the `Dispatch` trait.
+	// This quote block aggregates the genesis configuration of every pallet, including system.
+	let genesis_config = quote! {
+		// The genesis configuration of the whole runtime, with one field per pallet.
+		#[derive(Default)]
+		pub struct RuntimeGenesisConfig {
+			pub system: system::GenesisConfig<#runtime_struct>,
+			#( pub #pallet_names: #pallet_names::GenesisConfig<#runtime_struct> ),*
+		}
+	};
+
+	// This quote block makes the whole runtime transactional. The state of every pallet lives in
+	// the storage they share, which the system pallet opens, commits and reverts transactions on,
+	// along with its own values which are not in the storage.
+	let transactional_impl = quote! {
+		impl crate::support::Transactional for #runtime_struct {
+			fn start_transaction(&mut self) {
+				crate::support::Transactional::start_transaction(&mut self.system);
+			}
+
+			fn commit_transaction(&mut self) {
+				crate::support::Transactional::commit_transaction(&mut self.system);
+			}
+
+			fn rollback_transaction(&mut self) {
+				crate::support::Transactional::rollback_transaction(&mut self.system);
+			}
+		}
+	};
+
+	// This quote block implements the `RuntimeCall` and `RuntimeEvent` enums and implements the
+	// `Dispatch` trait.
 	let dispatch_impl = quote! {
 		// These are all the calls which are exposed to the world.
 		// Note that it is just an accumulation of the calls exposed by each pallet.
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
+		//
+		// A runtime call is encoded as the index of the pallet, followed by the pallet call.
 		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
 		pub enum RuntimeCall {
-			#( #pallet_names(#pallet_names::Call<#runtime_struct>) ),*
+			#(
+				#[codec(index = #pallet_indices)]
+				#pallet_names(#pallet_names::Call<#runtime_struct>)
+			),*
+		}
+
+		// These are all the events which can be deposited in the runtime.
+		// Note that it is just an accumulation of the events of each pallet, including system.
+		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone, PartialEq)]
+		pub enum RuntimeEvent {
+			system(system::Event),
+			#( #pallet_names(#pallet_names::Event<#runtime_struct>) ),*
 		}
 
+		// The weight of a runtime call is the weight of the pallet call.
+		impl crate::support::GetWeight for RuntimeCall {
+			fn weight(&self) -> crate::support::Weight {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => crate::support::GetWeight::weight(call),
+					)*
+				}
+			}
+		}
+
+		impl From<system::Event> for RuntimeEvent {
+			fn from(event: system::Event) -> Self {
+				RuntimeEvent::system(event)
+			}
+		}
+
+		#(
+			impl From<#pallet_names::Event<#runtime_struct>> for RuntimeEvent {
+				fn from(event: #pallet_names::Event<#runtime_struct>) -> Self {
+					RuntimeEvent::#pallet_names(event)
+				}
+			}
+		)*
+
 		impl crate::support::Dispatch for #runtime_struct {
-			type Caller = <Runtime as system::Config>::AccountId;
+			type Origin = <#runtime_struct as system::Config>::RuntimeOrigin;
 			type Call = RuntimeCall;
-			// Dispatch a call on behalf of a caller. Increments the caller's nonce.
+			// Dispatch a call from an origin.
 			//
 			// Dispatch allows us to identify which underlying pallet call we want to execute.
-			// Note that we extract the `caller` from the extrinsic, and use that information
+			// Note that we extract the `origin` from the extrinsic, and use that information
 			// to determine who we are executing the call on behalf of.
 			fn dispatch(
 				&mut self,
-				caller: Self::Caller,
+				origin: Self::Origin,
 				runtime_call: Self::Call,
-			) -> crate::support::DispatchResult {
+			) -> crate::support::DispatchResultWithPostInfo {
 				// This match statement will allow us to correctly route `RuntimeCall`s
-				// to the appropriate pallet level call.
+				// to the appropriate pallet level call. Module errors are tagged with the index of
+				// the pallet they come from.
 				match runtime_call {
 					#(
 						RuntimeCall::#pallet_names(call) => {
-							self.#pallet_names.dispatch(caller, call)?;
+							let res = crate::support::Dispatchable::dispatch(call, self, origin);
+							// Move the events of the pallet to the event log of the system pallet.
+							// If the call failed, the transaction will revert them.
+							for event in self.#pallet_names.take_events() {
+								self.system.deposit_event(RuntimeEvent::#pallet_names(event));
+							}
+							crate::support::OnReapedAccounts::on_reaped_accounts(self);
+							res.map_err(|e| e.in_pallet(#pallet_indices))
 						}
 					),*
 				}
-				Ok(())
+			}
+		}
+	};
+
+	// This quote block runs the hooks of every pallet, including system, in the order of the
+	// `Runtime` struct. The weights the pallets use are added up.
+	let hooks_impl = quote! {
+		impl crate::support::Hooks<Self, <Self as system::Config>::BlockNumber>
+			for #runtime_struct
+		{
+			fn on_initialize(
+				runtime: &mut Self,
+				n: <Self as system::Config>::BlockNumber,
+			) -> crate::support::Weight {
+				let mut weight =
+					<system::Pallet<Self> as crate::support::Hooks<Self, _>>::on_initialize(
+						runtime, n,
+					);
+				#(
+					weight = weight.saturating_add(
+						<#pallet_types as crate::support::Hooks<Self, _>>::on_initialize(
+							runtime, n,
+						),
+					);
+				)*
+				weight
+			}
+
+			fn on_finalize(runtime: &mut Self, n: <Self as system::Config>::BlockNumber) {
+				<system::Pallet<Self> as crate::support::Hooks<Self, _>>::on_finalize(runtime, n);
+				#( <#pallet_types as crate::support::Hooks<Self, _>>::on_finalize(runtime, n); )*
+			}
+
+			// Each pallet is offered the weight the pallets before it left.
+			fn on_idle(
+				runtime: &mut Self,
+				n: <Self as system::Config>::BlockNumber,
+				remaining_weight: crate::support::Weight,
+			) -> crate::support::Weight {
+				let mut weight = <system::Pallet<Self> as crate::support::Hooks<Self, _>>::on_idle(
+					runtime,
+					n,
+					remaining_weight,
+				);
+				#(
+					weight = weight.saturating_add(
+						<#pallet_types as crate::support::Hooks<Self, _>>::on_idle(
+							runtime,
+							n,
+							remaining_weight.saturating_sub(weight),
+						),
+					);
+				)*
+				weight
+			}
+
+			fn on_runtime_upgrade(runtime: &mut Self) -> crate::support::Weight {
+				let mut weight =
+					<system::Pallet<Self> as crate::support::Hooks<Self, _>>::on_runtime_upgrade(
+						runtime,
+					);
+				#(
+					weight = weight.saturating_add(
+						<#pallet_types as crate::support::Hooks<Self, _>>::on_runtime_upgrade(
+							runtime,
+						),
+					);
+				)*
+				weight
+			}
+		}
+	};
+
+	// This quote block gives access to each pallet from the runtime, which the calls of the pallets
+	// are dispatched on. The system pallet can only be read, for example to get the block number.
+	let as_mut_impl = quote! {
+		#(
+			impl AsMut<#pallet_types> for #runtime_struct {
+				fn as_mut(&mut self) -> &mut #pallet_types {
+					&mut self.#pallet_names
+				}
+			}
+		)*
+
+		impl AsRef<system::Pallet<Self>> for #runtime_struct {
+			fn as_ref(&self) -> &system::Pallet<Self> {
+				&self.system
 			}
 		}
 	};
@@ -85,7 +464,11 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 	// We combine and return all the generated code.
 	quote! {
 		#dispatch_impl
+		#as_mut_impl
 		#runtime_impl
+		#genesis_config
+		#transactional_impl
+		#hooks_impl
 	}
 	.into()
 }
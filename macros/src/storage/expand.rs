@@ -0,0 +1,19 @@
+use super::parse::StorageDef;
+use quote::quote;
+
+/// See the `fn storage` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_storage(def: StorageDef) -> proc_macro2::TokenStream {
+	let StorageDef { name, vis, prefix } = def;
+	let item_name = name.to_string();
+
+	// The prefix type names the pallet, from the module the item is declared in, and the item.
+	quote! {
+		#[doc(hidden)]
+		#vis struct #prefix;
+
+		impl crate::support::StoragePrefix for #prefix {
+			const PALLET: &'static str = module_path!();
+			const ITEM: &'static str = #item_name;
+		}
+	}
+}
@@ -0,0 +1,27 @@
+pub mod expand;
+pub mod parse;
+
+/// See the `fn storage` docs at the `lib.rs` of this crate for a high level definition.
+pub fn storage(
+	_attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	let mut item_type = syn::parse_macro_input!(item as syn::Item);
+
+	// First we parse the storage item. This also replaces the `_` placeholder in its type with the
+	// prefix type we generate...
+	let generated: proc_macro::TokenStream = match parse::StorageDef::try_from(&mut item_type) {
+		// ..then we generate our new code.
+		Ok(def) => expand::expand_storage(def).into(),
+		Err(e) => e.to_compile_error().into(),
+	};
+
+	// The final expanded code will be placed here: the type alias of the item, with its prefix
+	// filled in.
+	let mut finished: proc_macro::TokenStream =
+		quote::ToTokens::into_token_stream(item_type).into();
+
+	// Add our generated code to the end, and return the final result.
+	finished.extend(generated);
+	finished
+}
@@ -0,0 +1,50 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing a storage item.
+#[derive(Debug)]
+pub struct StorageDef {
+	/// This is the name of the type alias of the item, which is also the name it is stored under.
+	pub name: syn::Ident,
+	/// The visibility of the item, which its prefix type shares.
+	pub vis: syn::Visibility,
+	/// The name of the prefix type we generate for the item, which replaces the `_` placeholder.
+	pub prefix: syn::Ident,
+}
+
+impl StorageDef {
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing a type alias.
+		let item_type = if let syn::Item::Type(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::storage, expected item type"))
+		};
+
+		// The type must be one of the storage types, like `StorageMap<_, K, V>`. Its first generic
+		// argument is the prefix of the item, which we fill in.
+		let msg = "Invalid pallet::storage, expected a storage type with `_` as first argument";
+		let arguments = match &mut *item_type.ty {
+			syn::Type::Path(type_path) => {
+				type_path.path.segments.last_mut().map(|segment| &mut segment.arguments)
+			},
+			_ => None,
+		};
+		let first_arg = match arguments {
+			Some(syn::PathArguments::AngleBracketed(args)) => args.args.first_mut(),
+			_ => None,
+		};
+		let Some(syn::GenericArgument::Type(first_arg @ syn::Type::Infer(_))) = first_arg else {
+			return Err(syn::Error::new(item_type.ty.span(), msg))
+		};
+
+		let name = item_type.ident.clone();
+		let prefix = quote::format_ident!("{}Prefix", name);
+		*first_arg = syn::parse_quote!(#prefix);
+
+		// The bounds on the generics of the alias, like `T: Config`, are only there so the type can
+		// use `T::AccountId`. The compiler does not check them, and would warn about it.
+		item_type.attrs.push(syn::parse_quote!(#[allow(type_alias_bounds)]));
+
+		Ok(Self { name, vis: item_type.vis.clone(), prefix })
+	}
+}
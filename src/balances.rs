@@ -1,38 +1,614 @@
+use crate::support::{
+    BalanceStatus, BuildGenesisConfig, Currency, Decode, DispatchError, DispatchResult,
+    DispatchResultWithPostInfo, Encode, ExistenceRequirement, H256, Hooks, ProofError,
+    ReservableCurrency, Storage, StorageDoubleMap, StorageMap, StorageProof, StorageValue, Weight,
+};
+use crate::system::{ensure_root, ensure_signed};
+use core::fmt::Debug;
 use num::traits::{CheckedAdd, CheckedSub, Zero};
-use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 
 /// The configuration trait for the Balances Module.
 /// Contains the basic types needed for handling balances.
 pub trait Config: crate::system::Config {
     /// A type which can represent the balance of an account.
     /// Usually this is a large unsigned integer.
-    type Balance: Zero + CheckedSub + CheckedAdd + Copy;
+    type Balance: Zero + CheckedSub + CheckedAdd + Ord + Copy + Debug + Encode + Decode;
+
+    /// The minimum balance an account must keep to exist. Accounts which fall below it are
+    /// reaped, and the rest of their balance is lost.
+    const EXISTENTIAL_DEPOSIT: Self::Balance;
+
+    /// Convert the weight of a call into the fee paid for it.
+    fn weight_to_fee(weight: Weight) -> Self::Balance;
 }
 
+/// The weight of a transfer to an existing account.
+pub const TRANSFER_WEIGHT: Weight = 100;
+/// The extra weight of a transfer which creates the account of the recipient.
+pub const NEW_ACCOUNT_WEIGHT: Weight = 50;
+
+/// The identifier of a lock on the balance of an account, like `*b"vesting "`.
+pub type LockIdentifier = [u8; 8];
+
+/// A simple storage mapping from accounts to their free balances.
+#[macros::storage]
+type Balances<T: Config> = StorageMap<_, T::AccountId, T::Balance>;
+
+/// The sum of the free and reserved balances of all the accounts.
+#[macros::storage]
+type TotalIssuance<T: Config> = StorageValue<_, T::Balance>;
+
+/// The balances which are set aside from the free balance of accounts, like deposits.
+#[macros::storage]
+type ReservedBalances<T: Config> = StorageMap<_, T::AccountId, T::Balance>;
+
+/// The locks on the free balance of accounts, by account and by lock. The largest lock of an
+/// account is the part of its free balance which can't be withdrawn.
+#[macros::storage]
+type Locks<T: Config> = StorageDoubleMap<_, T::AccountId, LockIdentifier, T::Balance>;
+
 /// This is the Balances Module.
 /// It is a simple module which keeps track of how much balance each account has in this state
 /// machine.
 #[derive(Debug)]
 pub struct Pallet<T: Config> {
-    // A simple storage mapping from accounts to their balances.
-    balances: BTreeMap<T::AccountId, T::Balance>,
+    balances: Balances<T>,
+    total_issuance: TotalIssuance<T>,
+    reserved: ReservedBalances<T>,
+    locks: Locks<T>,
+    // The events deposited by this pallet, which are moved to the system pallet by the runtime.
+    events: Vec<Event<T>>,
+    // The accounts reaped since the last call, which the runtime removes from the other pallets.
+    reaped: Vec<T::AccountId>,
+}
+
+/// Some balance which was added to an account, but not to the total issuance yet.
+/// It must be resolved with `Pallet::resolve_positive`, or the total issuance will be wrong.
+#[must_use = "the total issuance must be increased with `Pallet::resolve_positive`"]
+pub struct PositiveImbalance<Balance>(Balance);
+
+/// Some balance which was removed from an account, but not from the total issuance yet.
+/// It must be resolved with `Pallet::resolve_negative`, or the total issuance will be wrong.
+#[must_use = "the total issuance must be decreased with `Pallet::resolve_negative`"]
+pub struct NegativeImbalance<Balance>(Balance);
+
+/// The initial state of the Balances Module.
+#[derive(Debug)]
+pub struct GenesisConfig<T: Config> {
+    /// The accounts which are endowed with some balance at genesis.
+    pub balances: Vec<(T::AccountId, T::Balance)>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+    fn default() -> Self {
+        Self { balances: Vec::new() }
+    }
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>, T::BlockNumber> for GenesisConfig<T> {
+    fn build(&self, pallet: &mut Pallet<T>, _n: T::BlockNumber) -> DispatchResult {
+        let mut accounts = BTreeSet::new();
+        for (who, amount) in &self.balances {
+            if !accounts.insert(who) {
+                return Err(DispatchError::Other("Duplicate account in the genesis balances"))
+            }
+            pallet.make_free_balance_be(who, *amount)?;
+        }
+        Ok(())
+    }
+}
+
+/// The events of the Balances Module.
+#[macros::event]
+pub enum Event<T: Config> {
+    /// `amount` was transferred from `from` to `to`.
+    Transfer { from: T::AccountId, to: T::AccountId, amount: T::Balance },
+    /// `who` paid a fee of `amount` for an extrinsic, once the unused weight was refunded.
+    FeePaid { who: T::AccountId, amount: T::Balance },
+    /// The balance of `who` was set to `amount` by the root origin.
+    BalanceSet { who: T::AccountId, amount: T::Balance },
+    /// `amount` was created in the account of `who` by the root origin.
+    Minted { who: T::AccountId, amount: T::Balance },
+    /// `amount` was destroyed from the account of `who` by the root origin.
+    Burned { who: T::AccountId, amount: T::Balance },
+    /// `amount` was moved from the free balance of `who` to their reserved balance.
+    Reserved { who: T::AccountId, amount: T::Balance },
+    /// `amount` was moved from the reserved balance of `who` back to their free balance.
+    Unreserved { who: T::AccountId, amount: T::Balance },
+    /// `amount` was destroyed from the reserved balance of `who`.
+    Slashed { who: T::AccountId, amount: T::Balance },
+    /// `amount` was moved from the reserved balance of `from` to the balance of `to`.
+    ReserveRepatriated {
+        from: T::AccountId,
+        to: T::AccountId,
+        amount: T::Balance,
+        status: BalanceStatus,
+    },
+    /// The account `who` fell below the existential deposit and was reaped. The `dust` left in it
+    /// was lost.
+    AccountReaped { who: T::AccountId, dust: T::Balance },
+}
+
+/// The errors which can be returned by the calls of the Balances Module.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The account does not have enough funds for this operation.
+    InsufficientBalance,
+    /// The balance of the account would overflow.
+    Overflow,
+    /// The transfer would create an account with less than the existential deposit.
+    ExistentialDeposit,
+    /// The transfer would reap the account of the sender, which asked to be kept alive, or which
+    /// has some reserved balance.
+    KeepAlive,
+    /// The balance is locked, and can't be withdrawn.
+    LiquidityRestrictions,
+    /// The account does not exist.
+    DeadAccount,
+    /// The total issuance would fall below zero, so it does not match the balances anymore.
+    Underflow,
 }
 
 impl<T: Config> Pallet<T> {
-    // Create a new instance of the balances module.
-    pub fn new() -> Self {
-        Self { balances: BTreeMap::new() }
+    // Create a new instance of the balances module, in the storage shared by all the pallets.
+    pub fn new(storage: &Storage) -> Self {
+        Self {
+            balances: Balances::<T>::new(storage),
+            total_issuance: TotalIssuance::<T>::new(storage),
+            reserved: ReservedBalances::<T>::new(storage),
+            locks: Locks::<T>::new(storage),
+            events: Vec::new(),
+            reaped: Vec::new(),
+        }
+    }
+
+    /// Deposit an event of this pallet.
+    pub fn deposit_event(&mut self, event: Event<T>) {
+        self.events.push(event);
+    }
+
+    /// Take all the events deposited by this pallet since the last call.
+    pub fn take_events(&mut self) -> Vec<Event<T>> {
+        core::mem::take(&mut self.events)
     }
 
-    /// Set the balance of an account `who` to some `amount`.
-    pub fn set_balance(&mut self, who: &T::AccountId, amount: T::Balance) {
-        self.balances.insert(who.clone(), amount);
+    /// Take all the accounts reaped by this pallet since the last call.
+    pub fn take_reaped_accounts(&mut self) -> Vec<T::AccountId> {
+        core::mem::take(&mut self.reaped)
     }
 
-    /// Get the balance of an account `who`.
-    /// If the account has no stored balance, we return zero.
+    /// Set the balance of an account `who` to some `amount`, creating or destroying the
+    /// difference. If `amount` is below the existential deposit, the account is reaped instead.
+    /// Nothing changes if an account can't be created with `amount`, if the locks or the reserved
+    /// balance of `who` prevent the decrease, or if the total issuance would overflow.
+    pub fn make_free_balance_be(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+    ) -> Result<(), Error> {
+        let balance = self.balance(who);
+        if let Some(increase) = amount.checked_sub(&balance) {
+            if !increase.is_zero() {
+                self.issue(who, increase)?;
+            }
+        } else if let Some(decrease) = balance.checked_sub(&amount) {
+            let imbalance = self.withdraw(who, decrease, false)?;
+            self.resolve_negative(imbalance)?;
+        }
+        Ok(())
+    }
+
+    /// Get the free balance of an account `who`.
+    /// If the account does not exist, we return zero.
     pub fn balance(&self, who: &T::AccountId) -> T::Balance {
-        *self.balances.get(who).unwrap_or(&T::Balance::zero())
+        self.balances.get(who).unwrap_or_else(T::Balance::zero)
+    }
+
+    /// Prove the free balance of `who` to a light client, which only holds the state root of the
+    /// block.
+    pub fn prove_balance(&self, who: &T::AccountId) -> StorageProof {
+        self.balances.prove(who)
+    }
+
+    /// Check a proof made by `prove_balance` against the state root of a block, and return the
+    /// free balance of `who` it proves. An account which does not exist has a balance of zero.
+    pub fn verify_balance(
+        state_root: &H256,
+        who: &T::AccountId,
+        proof: &[Vec<u8>],
+    ) -> Result<T::Balance, ProofError> {
+        Ok(Balances::<T>::verify(state_root, who, proof)?.unwrap_or_else(T::Balance::zero))
+    }
+
+    /// Check if the account `who` exists, which is when it has at least the existential deposit.
+    pub fn account_exists(&self, who: &T::AccountId) -> bool {
+        self.balances.contains_key(who)
+    }
+
+    /// Iterate over all the accounts which have a stored balance, in order.
+    pub fn balances(&self) -> impl Iterator<Item = (T::AccountId, T::Balance)> + use<T> {
+        self.balances.iter()
+    }
+
+    /// Get the reserved balance of an account `who`.
+    pub fn reserved_balance(&self, who: &T::AccountId) -> T::Balance {
+        self.reserved.get(who).unwrap_or_else(T::Balance::zero)
+    }
+
+    /// Get the part of the free balance of `who` which is locked: the largest of their locks.
+    pub fn locked_balance(&self, who: &T::AccountId) -> T::Balance {
+        let locks = self.locks.iter_prefix(who).map(|(_, amount)| amount);
+        locks.max().unwrap_or_else(T::Balance::zero)
+    }
+
+    /// Get the total issuance: the sum of the free and reserved balances of all the accounts.
+    pub fn total_issuance(&self) -> T::Balance {
+        self.total_issuance.get().unwrap_or_else(T::Balance::zero)
+    }
+
+    /// Check that the total issuance is the sum of the free and reserved balances of all the
+    /// accounts.
+    #[cfg(test)]
+    pub fn total_issuance_is_consistent(&self) -> bool {
+        let sum = self
+            .balances()
+            .chain(self.reserved.iter())
+            .try_fold(T::Balance::zero(), |sum, (_, balance)| sum.checked_add(&balance));
+        sum == Some(self.total_issuance())
+    }
+
+    /// Add `amount` to the balance of `who`, creating the account if needed.
+    /// The total issuance is not updated until the returned imbalance is resolved.
+    pub fn deposit_creating(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+    ) -> Result<PositiveImbalance<T::Balance>, Error> {
+        let new_balance = self.balance(who).checked_add(&amount).ok_or(Error::Overflow)?;
+        if new_balance < T::EXISTENTIAL_DEPOSIT {
+            return Err(Error::ExistentialDeposit)
+        }
+        self.balances.insert(who, new_balance);
+        Ok(PositiveImbalance(amount))
+    }
+
+    /// Remove `amount` from the free balance of `who`. The locked balance can't be removed.
+    /// The account is reaped if it falls below the existential deposit, unless `keep_alive` is
+    /// set or it has some reserved balance, in which case this fails. The dust lost when the
+    /// account is reaped is part of the returned imbalance. The total issuance is not updated
+    /// until the imbalance is resolved.
+    pub fn withdraw(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+        keep_alive: bool,
+    ) -> Result<NegativeImbalance<T::Balance>, Error> {
+        let balance = self.balance(who);
+        let new_balance = balance.checked_sub(&amount).ok_or(Error::InsufficientBalance)?;
+        if new_balance < self.locked_balance(who) {
+            return Err(Error::LiquidityRestrictions)
+        }
+        if new_balance >= T::EXISTENTIAL_DEPOSIT {
+            self.balances.insert(who, new_balance);
+            return Ok(NegativeImbalance(amount))
+        }
+        if keep_alive || !self.reserved_balance(who).is_zero() {
+            return Err(Error::KeepAlive)
+        }
+        self.locks.remove_prefix(who);
+        if self.balances.take(who).is_some() {
+            self.reaped.push(who.clone());
+            self.deposit_event(Event::AccountReaped { who: who.clone(), dust: new_balance });
+        }
+        Ok(NegativeImbalance(balance))
+    }
+
+    /// Add the balance created in accounts to the total issuance. Fails if the total issuance
+    /// would overflow, in which case it is left unchanged.
+    pub fn resolve_positive(
+        &mut self,
+        imbalance: PositiveImbalance<T::Balance>,
+    ) -> Result<(), Error> {
+        let total_issuance =
+            self.total_issuance().checked_add(&imbalance.0).ok_or(Error::Overflow)?;
+        self.total_issuance.put(total_issuance);
+        Ok(())
+    }
+
+    // Create `amount` in the account of `who`, and add it to the total issuance. Nothing is
+    // created if the total issuance would overflow.
+    fn issue(&mut self, who: &T::AccountId, amount: T::Balance) -> Result<(), Error> {
+        self.total_issuance().checked_add(&amount).ok_or(Error::Overflow)?;
+        let imbalance = self.deposit_creating(who, amount)?;
+        self.resolve_positive(imbalance)
+    }
+
+    /// Remove the balance destroyed in accounts from the total issuance. Fails if the total
+    /// issuance would underflow, in which case it is left unchanged.
+    pub fn resolve_negative(
+        &mut self,
+        imbalance: NegativeImbalance<T::Balance>,
+    ) -> Result<(), Error> {
+        let total_issuance =
+            self.total_issuance().checked_sub(&imbalance.0).ok_or(Error::Underflow)?;
+        self.total_issuance.put(total_issuance);
+        Ok(())
+    }
+
+    /// Move `amount` from the free balance of `who` to their reserved balance. The free balance
+    /// left must cover the locks and the existential deposit.
+    pub fn reserve(&mut self, who: &T::AccountId, amount: T::Balance) -> Result<(), Error> {
+        let reserved = self.reserved_balance(who).checked_add(&amount).ok_or(Error::Overflow)?;
+        // The balance stays in the account, so the total issuance does not change.
+        let NegativeImbalance(_) = self.withdraw(who, amount, true)?;
+        self.reserved.insert(who, reserved);
+        self.deposit_event(Event::Reserved { who: who.clone(), amount });
+        Ok(())
+    }
+
+    /// Move up to `amount` from the reserved balance of `who` back to their free balance.
+    /// Returns the part of `amount` which was not reserved, and could not be moved.
+    pub fn unreserve(&mut self, who: &T::AccountId, amount: T::Balance) -> T::Balance {
+        let (actual, remaining) = self.take_reserved(who, amount);
+        if actual.is_zero() {
+            return remaining
+        }
+        // An account with reserved balance is never reaped, so this can't fail.
+        if let Ok(PositiveImbalance(_)) = self.deposit_creating(who, actual) {
+            self.deposit_event(Event::Unreserved { who: who.clone(), amount: actual });
+        }
+        remaining
+    }
+
+    /// Destroy up to `amount` from the reserved balance of `who`.
+    /// The total issuance is not updated until the returned imbalance is resolved.
+    pub fn slash_reserved(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+    ) -> NegativeImbalance<T::Balance> {
+        let (actual, _) = self.take_reserved(who, amount);
+        if actual.is_zero() {
+            return NegativeImbalance(actual)
+        }
+        self.deposit_event(Event::Slashed { who: who.clone(), amount: actual });
+        NegativeImbalance(actual)
+    }
+
+    /// Move up to `amount` from the reserved balance of `slashed` to the free or reserved balance
+    /// of `beneficiary`, depending on `status`. Returns the part of `amount` which was not
+    /// reserved, and could not be moved.
+    pub fn repatriate_reserved(
+        &mut self,
+        slashed: &T::AccountId,
+        beneficiary: &T::AccountId,
+        amount: T::Balance,
+        status: BalanceStatus,
+    ) -> Result<T::Balance, Error> {
+        if slashed == beneficiary {
+            return match status {
+                BalanceStatus::Free => Ok(self.unreserve(slashed, amount)),
+                BalanceStatus::Reserved => {
+                    let reserved = self.reserved_balance(slashed);
+                    Ok(amount.checked_sub(&reserved).unwrap_or_else(T::Balance::zero))
+                },
+            }
+        }
+        let actual = amount.min(self.reserved_balance(slashed));
+        // Check that the beneficiary can receive the balance before taking it.
+        match status {
+            BalanceStatus::Free => {
+                let new_balance =
+                    self.balance(beneficiary).checked_add(&actual).ok_or(Error::Overflow)?;
+                if new_balance < T::EXISTENTIAL_DEPOSIT {
+                    return Err(Error::ExistentialDeposit)
+                }
+            },
+            BalanceStatus::Reserved => {
+                if !self.account_exists(beneficiary) {
+                    return Err(Error::DeadAccount)
+                }
+                self.reserved_balance(beneficiary).checked_add(&actual).ok_or(Error::Overflow)?;
+            },
+        }
+        self.take_reserved(slashed, actual);
+        // The balance moves between accounts, so the total issuance does not change.
+        match status {
+            BalanceStatus::Free => {
+                let PositiveImbalance(_) = self.deposit_creating(beneficiary, actual)?;
+            },
+            BalanceStatus::Reserved => {
+                let reserved = self.reserved_balance(beneficiary).checked_add(&actual);
+                self.reserved.insert(beneficiary, reserved.ok_or(Error::Overflow)?);
+            },
+        }
+        self.deposit_event(Event::ReserveRepatriated {
+            from: slashed.clone(),
+            to: beneficiary.clone(),
+            amount: actual,
+            status,
+        });
+        Ok(amount.checked_sub(&actual).unwrap_or_else(T::Balance::zero))
+    }
+
+    /// Lock `amount` of the free balance of `who` under `id`, replacing the previous lock with
+    /// the same `id`. Locks overlap: the largest lock is what can't be withdrawn.
+    pub fn set_lock(&mut self, id: LockIdentifier, who: &T::AccountId, amount: T::Balance) {
+        self.locks.insert(who, &id, amount);
+    }
+
+    /// Remove the lock `id` on the free balance of `who`.
+    pub fn remove_lock(&mut self, id: LockIdentifier, who: &T::AccountId) {
+        self.locks.remove(who, &id);
+    }
+
+    // Take up to `amount` from the reserved balance of `who`. Returns the amount taken, and the
+    // part of `amount` which was not reserved.
+    fn take_reserved(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+    ) -> (T::Balance, T::Balance) {
+        let reserved = self.reserved_balance(who);
+        let actual = amount.min(reserved);
+        let new_reserved = reserved.checked_sub(&actual).unwrap_or_else(T::Balance::zero);
+        if new_reserved.is_zero() {
+            self.reserved.remove(who);
+        } else {
+            self.reserved.insert(who, new_reserved);
+        }
+        (actual, amount.checked_sub(&actual).unwrap_or_else(T::Balance::zero))
+    }
+
+    /// Withdraw the fee for `weight` from `who`, before their extrinsic is dispatched.
+    /// Fees are burned. Paying a fee can't reap the account, so `who` must keep at least the
+    /// existential deposit.
+    pub fn withdraw_fee(&mut self, who: &T::AccountId, weight: Weight) -> DispatchResult {
+        let imbalance = self.withdraw(who, T::weight_to_fee(weight), true)?;
+        Ok(self.resolve_negative(imbalance)?)
+    }
+
+    /// Refund `who` the part of the fee withdrawn for `declared` weight which their extrinsic
+    /// did not use, given that it used `actual` weight.
+    /// Accounts reaped by their own extrinsic are not refunded, since that would bring them back
+    /// with less than the existential deposit.
+    pub fn refund_fee(&mut self, who: &T::AccountId, declared: Weight, actual: Weight) {
+        let fee = T::weight_to_fee(declared);
+        let mut actual_fee = T::weight_to_fee(actual);
+        if !self.account_exists(who) {
+            actual_fee = fee;
+        }
+        let refund = fee.checked_sub(&actual_fee).unwrap_or_else(T::Balance::zero);
+        // The refund was withdrawn from the account just before, so it can't overflow. It only
+        // fails for a reaped account, whose refund is zero.
+        let _ = self.issue(who, refund);
+        self.deposit_event(Event::FeePaid { who: who.clone(), amount: actual_fee });
+    }
+
+    // Move `amount` from `from` to `to`, and return whether this created the account of `to`.
+    // The account of `from` is reaped if it falls below the existential deposit, unless
+    // `keep_alive` is set, in which case the transfer fails. The dust lost when it is reaped is
+    // removed from the total issuance.
+    fn do_transfer(
+        &mut self,
+        from: T::AccountId,
+        to: T::AccountId,
+        amount: T::Balance,
+        keep_alive: bool,
+    ) -> Result<bool, Error> {
+        let new_from_balance =
+            self.balance(&from).checked_sub(&amount).ok_or(Error::InsufficientBalance)?;
+        if keep_alive && new_from_balance < T::EXISTENTIAL_DEPOSIT {
+            return Err(Error::KeepAlive)
+        }
+        let new_account = !self.account_exists(&to);
+        // A transfer to oneself changes nothing, once we know it could be made.
+        if from != to {
+            // Check that `to` can receive the amount, so the withdrawal never needs reverting.
+            let new_to_balance = self.balance(&to).checked_add(&amount).ok_or(Error::Overflow)?;
+            if new_to_balance < T::EXISTENTIAL_DEPOSIT {
+                return Err(Error::ExistentialDeposit)
+            }
+            let negative = self.withdraw(&from, amount, keep_alive)?;
+            let positive = self.deposit_creating(&to, amount)?;
+            self.resolve_negative(negative)?;
+            self.resolve_positive(positive)?;
+        }
+        self.deposit_event(Event::Transfer { from, to, amount });
+        Ok(new_account)
+    }
+
+    // The weight actually used by a transfer: transfers to an existing account use less.
+    fn transfer_weight(new_account: bool) -> DispatchResultWithPostInfo {
+        if new_account {
+            return Ok(().into())
+        }
+        Ok(Some(TRANSFER_WEIGHT).into())
+    }
+}
+
+// The balances pallet has no per-block logic.
+impl<T: Config> Hooks<T, T::BlockNumber> for Pallet<T> {}
+
+// Other pallets use the balances pallet through the `Currency` traits, which resolve the
+// imbalances themselves.
+impl<T: Config> Currency<T::AccountId> for Pallet<T> {
+    type Balance = T::Balance;
+
+    fn minimum_balance() -> T::Balance {
+        T::EXISTENTIAL_DEPOSIT
+    }
+
+    fn total_issuance(&self) -> T::Balance {
+        Pallet::total_issuance(self)
+    }
+
+    fn free_balance(&self, who: &T::AccountId) -> T::Balance {
+        self.balance(who)
+    }
+
+    fn transfer(
+        &mut self,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: T::Balance,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult {
+        let keep_alive = existence == ExistenceRequirement::KeepAlive;
+        self.do_transfer(from.clone(), to.clone(), amount, keep_alive)?;
+        Ok(())
+    }
+
+    fn burn_from(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult {
+        let keep_alive = existence == ExistenceRequirement::KeepAlive;
+        let imbalance = self.withdraw(who, amount, keep_alive)?;
+        Ok(self.resolve_negative(imbalance)?)
+    }
+
+    fn deposit_creating(&mut self, who: &T::AccountId, amount: T::Balance) -> DispatchResult {
+        Ok(self.issue(who, amount)?)
+    }
+}
+
+impl<T: Config> ReservableCurrency<T::AccountId> for Pallet<T> {
+    fn reserved_balance(&self, who: &T::AccountId) -> T::Balance {
+        Pallet::reserved_balance(self, who)
+    }
+
+    fn reserve(&mut self, who: &T::AccountId, amount: T::Balance) -> DispatchResult {
+        Ok(Pallet::reserve(self, who, amount)?)
+    }
+
+    fn unreserve(&mut self, who: &T::AccountId, amount: T::Balance) -> T::Balance {
+        Pallet::unreserve(self, who, amount)
+    }
+
+    fn slash_reserved(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+    ) -> Result<T::Balance, DispatchError> {
+        let imbalance = Pallet::slash_reserved(self, who, amount);
+        let remaining = amount.checked_sub(&imbalance.0).unwrap_or_else(T::Balance::zero);
+        self.resolve_negative(imbalance)?;
+        Ok(remaining)
+    }
+
+    fn repatriate_reserved(
+        &mut self,
+        slashed: &T::AccountId,
+        beneficiary: &T::AccountId,
+        amount: T::Balance,
+        status: BalanceStatus,
+    ) -> Result<T::Balance, DispatchError> {
+        Ok(Pallet::repatriate_reserved(self, slashed, beneficiary, amount, status)?)
     }
 }
 
@@ -40,67 +616,488 @@ impl<T: Config> Pallet<T> {
 impl<T: Config> Pallet<T> {
     /// Transfer `amount` from one account to another.
     /// This function verifies that `from` has at least `amount` balance to transfer,
-    /// and that no mathematical overflows occur.
+    /// and that no mathematical overflows occur. If `from` is left with less than the existential
+    /// deposit, its account is reaped.
+    /// Transfers to an account which already exists use less weight.
+    #[call_index(0)]
+    #[weight(TRANSFER_WEIGHT + NEW_ACCOUNT_WEIGHT)]
     pub fn transfer(
         &mut self,
-        caller: T::AccountId,
+        origin: T::RuntimeOrigin,
         to: T::AccountId,
         amount: T::Balance,
-    ) -> crate::support::DispatchResult {
-        let caller_balance = self.balance(&caller);
-        let to_balance = self.balance(&to);
+    ) -> DispatchResultWithPostInfo {
+        let caller = ensure_signed(origin)?;
+        let new_account = self.do_transfer(caller, to, amount, false)?;
+        Self::transfer_weight(new_account)
+    }
 
-        let new_caller_balance = caller_balance.checked_sub(&amount).ok_or("Not enough funds.")?;
-        let new_to_balance = to_balance.checked_add(&amount).ok_or("Overflow")?;
+    /// Set the balance of `who` to `amount`, creating or destroying the difference. Only the root
+    /// origin can do this, and it fails like `make_free_balance_be`.
+    #[call_index(1)]
+    #[weight(100)]
+    pub fn force_set_balance(
+        &mut self,
+        origin: T::RuntimeOrigin,
+        who: T::AccountId,
+        amount: T::Balance,
+    ) -> DispatchResult {
+        ensure_root(origin)?;
+        let balance = self.balance(&who);
+        self.make_free_balance_be(&who, amount)?;
+        // The account may have been reaped, in which case its balance is zero.
+        let amount = self.balance(&who);
+        if amount != balance {
+            self.deposit_event(Event::BalanceSet { who, amount });
+        }
+        Ok(())
+    }
 
-        self.balances.insert(caller, new_caller_balance);
-        self.balances.insert(to, new_to_balance);
+    /// Transfer `amount` like `transfer`, but fail rather than reap the account of the sender.
+    #[call_index(2)]
+    #[weight(TRANSFER_WEIGHT + NEW_ACCOUNT_WEIGHT)]
+    pub fn transfer_keep_alive(
+        &mut self,
+        origin: T::RuntimeOrigin,
+        to: T::AccountId,
+        amount: T::Balance,
+    ) -> DispatchResultWithPostInfo {
+        let caller = ensure_signed(origin)?;
+        let new_account = self.do_transfer(caller, to, amount, true)?;
+        Self::transfer_weight(new_account)
+    }
 
+    /// Transfer the whole balance of the sender to `to`, which reaps the account of the sender.
+    /// With `keep_alive`, the existential deposit is kept, and the account is not reaped.
+    #[call_index(3)]
+    #[weight(TRANSFER_WEIGHT + NEW_ACCOUNT_WEIGHT)]
+    pub fn transfer_all(
+        &mut self,
+        origin: T::RuntimeOrigin,
+        to: T::AccountId,
+        keep_alive: bool,
+    ) -> DispatchResultWithPostInfo {
+        let caller = ensure_signed(origin)?;
+        let mut amount = self.balance(&caller);
+        if keep_alive {
+            amount = amount.checked_sub(&T::EXISTENTIAL_DEPOSIT).unwrap_or_else(T::Balance::zero);
+        }
+        let new_account = self.do_transfer(caller, to, amount, keep_alive)?;
+        Self::transfer_weight(new_account)
+    }
+
+    /// Create `amount` in the account of `who`. Only the root origin can do this.
+    #[call_index(4)]
+    #[weight(100)]
+    pub fn mint(
+        &mut self,
+        origin: T::RuntimeOrigin,
+        who: T::AccountId,
+        amount: T::Balance,
+    ) -> DispatchResult {
+        ensure_root(origin)?;
+        self.issue(&who, amount)?;
+        self.deposit_event(Event::Minted { who, amount });
+        Ok(())
+    }
+
+    /// Destroy `amount` from the account of `who`, which is reaped if it falls below the
+    /// existential deposit. Only the root origin can do this.
+    #[call_index(5)]
+    #[weight(100)]
+    pub fn burn(
+        &mut self,
+        origin: T::RuntimeOrigin,
+        who: T::AccountId,
+        amount: T::Balance,
+    ) -> DispatchResult {
+        ensure_root(origin)?;
+        let imbalance = self.withdraw(&who, amount, false)?;
+        self.resolve_negative(imbalance)?;
+        self.deposit_event(Event::Burned { who, amount });
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{BalanceStatus, Error, Event, TRANSFER_WEIGHT};
+    use crate::support::{
+        Currency, DispatchError, DispatchResult, ExistenceRequirement, PostDispatchInfo,
+        ReservableCurrency, Storage, Weight,
+    };
+    use crate::system::RawOrigin;
+
     struct TestConfig;
 
     impl crate::system::Config for TestConfig {
         type AccountId = String;
+        type RuntimeOrigin = crate::system::RawOrigin<String>;
         type BlockNumber = u32;
         type Nonce = u32;
+        type Hash = crate::support::H256;
+        type Hashing = crate::support::BlakeTwo256;
+        type RuntimeEvent = crate::system::Event;
+        const MAX_BLOCK_WEIGHT: Weight = 1_000;
     }
 
     impl super::Config for TestConfig {
         type Balance = u128;
+        const EXISTENTIAL_DEPOSIT: u128 = 10;
+
+        fn weight_to_fee(weight: Weight) -> u128 {
+            weight.into()
+        }
+    }
+
+    fn signed(who: &str) -> RawOrigin<String> {
+        RawOrigin::Signed(who.to_string())
     }
 
     #[test]
     fn init_balances() {
-        let mut balances = super::Pallet::<TestConfig>::new();
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
 
         assert_eq!(balances.balance(&"alice".to_string()), 0);
-        balances.set_balance(&"alice".to_string(), 100);
+        balances.make_free_balance_be(&"alice".to_string(), 100).unwrap();
         assert_eq!(balances.balance(&"alice".to_string()), 100);
         assert_eq!(balances.balance(&"bob".to_string()), 0);
     }
 
     #[test]
     fn transfer_balance() {
-        let mut balances = super::Pallet::<TestConfig>::new();
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
 
         assert_eq!(
-            balances.transfer("alice".to_string(), "bob".to_string(), 51),
-            Err("Not enough funds.")
+            balances.transfer(signed("alice"), "bob".to_string(), 51),
+            Err(Error::InsufficientBalance.into())
         );
 
-        balances.set_balance(&"alice".to_string(), 100);
-        assert_eq!(balances.transfer("alice".to_string(), "bob".to_string(), 51), Ok(()));
+        balances.make_free_balance_be(&"alice".to_string(), 100).unwrap();
+        // Bob does not exist yet, so the transfer uses all of its declared weight.
+        assert_eq!(
+            balances.transfer(signed("alice"), "bob".to_string(), 51),
+            Ok(PostDispatchInfo { actual_weight: None })
+        );
         assert_eq!(balances.balance(&"alice".to_string()), 49);
         assert_eq!(balances.balance(&"bob".to_string()), 51);
+        assert_eq!(
+            balances.take_events(),
+            vec![Event::Transfer { from: "alice".to_string(), to: "bob".to_string(), amount: 51 }]
+        );
+
+        // The total issuance bounds every balance, so it is what overflows first.
+        assert_eq!(
+            balances.make_free_balance_be(&"bob".to_string(), u128::MAX),
+            Err(Error::Overflow)
+        );
+        assert_eq!(balances.balance(&"bob".to_string()), 51);
+
+        assert_eq!(
+            balances.transfer(signed("alice"), "bob".to_string(), 51),
+            Err(Error::InsufficientBalance.into())
+        );
+        assert_eq!(
+            balances.transfer(signed("alice"), "bob".to_string(), 0),
+            Ok(PostDispatchInfo { actual_weight: Some(TRANSFER_WEIGHT) })
+        );
+    }
+
+    #[test]
+    fn accounts_are_reaped() {
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
+        let (alice, bob, charlie) = ("alice".to_string(), "bob".to_string(), "charlie".to_string());
+        balances.make_free_balance_be(&alice, 100).unwrap();
+
+        // Accounts must be created with at least the existential deposit.
+        assert_eq!(
+            balances.transfer(signed("alice"), bob.clone(), 5),
+            Err(Error::ExistentialDeposit.into())
+        );
+        assert!(!balances.account_exists(&bob));
+
+        // The sender is reaped if it is left with less than the existential deposit.
+        assert!(balances.transfer(signed("alice"), bob.clone(), 95).is_ok());
+        assert!(!balances.account_exists(&alice));
+        assert_eq!(balances.balance(&alice), 0);
+        assert_eq!(balances.take_reaped_accounts(), vec![alice.clone()]);
+        assert_eq!(
+            balances.take_events(),
+            vec![
+                Event::AccountReaped { who: alice.clone(), dust: 5 },
+                Event::Transfer { from: alice.clone(), to: bob.clone(), amount: 95 },
+            ]
+        );
+
+        // Unless it asks to be kept alive.
+        assert_eq!(
+            balances.transfer_keep_alive(signed("bob"), charlie.clone(), 90),
+            Err(Error::KeepAlive.into())
+        );
+        assert!(balances.transfer_keep_alive(signed("bob"), charlie.clone(), 85).is_ok());
+        assert_eq!(balances.balance(&bob), 10);
+
+        // Transferring everything reaps the account, unless the existential deposit is kept.
+        assert!(balances.transfer_all(signed("charlie"), bob.clone(), true).is_ok());
+        assert_eq!(balances.balance(&charlie), 10);
+        assert_eq!(balances.balance(&bob), 85);
+        assert!(balances.transfer_all(signed("bob"), charlie.clone(), false).is_ok());
+        assert!(!balances.account_exists(&bob));
+        assert_eq!(balances.balance(&charlie), 95);
+        assert_eq!(balances.take_reaped_accounts(), vec![bob.clone()]);
+
+        // Setting a balance below the existential deposit reaps the account too.
+        assert_eq!(balances.force_set_balance(RawOrigin::Root, charlie.clone(), 0), Ok(()));
+        assert!(!balances.account_exists(&charlie));
+        assert_eq!(balances.take_reaped_accounts(), vec![charlie]);
+        assert!(balances.total_issuance_is_consistent());
+    }
+
+    #[test]
+    fn total_issuance_is_tracked() {
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+
+        // Only the root origin can create or destroy balance.
+        assert_eq!(
+            balances.mint(signed("alice"), alice.clone(), 100),
+            Err(DispatchError::BadOrigin)
+        );
+        assert_eq!(
+            balances.burn(signed("alice"), alice.clone(), 100),
+            Err(DispatchError::BadOrigin)
+        );
 
+        assert_eq!(balances.mint(RawOrigin::Root, alice.clone(), 100), Ok(()));
         assert_eq!(
-            balances.transfer("alice".to_string(), "bob".to_string(), 51),
-            Err("Not enough funds.")
+            balances.mint(RawOrigin::Root, bob.clone(), 5),
+            Err(Error::ExistentialDeposit.into())
         );
+        assert_eq!(balances.burn(RawOrigin::Root, alice.clone(), 30), Ok(()));
+        assert_eq!(
+            balances.burn(RawOrigin::Root, alice.clone(), 80),
+            Err(Error::InsufficientBalance.into())
+        );
+        assert_eq!(
+            balances.mint(RawOrigin::Root, bob.clone(), u128::MAX),
+            Err(Error::Overflow.into())
+        );
+        assert!(!balances.account_exists(&bob));
+        assert_eq!(balances.total_issuance(), 70);
+        assert_eq!(
+            balances.take_events(),
+            vec![
+                Event::Minted { who: alice.clone(), amount: 100 },
+                Event::Burned { who: alice.clone(), amount: 30 },
+            ]
+        );
+
+        // Setting a balance creates or destroys the difference.
+        assert_eq!(balances.force_set_balance(RawOrigin::Root, bob.clone(), 50), Ok(()));
+        assert_eq!(balances.total_issuance(), 120);
+        assert_eq!(balances.force_set_balance(RawOrigin::Root, bob.clone(), 20), Ok(()));
+        assert_eq!(balances.total_issuance(), 90);
+
+        // Transfers keep the total issuance, except for the dust lost when an account is reaped.
+        assert!(balances.transfer(signed("alice"), bob.clone(), 30).is_ok());
+        assert_eq!(balances.total_issuance(), 90);
+        assert!(balances.transfer(signed("bob"), alice.clone(), 45).is_ok());
+        assert_eq!(balances.total_issuance(), 85);
+        assert_eq!(balances.balance(&alice), 85);
+
+        // Fees are burned.
+        assert_eq!(balances.withdraw_fee(&alice, 60), Ok(()));
+        balances.refund_fee(&alice, 60, 40);
+        assert_eq!(balances.total_issuance(), 45);
+        assert!(balances.total_issuance_is_consistent());
+
+        // Imbalances which are not resolved leave the total issuance behind.
+        let _ = balances.deposit_creating(&alice, 10);
+        assert!(!balances.total_issuance_is_consistent());
+
+        // The total issuance can't overflow or underflow when imbalances are resolved.
+        balances.total_issuance.put(u128::MAX);
+        let imbalance = balances.deposit_creating(&alice, 10).unwrap();
+        assert_eq!(balances.resolve_positive(imbalance), Err(Error::Overflow));
+        assert_eq!(balances.total_issuance(), u128::MAX);
+        balances.total_issuance.put(5);
+        let imbalance = balances.withdraw(&alice, 10, true).unwrap();
+        assert_eq!(balances.resolve_negative(imbalance), Err(Error::Underflow));
+        assert_eq!(balances.total_issuance(), 5);
+    }
+
+    #[test]
+    fn reserves_and_locks() {
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+        balances.make_free_balance_be(&alice, 100).unwrap();
+
+        // The free balance left must cover the existential deposit.
+        assert_eq!(balances.reserve(&alice, 95), Err(Error::KeepAlive));
+        assert_eq!(balances.reserve(&alice, 50), Ok(()));
+        assert_eq!(balances.balance(&alice), 50);
+        assert_eq!(balances.reserved_balance(&alice), 50);
+        assert_eq!(balances.total_issuance(), 100);
+
+        // Accounts with reserved balance are not reaped.
+        assert_eq!(
+            balances.transfer(signed("alice"), bob.clone(), 45),
+            Err(Error::KeepAlive.into())
+        );
+
+        // Slashing destroys reserved balance, once the imbalance is resolved.
+        let imbalance = balances.slash_reserved(&alice, 20);
+        balances.resolve_negative(imbalance).unwrap();
+        assert_eq!(balances.reserved_balance(&alice), 30);
+        assert_eq!(balances.total_issuance(), 80);
+        assert_eq!(
+            balances.take_events().last(),
+            Some(&Event::Slashed { who: alice.clone(), amount: 20 })
+        );
+        let imbalance = balances.slash_reserved(&bob, 20);
+        balances.resolve_negative(imbalance).unwrap();
+        assert_eq!(balances.take_events(), vec![]);
+
+        // Reserved balance can be moved to another account, as far as there is some.
+        assert_eq!(balances.repatriate_reserved(&alice, &bob, 20, BalanceStatus::Free), Ok(0));
+        assert_eq!(balances.balance(&bob), 20);
+        assert_eq!(balances.repatriate_reserved(&alice, &bob, 15, BalanceStatus::Reserved), Ok(5));
+        assert_eq!(balances.reserved_balance(&bob), 10);
+        assert_eq!(balances.reserved_balance(&alice), 0);
+        assert_eq!(
+            balances.repatriate_reserved(&bob, &"charlie".to_string(), 5, BalanceStatus::Reserved),
+            Err(Error::DeadAccount)
+        );
+        assert_eq!(balances.unreserve(&bob, 15), 5);
+        assert_eq!(balances.balance(&bob), 30);
+        assert_eq!(balances.reserved_balance(&bob), 0);
+        assert!(balances.total_issuance_is_consistent());
+
+        // Locked balance can't be transferred, and the largest lock wins.
+        balances.set_lock(*b"vesting ", &alice, 30);
+        balances.set_lock(*b"staking ", &alice, 10);
+        assert_eq!(balances.locked_balance(&alice), 30);
+        assert_eq!(
+            balances.transfer(signed("alice"), bob.clone(), 25),
+            Err(Error::LiquidityRestrictions.into())
+        );
+        assert!(balances.transfer(signed("alice"), bob.clone(), 20).is_ok());
+        balances.set_lock(*b"vesting ", &alice, 5);
+        balances.remove_lock(*b"staking ", &alice);
+        assert_eq!(balances.locked_balance(&alice), 5);
+        balances.remove_lock(*b"vesting ", &alice);
+        assert!(balances.transfer(signed("alice"), bob.clone(), 30).is_ok());
+        assert!(!balances.account_exists(&alice));
+        assert!(balances.total_issuance_is_consistent());
+    }
+
+    // Pay a deposit through the currency traits, like another pallet would.
+    fn pay_deposit<C: ReservableCurrency<String>>(
+        currency: &mut C,
+        who: &String,
+        fee: C::Balance,
+        deposit: C::Balance,
+    ) -> DispatchResult {
+        currency.burn_from(who, fee, ExistenceRequirement::KeepAlive)?;
+        currency.reserve(who, deposit)
+    }
+
+    #[test]
+    fn currency_traits() {
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+        assert_eq!(<super::Pallet<TestConfig> as Currency<String>>::minimum_balance(), 10);
+
+        assert_eq!(Currency::deposit_creating(&mut balances, &alice, 100), Ok(()));
+        assert_eq!(
+            Currency::deposit_creating(&mut balances, &bob, 5),
+            Err(Error::ExistentialDeposit.into())
+        );
+        assert_eq!(Currency::total_issuance(&balances), 100);
+
+        assert_eq!(pay_deposit(&mut balances, &alice, 10, 30), Ok(()));
+        assert_eq!(balances.free_balance(&alice), 60);
+        assert_eq!(ReservableCurrency::reserved_balance(&balances, &alice), 30);
+        assert_eq!(Currency::total_issuance(&balances), 90);
+
+        // Slashing returns the part which was not reserved, and burns the rest.
+        assert_eq!(ReservableCurrency::slash_reserved(&mut balances, &alice, 40), Ok(10));
+        assert_eq!(Currency::total_issuance(&balances), 60);
+
+        assert_eq!(
+            Currency::transfer(&mut balances, &alice, &bob, 55, ExistenceRequirement::KeepAlive),
+            Err(Error::KeepAlive.into())
+        );
+        assert_eq!(
+            Currency::transfer(&mut balances, &alice, &bob, 60, ExistenceRequirement::AllowDeath),
+            Ok(())
+        );
+        assert!(!balances.account_exists(&alice));
+        assert_eq!(balances.free_balance(&bob), 60);
+        assert!(balances.total_issuance_is_consistent());
+    }
+
+    #[test]
+    fn force_set_balance_requires_root() {
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
+        let alice = "alice".to_string();
+
+        assert_eq!(
+            balances.force_set_balance(signed("alice"), alice.clone(), 100),
+            Err(DispatchError::BadOrigin)
+        );
+        assert_eq!(
+            balances.force_set_balance(RawOrigin::None, alice.clone(), 100),
+            Err(DispatchError::BadOrigin)
+        );
+        assert_eq!(balances.balance(&alice), 0);
+
+        assert_eq!(balances.force_set_balance(RawOrigin::Root, alice.clone(), 100), Ok(()));
+        assert_eq!(balances.balance(&alice), 100);
+        assert_eq!(
+            balances.take_events(),
+            vec![Event::BalanceSet { who: alice.clone(), amount: 100 }]
+        );
+
+        // Nothing is set, and no event is deposited, if the balance does not change or can't.
+        assert_eq!(balances.force_set_balance(RawOrigin::Root, alice.clone(), 100), Ok(()));
+        balances.set_lock(*b"vesting ", &alice, 50);
+        assert_eq!(
+            balances.force_set_balance(RawOrigin::Root, alice.clone(), 20),
+            Err(Error::LiquidityRestrictions.into())
+        );
+        assert_eq!(
+            balances.force_set_balance(RawOrigin::Root, "bob".to_string(), 5),
+            Err(Error::ExistentialDeposit.into())
+        );
+        assert_eq!(balances.balance(&alice), 100);
+        assert_eq!(balances.take_events(), vec![]);
+    }
+
+    #[test]
+    fn fees_are_charged() {
+        let mut balances = super::Pallet::<TestConfig>::new(&Storage::new());
+        let alice = "alice".to_string();
+        balances.make_free_balance_be(&alice, 100).unwrap();
+
+        assert_eq!(balances.withdraw_fee(&alice, 101), Err(Error::InsufficientBalance.into()));
+        assert_eq!(balances.withdraw_fee(&alice, 60), Ok(()));
+        assert_eq!(balances.balance(&alice), 40);
+
+        // Only the fee for the weight which was used is kept.
+        balances.refund_fee(&alice, 60, 25);
+        assert_eq!(balances.balance(&alice), 75);
+        assert_eq!(balances.take_events(), vec![Event::FeePaid { who: alice.clone(), amount: 25 }]);
+
+        // Paying a fee can't reap an account.
+        assert_eq!(balances.withdraw_fee(&alice, 70), Err(Error::KeepAlive.into()));
+        assert_eq!(balances.withdraw_fee(&alice, 65), Ok(()));
+
+        // An account reaped by its own extrinsic is not refunded.
+        assert!(balances.transfer_all(signed("alice"), "bob".to_string(), false).is_ok());
+        balances.take_events();
+        balances.refund_fee(&alice, 65, 0);
+        assert!(!balances.account_exists(&alice));
+        assert_eq!(balances.take_events(), vec![Event::FeePaid { who: alice, amount: 65 }]);
     }
 }
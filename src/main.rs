@@ -1,7 +1,10 @@
 mod balances;
+mod node;
 mod proof_of_existence;
+mod sudo;
 mod support;
 mod system;
+mod utility;
 
 use crate::support::Dispatch;
 
@@ -9,14 +12,18 @@ use crate::support::Dispatch;
 // Modules are configured for these types directly, and they satisfy all of our
 // trait requirements.
 mod types {
-    pub type AccountId = String;
+    pub type AccountId = crate::support::crypto::Public;
+    pub type Signature = crate::support::crypto::Signature;
     pub type Balance = u128;
     pub type BlockNumber = u32;
     pub type Nonce = u32;
-    pub type Extrinsic = crate::support::Extrinsic<AccountId, crate::RuntimeCall>;
-    pub type Header = crate::support::Header<BlockNumber>;
+    pub type Hash = crate::support::H256;
+    pub type Hashing = crate::support::BlakeTwo256;
+    pub type Extrinsic = crate::support::Extrinsic<AccountId, crate::RuntimeCall, Signature, Nonce>;
+    pub type Header = crate::support::Header<BlockNumber, Hash>;
     pub type Block = crate::support::Block<Header, Extrinsic>;
-    pub type Content = String;
+    pub type ContentHash = crate::support::H256;
+    pub type ContentHasher = crate::support::Sha256;
 }
 
 // This is our main Runtime.
@@ -27,94 +34,987 @@ pub struct Runtime {
     system: system::Pallet<Self>,
     balances: balances::Pallet<Self>,
     proof_of_existence: proof_of_existence::Pallet<Self>,
+    sudo: sudo::Pallet<Self>,
+    utility: utility::Pallet<Self>,
 }
 
 impl system::Config for Runtime {
     type AccountId = types::AccountId;
+    type RuntimeOrigin = system::RawOrigin<types::AccountId>;
     type BlockNumber = types::BlockNumber;
     type Nonce = types::Nonce;
+    type Hash = types::Hash;
+    type Hashing = types::Hashing;
+    type RuntimeEvent = RuntimeEvent;
+    const MAX_BLOCK_WEIGHT: support::Weight = 1_000;
 }
 
 impl balances::Config for Runtime {
     type Balance = types::Balance;
+    const EXISTENTIAL_DEPOSIT: types::Balance = 1;
+
+    // One unit of balance for every 10 units of weight.
+    fn weight_to_fee(weight: support::Weight) -> types::Balance {
+        (weight / 10).into()
+    }
 }
 
 impl proof_of_existence::Config for Runtime {
-    type Content = types::Content;
+    type ContentHash = types::ContentHash;
+    // Claims use the same hash as `sha256sum`, so anyone can check which file was notarized.
+    type ContentHasher = types::ContentHasher;
+    type Currency = balances::Pallet<Runtime>;
+    const CLAIM_DEPOSIT: types::Balance = 10;
+    const RENEWAL_FEE: types::Balance = 5;
+    const MAX_MEMO_LENGTH: u32 = 128;
+    const MAX_EXPIRED_PER_BLOCK: u32 = 16;
+}
+
+impl sudo::Config for Runtime {
+    type RuntimeCall = RuntimeCall;
+}
+
+impl utility::Config for Runtime {
+    type RuntimeCall = RuntimeCall;
+}
+
+// The fees of extrinsics are paid with the balances pallet.
+impl support::ChargeTransaction for Runtime {
+    type AccountId = types::AccountId;
+
+    fn withdraw_fee(
+        &mut self,
+        who: &types::AccountId,
+        weight: support::Weight,
+    ) -> support::DispatchResult {
+        self.balances.withdraw_fee(who, weight)
+    }
+
+    fn refund_fee(
+        &mut self,
+        who: &types::AccountId,
+        declared: support::Weight,
+        actual: support::Weight,
+    ) {
+        self.balances.refund_fee(who, declared, actual)
+    }
+}
+
+// The accounts reaped by the balances pallet lose their nonce. An account can be reaped and
+// created again by the same call, in which case it is kept.
+impl support::OnReapedAccounts for Runtime {
+    fn on_reaped_accounts(&mut self) {
+        for who in self.balances.take_reaped_accounts() {
+            if !self.balances.account_exists(&who) {
+                self.system.kill_account(&who);
+            }
+        }
+    }
 }
 
 fn main() {
-    // Create a new instance of the Runtime.
-    // It will instantiate with it all the modules it uses.
-    let mut runtime = Runtime::new();
-    let alice = "alice".to_string();
-    let bob = "bob".to_string();
-    let charlie = "charlie".to_string();
-
-    // Initialize the system with some initial balance.
-    runtime.balances.set_balance(&alice, 100);
-
-    // Here are the extrinsics in our block.
-    // You can add or remove these based on the modules and calls you have set up.
-    let block_1 = types::Block {
-        header: support::Header { block_number: 1 },
-        extrinsics: vec![
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::balances(balances::Call::transfer {
-                    to: bob.clone(),
-                    amount: 30,
-                }),
-            },
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::balances(balances::Call::transfer { to: charlie, amount: 20 }),
-            },
-        ],
-    };
+    // Everything our node can do is a command, see `node::cli::USAGE`.
+    let result = node::cli::Cli::parse(std::env::args().skip(1)).and_then(node::cli::run);
+    if let Err(error) = result {
+        eprintln!("Error: {error}");
+        std::process::exit(1);
+    }
+}
 
-    let block_2 = types::Block {
-        header: support::Header { block_number: 2 },
-        extrinsics: vec![
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
-                    claim: "Hello, world!".to_string(),
-                }),
-            },
-            support::Extrinsic {
-                caller: bob.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
-                    claim: "Hello, world!".to_string(),
-                }),
-            },
-        ],
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::support::keyring::Keyring;
+    use crate::support::{
+        BuildGenesisConfig, Decode, DispatchError, Encode, GetWeight, Hasher, ModuleError,
     };
 
-    let block_3 = types::Block {
-        header: support::Header { block_number: 3 },
-        extrinsics: vec![
-            support::Extrinsic {
-                caller: alice,
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
-                    claim: "Hello, world!".to_string(),
+    // Create an extrinsic signed by one of the test accounts.
+    fn signed(who: Keyring, call: RuntimeCall, nonce: types::Nonce) -> types::Extrinsic {
+        types::Extrinsic::new_signed_by(&who.pair(), call, nonce, &types::Hash::default())
+    }
+
+    #[test]
+    fn dispatch_errors_are_tagged_with_pallet_index() {
+        let mut runtime = Runtime::new();
+
+        let res = runtime.dispatch(
+            system::RawOrigin::Signed(Keyring::Alice.public()),
+            RuntimeCall::balances(balances::Call::transfer {
+                to: Keyring::Bob.public(),
+                amount: 10,
+            }),
+        );
+        assert_eq!(
+            res.map_err(|e| e.error),
+            Err(DispatchError::Module(ModuleError {
+                index: Some(1),
+                error: 0,
+                message: Some("InsufficientBalance"),
+            }))
+        );
+
+        let res = runtime.dispatch(
+            system::RawOrigin::Signed(Keyring::Alice.public()),
+            RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
+                claim: types::ContentHasher::hash(b"Hello, world!"),
+            }),
+        );
+        let expected = DispatchError::from(proof_of_existence::Error::ClaimNotFound).in_pallet(2);
+        assert_eq!(res.map_err(|e| e.error), Err(expected));
+    }
+
+    #[test]
+    fn failed_dispatch_reverts_storage() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        runtime.balances.make_free_balance_be(&alice, 100).unwrap();
+
+        let res = support::with_transaction(&mut runtime, |runtime| {
+            runtime.balances.make_free_balance_be(&alice, 0).unwrap();
+            runtime.dispatch(
+                system::RawOrigin::Signed(alice),
+                RuntimeCall::balances(balances::Call::transfer {
+                    to: Keyring::Bob.public(),
+                    amount: 10,
                 }),
-            },
-            support::Extrinsic {
-                caller: bob,
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
-                    claim: "Hello, world!".to_string(),
+            )
+        });
+        assert!(res.is_err());
+        assert_eq!(runtime.balances.balance(&alice), 100);
+
+        // Changes of a successful dispatch are kept.
+        let res = support::with_transaction(&mut runtime, |runtime| {
+            runtime.dispatch(
+                system::RawOrigin::Signed(alice),
+                RuntimeCall::balances(balances::Call::transfer {
+                    to: Keyring::Bob.public(),
+                    amount: 10,
                 }),
-            },
-        ],
-    };
+            )
+        });
+        assert!(res.is_ok());
+        assert_eq!(runtime.balances.balance(&alice), 90);
+    }
+
+    #[test]
+    fn block_events_are_recorded() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        runtime.balances.make_free_balance_be(&alice, 100).unwrap();
+
+        let block = runtime
+            .build_block(vec![
+                signed(
+                    Keyring::Alice,
+                    RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 30 }),
+                    0,
+                ),
+                signed(
+                    Keyring::Bob,
+                    RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
+                        claim: types::ContentHasher::hash(b"Hello, world!"),
+                    }),
+                    0,
+                ),
+            ])
+            .unwrap();
+        // Building a block does not change the state.
+        assert_eq!(runtime.system.block_number(), 0);
+        assert_eq!(runtime.balances.balance(&alice), 100);
+        runtime.execute_block(block).unwrap();
+
+        let error = DispatchError::from(proof_of_existence::Error::ClaimNotFound).in_pallet(2);
+        assert_eq!(
+            runtime.system.events(),
+            &[
+                system::EventRecord {
+                    phase: system::Phase::ApplyExtrinsic(0),
+                    event: RuntimeEvent::balances(balances::Event::Transfer {
+                        from: alice,
+                        to: bob,
+                        amount: 30,
+                    }),
+                },
+                system::EventRecord {
+                    phase: system::Phase::ApplyExtrinsic(0),
+                    event: RuntimeEvent::balances(balances::Event::FeePaid {
+                        who: alice,
+                        amount: 15,
+                    }),
+                },
+                system::EventRecord {
+                    phase: system::Phase::ApplyExtrinsic(0),
+                    event: RuntimeEvent::system(system::Event::ExtrinsicSuccess),
+                },
+                system::EventRecord {
+                    phase: system::Phase::ApplyExtrinsic(1),
+                    event: RuntimeEvent::balances(balances::Event::FeePaid {
+                        who: bob,
+                        amount: 10,
+                    }),
+                },
+                system::EventRecord {
+                    phase: system::Phase::ApplyExtrinsic(1),
+                    event: RuntimeEvent::system(system::Event::ExtrinsicFailed { error }),
+                },
+            ]
+        );
+
+        // The events of the previous block are cleared when a new block starts.
+        let block = runtime.build_block(vec![]).unwrap();
+        assert_eq!(runtime.system.events().len(), 5);
+        runtime.execute_block(block).unwrap();
+        assert!(runtime.system.events().is_empty());
+    }
+
+    #[test]
+    fn blocks_are_chained_and_verified() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        runtime.balances.make_free_balance_be(&alice, 100).unwrap();
+        let transfer = |nonce| {
+            let call = balances::Call::transfer { to: Keyring::Bob.public(), amount: 30 };
+            signed(Keyring::Alice, RuntimeCall::balances(call), nonce)
+        };
+
+        let block_1 = runtime.build_block(vec![transfer(0)]).unwrap();
+        assert_eq!(block_1.header.parent_hash, types::Hash::default());
+        assert_eq!(block_1.header.block_number, 1);
+        let block_1_hash = block_1.header.hash::<types::Hashing>();
+
+        // A block with a wrong state root, extrinsics root or parent hash is rejected, and none of
+        // its changes are kept.
+        let mut bad_block = block_1.clone();
+        bad_block.header.state_root = types::Hash::default();
+        assert_eq!(
+            runtime.execute_block(bad_block),
+            Err(DispatchError::Other("state root does not match the state after the block"))
+        );
+        let mut bad_block = block_1.clone();
+        bad_block.extrinsics.push(transfer(1));
+        assert_eq!(
+            runtime.execute_block(bad_block),
+            Err(DispatchError::Other("extrinsics root does not match the extrinsics of the block"))
+        );
+        let mut bad_block = block_1.clone();
+        bad_block.header.parent_hash = block_1_hash;
+        assert_eq!(
+            runtime.execute_block(bad_block),
+            Err(DispatchError::Other("parent hash does not match the last block"))
+        );
+        assert_eq!(runtime.system.block_number(), 0);
+        assert_eq!(runtime.balances.balance(&alice), 100);
+
+        runtime.execute_block(block_1.clone()).unwrap();
+        assert_eq!(runtime.system.parent_hash(), block_1_hash);
+        // Bob's account did not exist yet, so Alice paid the full fee of 15.
+        assert_eq!(runtime.balances.balance(&alice), 55);
+
+        // The same block can't be imported twice, and the next block builds on top of it.
+        assert!(runtime.execute_block(block_1).is_err());
+        let block_2 = runtime.build_block(vec![transfer(1)]).unwrap();
+        assert_eq!(block_2.header.parent_hash, block_1_hash);
+        runtime.execute_block(block_2).unwrap();
+        assert_eq!(runtime.balances.balance(&alice), 15);
+    }
+
+    #[test]
+    fn signatures_are_verified() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        runtime.balances.make_free_balance_be(&alice, 100).unwrap();
+        let call = RuntimeCall::balances(balances::Call::transfer {
+            to: Keyring::Bob.public(),
+            amount: 30,
+        });
+        let bad_signature = DispatchError::from(system::Error::BadSignature).in_pallet(0);
+
+        // Bob can't sign for Alice.
+        let mut extrinsic = signed(Keyring::Bob, call.clone(), 0);
+        extrinsic.signature.as_mut().unwrap().signer = alice;
+        assert_eq!(runtime.build_block(vec![extrinsic]), Err(bad_signature));
+
+        // The signature covers the call, the nonce, and the genesis hash.
+        let mut extrinsic = signed(Keyring::Alice, call.clone(), 0);
+        extrinsic.call = RuntimeCall::balances(balances::Call::transfer { to: alice, amount: 30 });
+        assert_eq!(runtime.build_block(vec![extrinsic]), Err(bad_signature));
+        let mut extrinsic = signed(Keyring::Alice, call.clone(), 0);
+        extrinsic.signature.as_mut().unwrap().nonce = 1;
+        assert_eq!(runtime.build_block(vec![extrinsic]), Err(bad_signature));
+        let extrinsic = types::Extrinsic::new_signed_by(
+            &Keyring::Alice.pair(),
+            call.clone(),
+            0,
+            &types::Hashing::hash(b"another chain"),
+        );
+        assert_eq!(runtime.build_block(vec![extrinsic]), Err(bad_signature));
+
+        // A block including an invalid extrinsic is rejected as a whole.
+        let mut block = runtime.build_block(vec![signed(Keyring::Alice, call.clone(), 0)]).unwrap();
+        block.extrinsics[0].signature.as_mut().unwrap().signer = Keyring::Charlie.public();
+        block.header.extrinsics_root = types::Hashing::hash_of(&block.extrinsics);
+        assert_eq!(runtime.execute_block(block), Err(bad_signature));
+        assert_eq!(runtime.balances.balance(&alice), 100);
+
+        // Unsigned extrinsics are dispatched from the `None` origin, which none of our calls
+        // accept, and a failed unsigned extrinsic makes the block invalid.
+        assert_eq!(
+            runtime.build_block(vec![types::Extrinsic::new_unsigned(call)]),
+            Err(DispatchError::from(system::Error::UnsignedNotAllowed).in_pallet(0))
+        );
+    }
+
+    #[test]
+    fn privileged_calls_require_root() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        runtime.balances.make_free_balance_be(&alice, 100).unwrap();
+        let set_balance =
+            RuntimeCall::balances(balances::Call::force_set_balance { who: alice, amount: 1_000 });
+
+        // Signed extrinsics can't make root calls.
+        let block =
+            runtime.build_block(vec![signed(Keyring::Alice, set_balance.clone(), 0)]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert_eq!(
+            runtime.system.events().last().unwrap().event,
+            RuntimeEvent::system(system::Event::ExtrinsicFailed {
+                error: DispatchError::BadOrigin
+            })
+        );
+        assert_eq!(runtime.balances.balance(&alice), 90);
+
+        // The root origin can only be used from the runtime itself.
+        let res = runtime.dispatch(system::RawOrigin::Root, set_balance);
+        assert_eq!(res, Ok(support::PostDispatchInfo { actual_weight: None }));
+        assert_eq!(runtime.balances.balance(&alice), 1_000);
+    }
+
+    #[test]
+    fn sudo_dispatches_calls_as_root() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        let charlie = Keyring::Charlie.public();
+        sudo::GenesisConfig { key: Some(alice) }.build(&mut runtime.sudo, 0).unwrap();
+        runtime.balances.make_free_balance_be(&bob, 100).unwrap();
+        let sudo = |call| RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(call) });
+        let set_balance =
+            RuntimeCall::balances(balances::Call::force_set_balance { who: charlie, amount: 50 });
+        let last_event = |runtime: &Runtime| runtime.system.events().last().unwrap().event.clone();
+
+        // Only the sudo key can make sudo calls.
+        let res = runtime.dispatch(system::RawOrigin::Signed(bob), sudo(set_balance.clone()));
+        assert_eq!(
+            res.map_err(|e| e.error),
+            Err(DispatchError::from(sudo::Error::RequireSudo).in_pallet(3))
+        );
+        assert_eq!(runtime.balances.balance(&charlie), 0);
+
+        // The weight of a sudo call includes the weight of the call it dispatches.
+        let res = runtime.dispatch(system::RawOrigin::Signed(alice), sudo(set_balance.clone()));
+        assert_eq!(res, Ok(support::PostDispatchInfo { actual_weight: Some(200) }));
+        assert_eq!(runtime.balances.balance(&charlie), 50);
+        assert_eq!(
+            last_event(&runtime),
+            RuntimeEvent::sudo(sudo::Event::Sudid { sudo_result: Ok(()) })
+        );
+
+        // A failed call is reverted, but the sudo call succeeds, and records the error.
+        let transfer = RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 10 });
+        let res = runtime.dispatch(system::RawOrigin::Signed(alice), sudo(transfer.clone()));
+        assert!(res.is_ok());
+        assert_eq!(
+            last_event(&runtime),
+            RuntimeEvent::sudo(sudo::Event::Sudid { sudo_result: Err(DispatchError::BadOrigin) })
+        );
+
+        // The sudo key can dispatch signed calls on behalf of other accounts.
+        let sudo_as = RuntimeCall::sudo(sudo::Call::sudo_as {
+            who: bob,
+            call: Box::new(RuntimeCall::balances(balances::Call::transfer {
+                to: charlie,
+                amount: 30,
+            })),
+        });
+        let res = runtime.dispatch(system::RawOrigin::Signed(alice), sudo_as.clone());
+        assert!(res.is_ok());
+        assert_eq!(runtime.balances.balance(&bob), 70);
+        assert_eq!(runtime.balances.balance(&charlie), 80);
+        assert_eq!(
+            last_event(&runtime),
+            RuntimeEvent::sudo(sudo::Event::SudoAsDone { sudo_result: Ok(()) })
+        );
+
+        // The root origin is not the sudo key, so sudo calls can't be nested.
+        let res = runtime.dispatch(system::RawOrigin::Signed(alice), sudo(sudo_as));
+        assert!(res.is_ok());
+        assert_eq!(
+            last_event(&runtime),
+            RuntimeEvent::sudo(sudo::Event::Sudid { sudo_result: Err(DispatchError::BadOrigin) })
+        );
+        assert_eq!(runtime.balances.balance(&bob), 70);
+
+        // The sudo key can hand over its rights.
+        let set_key = RuntimeCall::sudo(sudo::Call::set_key { new: bob });
+        assert!(runtime.dispatch(system::RawOrigin::Signed(alice), set_key).is_ok());
+        assert_eq!(runtime.sudo.key(), Some(bob));
+        assert_eq!(
+            last_event(&runtime),
+            RuntimeEvent::sudo(sudo::Event::KeyChanged { old: Some(alice), new: bob })
+        );
+        let res = runtime.dispatch(system::RawOrigin::Signed(alice), sudo(set_balance));
+        assert_eq!(
+            res.map_err(|e| e.error),
+            Err(DispatchError::from(sudo::Error::RequireSudo).in_pallet(3))
+        );
+    }
+
+    #[test]
+    fn utility_batches_calls() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        runtime.balances.make_free_balance_be(&alice, 1_000).unwrap();
+        let transfer = |amount| RuntimeCall::balances(balances::Call::transfer { to: bob, amount });
+        let calls = vec![transfer(10), transfer(2_000), transfer(10)];
+        let insufficient_balance =
+            DispatchError::from(balances::Error::InsufficientBalance).in_pallet(1);
+        let utility_events = |runtime: &Runtime| {
+            let events = runtime.system.events().iter().filter_map(|record| match &record.event {
+                RuntimeEvent::utility(event) => Some(event.clone()),
+                _ => None,
+            });
+            events.collect::<Vec<_>>()
+        };
+
+        // A whole batch is a single extrinsic, with a single nonce.
+        let batch = RuntimeCall::utility(utility::Call::batch { calls: calls.clone() });
+        let block = runtime.build_block(vec![signed(Keyring::Alice, batch, 0)]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert_eq!(runtime.system.account_nonce(&alice), 1);
+
+        // `batch` stops at the first failed call, and keeps the calls before it.
+        assert_eq!(runtime.balances.balance(&bob), 10);
+        assert_eq!(
+            utility_events(&runtime),
+            vec![
+                utility::Event::ItemCompleted,
+                utility::Event::BatchInterrupted { index: 1, error: insufficient_balance },
+            ]
+        );
+
+        // `force_batch` dispatches every call, and reverts the failed ones only.
+        runtime.system.initialize_block();
+        let force_batch = RuntimeCall::utility(utility::Call::force_batch { calls: calls.clone() });
+        assert!(runtime.dispatch(system::RawOrigin::Signed(alice), force_batch).is_ok());
+        assert_eq!(runtime.balances.balance(&bob), 30);
+        assert_eq!(
+            utility_events(&runtime),
+            vec![
+                utility::Event::ItemCompleted,
+                utility::Event::ItemFailed { error: insufficient_balance },
+                utility::Event::ItemCompleted,
+                utility::Event::BatchCompletedWithErrors,
+            ]
+        );
+
+        // `batch_all` reverts every call if one of them fails.
+        let batch_all = RuntimeCall::utility(utility::Call::batch_all { calls });
+        let res = runtime.dispatch(system::RawOrigin::Signed(alice), batch_all);
+        assert_eq!(res.map_err(|e| e.error), Err(insufficient_balance));
+        assert_eq!(runtime.balances.balance(&bob), 30);
+
+        // A batch only uses the weight of its calls, and of dispatching them.
+        let batch_all = RuntimeCall::utility(utility::Call::batch_all {
+            calls: vec![transfer(10), transfer(10)],
+        });
+        assert_eq!(batch_all.weight(), 2 * (150 + 10));
+        let res = runtime.dispatch(system::RawOrigin::Signed(alice), batch_all);
+        assert_eq!(res, Ok(support::PostDispatchInfo { actual_weight: Some(2 * (100 + 10)) }));
+        assert_eq!(runtime.balances.balance(&bob), 50);
+        assert_eq!(utility_events(&runtime).last(), Some(&utility::Event::BatchCompleted));
+    }
+
+    #[test]
+    fn nonces_prevent_replay() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        runtime.balances.make_free_balance_be(&alice, 100).unwrap();
+        let transfer = |nonce| {
+            signed(
+                Keyring::Alice,
+                RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 10 }),
+                nonce,
+            )
+        };
+        let stale = DispatchError::from(system::Error::StaleNonce).in_pallet(0);
+        let future = DispatchError::from(system::Error::FutureNonce).in_pallet(0);
+
+        // Nonces must be used in order, starting from zero.
+        assert_eq!(runtime.build_block(vec![transfer(1)]), Err(future));
+        assert_eq!(runtime.build_block(vec![transfer(0), transfer(0)]), Err(stale));
+        assert_eq!(runtime.build_block(vec![transfer(0), transfer(2)]), Err(future));
+
+        let block = runtime.build_block(vec![transfer(0), transfer(1)]).unwrap();
+        runtime.execute_block(block.clone()).unwrap();
+        assert_eq!(runtime.system.account_nonce(&alice), 2);
+        assert_eq!(runtime.balances.balance(&bob), 20);
 
-    // Execute the extrinsics which make up our blocks.
-    // If there are any errors, our system panics, since we should not execute invalid blocks.
-    runtime.execute_block(block_1).expect("invalid block");
-    runtime.execute_block(block_2).expect("invalid block");
-    runtime.execute_block(block_3).expect("invalid block");
+        // The same extrinsics can't be replayed in a later block.
+        let mut replay = runtime.build_block(vec![]).unwrap();
+        replay.extrinsics = block.extrinsics;
+        replay.header.extrinsics_root = types::Hashing::hash_of(&replay.extrinsics);
+        assert_eq!(runtime.execute_block(replay), Err(stale));
+        assert_eq!(runtime.system.account_nonce(&alice), 2);
+        assert_eq!(runtime.balances.balance(&bob), 20);
 
-    // Simply print the debug format of our runtime state.
-    println!("{runtime:#?}");
-}
\ No newline at end of file
+        // A failed call still uses up its nonce.
+        let call = RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 1000 });
+        let block = runtime.build_block(vec![signed(Keyring::Alice, call, 2)]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert_eq!(runtime.system.account_nonce(&alice), 3);
+    }
+
+    #[test]
+    fn reaped_accounts_lose_their_nonce() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        runtime.balances.make_free_balance_be(&alice, 1_000).unwrap();
+        let transfer_all =
+            RuntimeCall::balances(balances::Call::transfer_all { to: bob, keep_alive: false });
+
+        let block = runtime.build_block(vec![signed(Keyring::Alice, transfer_all, 0)]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert!(!runtime.balances.account_exists(&alice));
+        assert_eq!(runtime.system.account_nonce(&alice), 0);
+        // Alice paid the fee for the whole declared weight, and sent the rest to Bob.
+        assert_eq!(runtime.balances.balance(&bob), 1_000 - 15);
+
+        // Once the account is created again, its nonces start from zero.
+        let transfer = RuntimeCall::balances(balances::Call::transfer { to: alice, amount: 100 });
+        let block = runtime.build_block(vec![signed(Keyring::Bob, transfer, 0)]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert_eq!(runtime.balances.balance(&alice), 100);
+        let call = RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 10 });
+        assert!(runtime.build_block(vec![signed(Keyring::Alice, call, 0)]).is_ok());
+        assert!(runtime.balances.total_issuance_is_consistent());
+    }
+
+    #[test]
+    fn expired_claims_are_pruned_at_the_end_of_the_block() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        runtime.balances.make_free_balance_be(&alice, 1_000).unwrap();
+        let doc = types::ContentHasher::hash(b"doc");
+        let create_claim =
+            RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+                claim: doc,
+                memo: None,
+                expires: Some(2),
+            });
+
+        // The claim is active until the end of block 2, and its deposit is returned when it is
+        // pruned.
+        let block = runtime.build_block(vec![signed(Keyring::Alice, create_claim, 0)]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert!(runtime.proof_of_existence.is_active(&doc, 1));
+        assert_eq!(runtime.proof_of_existence.verify(b"doc", 2), Some(alice));
+        assert_eq!(runtime.balances.reserved_balance(&alice), 10);
+
+        let block = runtime.build_block(vec![]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert_eq!(runtime.proof_of_existence.get_claim(&doc), None);
+        assert_eq!(runtime.balances.reserved_balance(&alice), 0);
+        let expired = RuntimeEvent::proof_of_existence(proof_of_existence::Event::ClaimExpired {
+            who: alice,
+            claim: doc,
+        });
+        assert_eq!(
+            runtime.system.events().last(),
+            Some(&system::EventRecord { phase: system::Phase::Finalization, event: expired })
+        );
+    }
+
+    #[test]
+    fn fees_and_weight_are_charged() {
+        let mut runtime = Runtime::new();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        runtime.balances.make_free_balance_be(&alice, 1_000).unwrap();
+        let transfer = |nonce| {
+            signed(
+                Keyring::Alice,
+                RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 1 }),
+                nonce,
+            )
+        };
+
+        // The first transfer creates the account of Bob, and uses all of its declared weight. The
+        // second one uses less, and the fee for its unused weight is refunded. The proof of
+        // existence pallet then reads the claims expiring in the genesis block and in this one.
+        let read_expiries = proof_of_existence::READ_EXPIRIES_WEIGHT;
+        let block = runtime.build_block(vec![transfer(0), transfer(1)]).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert_eq!(runtime.system.block_weight(), 150 + 100 + 2 * read_expiries);
+        assert_eq!(runtime.balances.balance(&alice), 1_000 - 2 - 15 - 10);
+        // The fees are burned.
+        assert_eq!(runtime.balances.total_issuance(), 1_000 - 15 - 10);
+        assert!(runtime.balances.total_issuance_is_consistent());
+
+        // The declared weight of each extrinsic must fit in the block, even if some of it is
+        // refunded later.
+        let block = runtime.build_block((2..11).map(transfer).collect()).unwrap();
+        runtime.execute_block(block).unwrap();
+        assert_eq!(runtime.system.block_weight(), 900 + read_expiries);
+        assert_eq!(
+            runtime.build_block((11..21).map(transfer).collect()),
+            Err(DispatchError::from(system::Error::ExhaustsResources).in_pallet(0))
+        );
+
+        // Extrinsics whose signer can't pay the fee are invalid.
+        let call = RuntimeCall::balances(balances::Call::transfer { to: alice, amount: 0 });
+        assert_eq!(
+            runtime.build_block(vec![signed(Keyring::Charlie, call, 0)]),
+            Err(DispatchError::from(system::Error::CannotPayFees).in_pallet(0))
+        );
+    }
+
+    #[test]
+    fn calls_and_blocks_are_encoded() {
+        let mut runtime = Runtime::new();
+        runtime.balances.make_free_balance_be(&Keyring::Alice.public(), 100).unwrap();
+        let bob = Keyring::Bob.public();
+
+        // A runtime call is the pallet index, the call index, and the arguments.
+        let call = RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 30 });
+        let mut expected = vec![1, 0];
+        expected.extend_from_slice(&bob.0);
+        expected.extend_from_slice(&30u128.to_le_bytes());
+        assert_eq!(call.encode(), expected);
+        assert_eq!(RuntimeCall::decode_all(&expected), Ok(call.clone()));
+
+        let claim = RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
+            claim: support::H256([7; 32]),
+        });
+        assert_eq!(claim.encode(), [vec![2, 1], vec![7; 32]].concat());
+        assert_eq!(RuntimeCall::decode_all(&claim.encode()), Ok(claim.clone()));
+
+        // Nested calls are encoded after the index of the call which contains them.
+        let sudo = RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(claim.clone()) });
+        assert_eq!(sudo.encode(), [vec![3, 0, 2, 1], vec![7; 32]].concat());
+        assert_eq!(RuntimeCall::decode_all(&sudo.encode()), Ok(sudo));
+
+        // Deeply nested calls are rejected, rather than overflowing the stack while decoding.
+        let nested = |depth| [[3, 0].repeat(depth), vec![2, 1], vec![7; 32]].concat();
+        assert!(RuntimeCall::decode_all(&nested(100)).is_ok());
+        assert_eq!(
+            RuntimeCall::decode_all(&nested(1_000_000)),
+            Err(support::codec::Error("Maximum depth exceeded"))
+        );
+
+        // Unknown pallets and calls are rejected.
+        let invalid = Err(support::codec::Error("Invalid enum variant"));
+        assert_eq!(RuntimeCall::decode_all(&[0, 0]), invalid);
+        assert_eq!(
+            RuntimeCall::decode_all(&[2, 9]),
+            Err(support::codec::Error("Invalid call index"))
+        );
+
+        // Whole blocks can be decoded and executed.
+        let block = runtime
+            .build_block(vec![signed(Keyring::Alice, call, 0), signed(Keyring::Bob, claim, 0)])
+            .unwrap();
+        let decoded = types::Block::decode_all(&block.encode()).unwrap();
+        assert_eq!(decoded, block);
+        runtime.execute_block(decoded).unwrap();
+        // Bob paid 10 for his claim, which failed.
+        assert_eq!(runtime.balances.balance(&bob), 20);
+    }
+
+    #[test]
+    fn genesis_block_starts_the_chain() {
+        let alice = Keyring::Alice.public();
+        let mut genesis = RuntimeGenesisConfig::default();
+        genesis.system.block_number = 10;
+        genesis.balances.balances = vec![(alice, 100)];
+        let doc = types::ContentHasher::hash(b"doc");
+        genesis.proof_of_existence.claims = vec![(doc, alice)];
+        let mut runtime = Runtime::from_genesis(&genesis).unwrap();
+
+        assert_eq!(runtime.system.block_number(), 10);
+        assert_eq!(runtime.balances.balance(&alice), 100);
+        assert_eq!(runtime.proof_of_existence.get_claim(&doc), Some(alice));
+        // Genesis claims are created in the genesis block.
+        assert_eq!(runtime.proof_of_existence.claim_info(&doc).unwrap().created, 10);
+
+        // The genesis hash commits to the genesis state, and is the parent of the first block.
+        let genesis_header = runtime.genesis_header();
+        assert_eq!(genesis_header.state_root, runtime.state_root());
+        let genesis_hash = genesis_header.hash::<types::Hashing>();
+        assert_eq!(runtime.system.genesis_hash(), genesis_hash);
+
+        let call = RuntimeCall::balances(balances::Call::transfer {
+            to: Keyring::Bob.public(),
+            amount: 10,
+        });
+        let extrinsic =
+            types::Extrinsic::new_signed_by(&Keyring::Alice.pair(), call.clone(), 0, &genesis_hash);
+        let block = runtime.build_block(vec![extrinsic]).unwrap();
+        assert_eq!(block.header.block_number, 11);
+        assert_eq!(block.header.parent_hash, genesis_hash);
+        runtime.execute_block(block).unwrap();
+        assert_eq!(runtime.balances.balance(&alice), 75);
+
+        // Extrinsics signed for another chain are rejected.
+        assert_eq!(
+            runtime.build_block(vec![signed(Keyring::Alice, call, 1)]),
+            Err(DispatchError::from(system::Error::BadSignature).in_pallet(0))
+        );
+    }
+
+    #[test]
+    fn light_clients_verify_state_against_headers() {
+        let (alice, bob) = (Keyring::Alice.public(), Keyring::Bob.public());
+        let mut genesis = RuntimeGenesisConfig::default();
+        genesis.balances.balances = vec![(alice, 100)];
+        let mut runtime = Runtime::from_genesis(&genesis).unwrap();
+        let genesis_root = runtime.genesis_header().state_root;
+
+        let doc = types::ContentHasher::hash(b"doc");
+        let calls = [
+            RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 30 }),
+            RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+                claim: doc,
+                memo: None,
+                expires: None,
+            }),
+        ];
+        let genesis_hash = runtime.system.genesis_hash();
+        let extrinsics = calls.into_iter().zip(0..).map(|(call, nonce)| {
+            types::Extrinsic::new_signed_by(&Keyring::Alice.pair(), call, nonce, &genesis_hash)
+        });
+        let block = runtime.build_block(extrinsics.collect()).unwrap();
+        let header = block.header.clone();
+        runtime.execute_block(block).unwrap();
+
+        // The light client only holds the header of the block.
+        let proof = runtime.balances.prove_balance(&bob);
+        let verify = balances::Pallet::<Runtime>::verify_balance;
+        assert_eq!(verify(&header.state_root, &bob, &proof), Ok(30));
+        assert_eq!(verify(&genesis_root, &bob, &proof), Err(support::ProofError::InvalidNode));
+        let charlie = Keyring::Charlie.public();
+        let proof = runtime.balances.prove_balance(&charlie);
+        assert_eq!(verify(&header.state_root, &charlie, &proof), Ok(0));
+
+        let proof = runtime.proof_of_existence.prove_claim(&doc);
+        let verify = proof_of_existence::Pallet::<Runtime>::verify_claim;
+        let info = verify(&header.state_root, &doc, &proof).unwrap().unwrap();
+        assert_eq!((info.owner, info.expires), (alice, None));
+        let unknown = types::ContentHasher::hash(b"unknown");
+        let proof = runtime.proof_of_existence.prove_claim(&unknown);
+        assert_eq!(verify(&header.state_root, &unknown, &proof), Ok(None));
+    }
+
+    // A runtime of pallets which only record when their hooks are called, to check the order the
+    // runtime calls them in.
+    #[allow(dead_code)]
+    mod hooks {
+        use crate::support::Dispatch;
+        use crate::{support, system};
+        use std::cell::RefCell;
+
+        thread_local! {
+            // The hooks called so far, as the name of the pallet and the name of the hook.
+            pub static CALLED: RefCell<Vec<(&'static str, &'static str)>> = const {
+                RefCell::new(Vec::new())
+            };
+        }
+
+        macro_rules! recording_pallet {
+            ($name:ident) => {
+                pub mod $name {
+                    use crate::support::{self, Hooks, Weight};
+
+                    pub trait Config: crate::system::Config + AsMut<Pallet<Self>> + Sized {}
+
+                    #[derive(Debug)]
+                    pub struct Pallet<T: Config> {
+                        events: Vec<Event<T>>,
+                    }
+
+                    #[derive(Debug)]
+                    pub struct GenesisConfig<T: Config>(core::marker::PhantomData<T>);
+
+                    impl<T: Config> Default for GenesisConfig<T> {
+                        fn default() -> Self {
+                            Self(core::marker::PhantomData)
+                        }
+                    }
+
+                    impl<T: Config> support::BuildGenesisConfig<Pallet<T>, T::BlockNumber>
+                        for GenesisConfig<T>
+                    {
+                        fn build(
+                            &self,
+                            _: &mut Pallet<T>,
+                            _: T::BlockNumber,
+                        ) -> support::DispatchResult {
+                            Ok(())
+                        }
+                    }
+
+                    #[macros::event]
+                    pub enum Event<T: Config> {
+                        Recorded { n: T::BlockNumber },
+                    }
+
+                    impl<T: Config> Pallet<T> {
+                        pub fn new(_: &support::Storage) -> Self {
+                            Self { events: Vec::new() }
+                        }
+
+                        pub fn take_events(&mut self) -> Vec<Event<T>> {
+                            core::mem::take(&mut self.events)
+                        }
+
+                        fn record(hook: &'static str) {
+                            super::CALLED.with(|called| {
+                                called.borrow_mut().push((stringify!($name), hook))
+                            });
+                        }
+                    }
+
+                    impl<T: Config> Hooks<T, T::BlockNumber> for Pallet<T> {
+                        fn on_initialize(_: &mut T, _: T::BlockNumber) -> Weight {
+                            Self::record("on_initialize");
+                            1
+                        }
+
+                        fn on_finalize(_: &mut T, _: T::BlockNumber) {
+                            Self::record("on_finalize");
+                        }
+
+                        fn on_idle(_: &mut T, _: T::BlockNumber, _: Weight) -> Weight {
+                            Self::record("on_idle");
+                            1
+                        }
+
+                        fn on_runtime_upgrade(_: &mut T) -> Weight {
+                            Self::record("on_runtime_upgrade");
+                            1
+                        }
+                    }
+
+                    // The runtime needs a call in every pallet.
+                    #[macros::call]
+                    impl<T: Config> Pallet<T> {
+                        #[call_index(0)]
+                        #[weight(0)]
+                        pub fn record_block(
+                            &mut self,
+                            _origin: T::RuntimeOrigin,
+                            n: T::BlockNumber,
+                        ) -> support::DispatchResult {
+                            self.events.push(Event::Recorded { n });
+                            Ok(())
+                        }
+                    }
+                }
+            };
+        }
+
+        recording_pallet!(zeta);
+        recording_pallet!(alpha);
+
+        mod types {
+            pub type AccountId = crate::support::crypto::Public;
+            pub type Signature = crate::support::crypto::Signature;
+            pub type BlockNumber = u32;
+            pub type Nonce = u32;
+            pub type Hash = crate::support::H256;
+            pub type Extrinsic =
+                crate::support::Extrinsic<AccountId, super::RuntimeCall, Signature, Nonce>;
+            pub type Header = crate::support::Header<BlockNumber, Hash>;
+            pub type Block = crate::support::Block<Header, Extrinsic>;
+        }
+
+        // The pallets are not in the alphabetical order, which the hooks must not follow.
+        #[derive(Debug)]
+        #[macros::runtime]
+        pub struct Runtime {
+            system: system::Pallet<Self>,
+            zeta: zeta::Pallet<Self>,
+            alpha: alpha::Pallet<Self>,
+        }
+
+        impl system::Config for Runtime {
+            type AccountId = types::AccountId;
+            type RuntimeOrigin = system::RawOrigin<types::AccountId>;
+            type BlockNumber = types::BlockNumber;
+            type Nonce = types::Nonce;
+            type Hash = types::Hash;
+            type Hashing = support::BlakeTwo256;
+            type RuntimeEvent = RuntimeEvent;
+            const MAX_BLOCK_WEIGHT: support::Weight = 1_000;
+        }
+
+        impl zeta::Config for Runtime {}
+
+        impl alpha::Config for Runtime {}
+
+        impl support::ChargeTransaction for Runtime {
+            type AccountId = types::AccountId;
+
+            fn withdraw_fee(
+                &mut self,
+                _: &types::AccountId,
+                _: support::Weight,
+            ) -> support::DispatchResult {
+                Ok(())
+            }
+
+            fn refund_fee(
+                &mut self,
+                _: &types::AccountId,
+                _: support::Weight,
+                _: support::Weight,
+            ) {
+            }
+        }
+
+        impl support::OnReapedAccounts for Runtime {
+            fn on_reaped_accounts(&mut self) {}
+        }
+
+        #[test]
+        fn hooks_are_called_in_the_order_of_the_runtime() {
+            use support::Hooks;
+
+            let mut runtime = Runtime::new();
+            let block = runtime.build_block(vec![]).unwrap();
+            CALLED.with(|called| called.borrow_mut().clear());
+            runtime.execute_block(block).unwrap();
+            // The weight used by each hook is taken from the block.
+            assert_eq!(runtime.system.block_weight(), 4);
+            assert_eq!(Runtime::on_runtime_upgrade(&mut runtime), 2);
+            assert_eq!(
+                CALLED.with(|called| called.take()),
+                vec![
+                    ("zeta", "on_initialize"),
+                    ("alpha", "on_initialize"),
+                    ("zeta", "on_idle"),
+                    ("alpha", "on_idle"),
+                    ("zeta", "on_finalize"),
+                    ("alpha", "on_finalize"),
+                    ("zeta", "on_runtime_upgrade"),
+                    ("alpha", "on_runtime_upgrade"),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,361 @@
+mod chain_spec;
+pub mod cli;
+mod json;
+
+use crate::support::keyring::Keyring;
+use crate::support::{
+    Decode, DispatchError, Encode, FileBackend, RecordLog, Storage, codec, with_transaction,
+};
+use crate::{Runtime, types};
+use std::path::Path;
+
+pub use chain_spec::ChainSpec;
+
+/// The reasons why a command of the node can fail.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// A JSON file is malformed.
+    Json(serde_json::Error),
+    /// Some binary data could not be decoded.
+    Codec(codec::Error),
+    /// A block could not be built or imported.
+    Dispatch(DispatchError),
+    /// The genesis state of the chain spec could not be built.
+    Genesis(DispatchError),
+    /// Some input does not have the expected format.
+    Invalid(String),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{error}"),
+            Error::Json(error) => write!(f, "Invalid JSON: {error}"),
+            Error::Codec(error) => write!(f, "{error}"),
+            Error::Dispatch(error) => write!(f, "Invalid block: {error}"),
+            Error::Genesis(error) => write!(f, "Invalid genesis: {error}"),
+            Error::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json(error)
+    }
+}
+
+impl From<codec::Error> for Error {
+    fn from(error: codec::Error) -> Self {
+        Error::Codec(error)
+    }
+}
+
+impl From<DispatchError> for Error {
+    fn from(error: DispatchError) -> Self {
+        Error::Dispatch(error)
+    }
+}
+
+// The file in the base path where the imported blocks are stored, as a log of one record per
+// block.
+const BLOCKS_FILE: &str = "blocks.log";
+
+// The file in the base path where the state of the runtime is stored, as a log of the changes of
+// each block.
+const STATE_FILE: &str = "state.log";
+
+// The file in the base path where the hash of the genesis block of the stored state is kept, as a
+// log of a single record. It is written before the genesis state.
+const GENESIS_FILE: &str = "genesis.log";
+
+/// A node: the runtime, and the chain of blocks imported on top of the genesis state.
+///
+/// The blocks and the state of the runtime are stored in the base path of the node. When the node
+/// is opened again, it resumes from the state after the last imported block.
+pub struct Node {
+    pub runtime: Runtime,
+    // The storage of the runtime, which the node writes to disk after each block.
+    storage: Storage,
+    blocks: Vec<types::Block>,
+    // Where the node appends each block it imports.
+    blocks_log: RecordLog,
+}
+
+impl Node {
+    /// Open the node stored in `base_path`, for the chain described by `spec`.
+    pub fn open(base_path: &Path, spec: &ChainSpec) -> Result<Self, Error> {
+        std::fs::create_dir_all(base_path)?;
+        let mut storage = Storage::with_backend(FileBackend::open(&base_path.join(STATE_FILE))?);
+        let genesis = spec.build_genesis()?;
+        let genesis_hash = genesis.system.genesis_hash();
+
+        // The state can only be resumed for the chain it was created for. A state without a
+        // genesis hash was not created by this node, so it is not resumed either.
+        let (mut genesis_log, records) = RecordLog::open(&base_path.join(GENESIS_FILE))?;
+        let stored_genesis_hash =
+            records.first().map(|record| types::Hash::decode_all(record)).transpose()?;
+        let is_new = storage.iter_prefix(&[]).is_empty();
+        if stored_genesis_hash.is_none() && is_new {
+            genesis_log.append(&genesis_hash.encode())?;
+        } else if stored_genesis_hash != Some(genesis_hash) {
+            return Err(Error::Invalid(format!(
+                "{} holds the state of another chain",
+                base_path.display()
+            )))
+        }
+        if is_new {
+            for (key, value) in genesis.system.storage().iter_prefix(&[]) {
+                storage.insert(key, value);
+            }
+            storage.flush()?;
+        }
+        let (blocks_log, records) = RecordLog::open(&base_path.join(BLOCKS_FILE))?;
+        let blocks = records
+            .iter()
+            .map(|record| types::Block::decode_all(record))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The hashes of the genesis block and of the last block are not part of the state, so we
+        // take them from the chain spec and from the stored blocks.
+        if blocks.first().is_some_and(|block| block.header.parent_hash != genesis_hash) {
+            return Err(Error::Invalid(format!(
+                "{} holds the blocks of another chain",
+                base_path.display()
+            )))
+        }
+        let mut runtime = Runtime::with_storage(&storage);
+        runtime.system.set_genesis_hash(genesis_hash);
+        let mut node = Node { runtime, storage, blocks, blocks_log };
+
+        // The blocks are stored before the state, so if the node was stopped in between, the
+        // state misses the last blocks, which we execute again.
+        let first_block_number = genesis.system.block_number();
+        let executed = node.runtime.system.block_number().saturating_sub(first_block_number);
+        let Some(missing) = node.blocks.get(executed as usize..) else {
+            return Err(Error::Invalid(format!(
+                "The state in {} is ahead of the stored blocks",
+                base_path.display()
+            )))
+        };
+        if let Some(last) = executed.checked_sub(1).map(|index| &node.blocks[index as usize]) {
+            let hash = last.header.hash::<types::Hashing>();
+            node.runtime.system.set_parent_hash(hash);
+        }
+        for block in missing.iter().cloned() {
+            node.runtime.execute_block(block)?;
+        }
+        node.storage.flush()?;
+        Ok(node)
+    }
+
+    /// The blocks imported so far, in order.
+    pub fn blocks(&self) -> &[types::Block] {
+        &self.blocks
+    }
+
+    /// Execute `block` on top of the chain, and store it along with the new state.
+    pub fn import_block(&mut self, block: types::Block) -> Result<(), Error> {
+        // The block is executed in a transaction, which is only committed once the block is
+        // stored. If it can't be stored, the runtime is left as it was, rather than ahead of the
+        // stored blocks.
+        let blocks_log = &mut self.blocks_log;
+        with_transaction(&mut self.runtime, |runtime| {
+            runtime.execute_block(block.clone())?;
+            Ok::<_, Error>(blocks_log.append(&block.encode())?)
+        })?;
+        self.blocks.push(block);
+        Ok(self.storage.flush()?)
+    }
+}
+
+/// Read blocks from a file: a JSON file if its extension is `.json`, and their binary encoding
+/// otherwise.
+pub fn read_blocks(path: &Path) -> Result<Vec<types::Block>, Error> {
+    let bytes = std::fs::read(path)?;
+    if is_json(path) {
+        return json::blocks_from_json(&serde_json::from_slice(&bytes)?)
+    }
+    Ok(Vec::<types::Block>::decode_all(&bytes)?)
+}
+
+/// Write blocks to a file, in the same format `read_blocks` expects.
+pub fn write_blocks(path: &Path, blocks: &[types::Block]) -> Result<(), Error> {
+    if is_json(path) {
+        let json = serde_json::to_string_pretty(&json::blocks_to_json(blocks))?;
+        return Ok(std::fs::write(path, json)?)
+    }
+    Ok(std::fs::write(path, blocks.encode())?)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "json")
+}
+
+/// Parse an account: either the name of a test account like `alice`, or a hex public key.
+pub fn parse_account(account: &str) -> Result<types::AccountId, Error> {
+    if let Some(keyring) = Keyring::from_name(account) {
+        return Ok(keyring.public())
+    }
+    json::parse_hex(account)
+        .map(crate::support::crypto::Public)
+        .map_err(|_| Error::Invalid(format!("Invalid account: {account}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RuntimeCall;
+    use crate::balances;
+    use std::path::PathBuf;
+
+    // A fresh directory for each test, so tests can run in parallel.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("node-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn transfer_block(node: &mut Node, amount: types::Balance) -> types::Block {
+        let alice = Keyring::Alice.public();
+        let call =
+            RuntimeCall::balances(balances::Call::transfer { to: Keyring::Bob.public(), amount });
+        let extrinsic = types::Extrinsic::new_signed_by(
+            &Keyring::Alice.pair(),
+            call,
+            node.runtime.system.account_nonce(&alice),
+            &node.runtime.system.genesis_hash(),
+        );
+        node.runtime.build_block(vec![extrinsic]).unwrap()
+    }
+
+    #[test]
+    fn node_resumes_from_stored_blocks() {
+        let dir = test_dir("resume");
+        let spec = ChainSpec::development();
+        let bob = Keyring::Bob.public();
+
+        let mut node = Node::open(&dir, &spec).unwrap();
+        for amount in [10, 20] {
+            let block = transfer_block(&mut node, amount);
+            node.import_block(block).unwrap();
+        }
+        assert_eq!(node.runtime.balances.balance(&bob), 1_030);
+
+        // An invalid block is not stored.
+        let mut block = transfer_block(&mut node, 5);
+        block.header.block_number = 5;
+        assert!(matches!(node.import_block(block), Err(Error::Dispatch(_))));
+        assert_eq!(node.blocks().len(), 2);
+
+        let node = Node::open(&dir, &spec).unwrap();
+        assert_eq!(node.blocks().len(), 2);
+        assert_eq!(node.runtime.system.block_number(), 2);
+        assert_eq!(node.runtime.balances.balance(&bob), 1_030);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn node_recovers_when_stopped_mid_block() {
+        let dir = test_dir("crash");
+        let spec = ChainSpec::development();
+        let bob = Keyring::Bob.public();
+        let state_path = dir.join(STATE_FILE);
+
+        let mut node = Node::open(&dir, &spec).unwrap();
+        let block = transfer_block(&mut node, 10);
+        node.import_block(block).unwrap();
+        let blocks_len = std::fs::metadata(dir.join(BLOCKS_FILE)).unwrap().len();
+        let state_len = std::fs::metadata(&state_path).unwrap().len();
+        let block = transfer_block(&mut node, 20);
+        node.import_block(block).unwrap();
+        let state_root = node.runtime.state_root();
+        drop(node);
+
+        // The node is killed while it writes the state of the second block, after the block
+        // itself is stored. The block is executed again.
+        let state = std::fs::OpenOptions::new().write(true).open(&state_path).unwrap();
+        state.set_len(state_len + 10).unwrap();
+        let mut node = Node::open(&dir, &spec).unwrap();
+        assert_eq!(node.runtime.system.block_number(), 2);
+        assert_eq!(node.runtime.state_root(), state_root);
+        assert_eq!(node.runtime.balances.balance(&bob), 1_030);
+
+        // The node carries on from there.
+        let block = transfer_block(&mut node, 30);
+        node.import_block(block).unwrap();
+        assert_eq!(node.runtime.balances.balance(&bob), 1_060);
+        drop(node);
+
+        // The node is killed while it stores the second block, before anything of its state is
+        // stored. It resumes from the first block.
+        let blocks = std::fs::OpenOptions::new().write(true).open(dir.join(BLOCKS_FILE)).unwrap();
+        blocks.set_len(blocks_len + 10).unwrap();
+        state.set_len(state_len + 10).unwrap();
+        let mut node = Node::open(&dir, &spec).unwrap();
+        assert_eq!(node.blocks().len(), 1);
+        assert_eq!(node.runtime.system.block_number(), 1);
+        assert_eq!(node.runtime.balances.balance(&bob), 1_010);
+        let block = transfer_block(&mut node, 20);
+        node.import_block(block).unwrap();
+        assert_eq!(node.runtime.state_root(), state_root);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn node_refuses_the_state_of_another_chain() {
+        let dir = test_dir("other-chain");
+        let spec = ChainSpec::development();
+        let mut other_spec = ChainSpec::development();
+        other_spec.genesis.balances.balances.push((Keyring::Charlie.public(), 1_000));
+        let is_refused = |res: Result<Node, Error>| {
+            matches!(res, Err(Error::Invalid(message)) if message.ends_with("of another chain"))
+        };
+
+        // There are no blocks to tell the chains apart, only the stored genesis hash.
+        drop(Node::open(&dir, &spec).unwrap());
+        assert!(is_refused(Node::open(&dir, &other_spec)));
+        let mut node = Node::open(&dir, &spec).unwrap();
+        let block = transfer_block(&mut node, 10);
+        node.import_block(block).unwrap();
+        drop(node);
+        assert!(is_refused(Node::open(&dir, &other_spec)));
+
+        // A state without a genesis hash is not resumed.
+        std::fs::remove_file(dir.join(GENESIS_FILE)).unwrap();
+        assert!(is_refused(Node::open(&dir, &spec)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn blocks_files_roundtrip() {
+        let dir = test_dir("files");
+        let mut node = Node::open(&dir, &ChainSpec::development()).unwrap();
+        let block = transfer_block(&mut node, 10);
+        node.import_block(block).unwrap();
+
+        for file in ["export.json", "export.bin"] {
+            let path = dir.join(file);
+            write_blocks(&path, node.blocks()).unwrap();
+            assert_eq!(read_blocks(&path).unwrap(), node.blocks());
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn accounts_are_parsed() {
+        let alice = Keyring::Alice.public();
+        assert_eq!(parse_account("alice").unwrap(), alice);
+        assert_eq!(parse_account(&alice.to_string()).unwrap(), alice);
+        assert!(parse_account("dave").is_err());
+        assert!(parse_account("0x1234").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use super::json::{field, invalid, parse_hex, str_field};
+use super::{Error, parse_account};
+use crate::support::H256;
+use crate::support::keyring::Keyring;
+use crate::{Runtime, RuntimeGenesisConfig, types};
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+
+/// A chain specification: the name of the chain, and its genesis state.
+///
+/// Nodes started from the same chain spec start from the same genesis block, so they can import
+/// the same blocks.
+pub struct ChainSpec {
+    /// A human readable name for the chain.
+    pub name: String,
+    /// A short identifier for the chain.
+    pub id: String,
+    /// The genesis configuration of every pallet.
+    pub genesis: RuntimeGenesisConfig,
+}
+
+impl ChainSpec {
+    /// The chain spec used for development, where Alice and Bob start with enough funds to pay
+    /// the fees of a few extrinsics, and Alice is the sudo key.
+    pub fn development() -> Self {
+        let mut genesis = RuntimeGenesisConfig::default();
+        genesis.balances.balances =
+            vec![(Keyring::Alice.public(), 1_000), (Keyring::Bob.public(), 1_000)];
+        genesis.sudo.key = Some(Keyring::Alice.public());
+        Self { name: "Development".to_string(), id: "dev".to_string(), genesis }
+    }
+
+    /// Build a runtime with the genesis state of this chain.
+    pub fn build_genesis(&self) -> Result<Runtime, Error> {
+        Runtime::from_genesis(&self.genesis).map_err(Error::Genesis)
+    }
+
+    /// The JSON representation of this chain spec, as written by `build-spec`.
+    pub fn to_json(&self) -> Value {
+        let genesis = &self.genesis;
+        let balances = genesis
+            .balances
+            .balances
+            .iter()
+            .map(|(who, amount)| json!([who.to_string(), balance_to_json(*amount)]))
+            .collect::<Vec<_>>();
+        let claims = genesis
+            .proof_of_existence
+            .claims
+            .iter()
+            .map(|(claim, owner)| json!([claim.to_string(), owner.to_string()]))
+            .collect::<Vec<_>>();
+        let sudo_key = genesis.sudo.key.as_ref().map(ToString::to_string);
+        json!({
+            "name": self.name,
+            "id": self.id,
+            "genesis": {
+                "system": { "block_number": genesis.system.block_number },
+                "balances": { "balances": balances },
+                "proof_of_existence": { "claims": claims },
+                "sudo": { "key": sudo_key },
+            },
+        })
+    }
+
+    /// Read a chain spec from its JSON representation.
+    ///
+    /// Accounts can be written as hex public keys, or as the names of the test accounts. Pallets
+    /// missing from the genesis keep their default genesis configuration. The genesis must be
+    /// valid: each account and each claim can only appear once, and the total issuance must fit
+    /// in a balance.
+    pub fn from_json(value: &Value) -> Result<Self, Error> {
+        let json_genesis = field(value, "genesis")?;
+        let mut genesis = RuntimeGenesisConfig::default();
+        if let Some(system) = json_genesis.get("system") {
+            genesis.system.block_number = field(system, "block_number")?
+                .as_u64()
+                .and_then(|number| types::BlockNumber::try_from(number).ok())
+                .ok_or_else(|| invalid("Invalid block number"))?;
+        }
+        if let Some(balances) = json_genesis.get("balances") {
+            genesis.balances.balances = pairs(field(balances, "balances")?, |who, amount| {
+                let who = who.as_str().ok_or_else(|| invalid("Expected an account"))?;
+                Ok((parse_account(who)?, balance_from_json(amount)?))
+            })?;
+        }
+        if let Some(proof_of_existence) = json_genesis.get("proof_of_existence") {
+            genesis.proof_of_existence.claims =
+                pairs(field(proof_of_existence, "claims")?, |claim, owner| {
+                    let claim = claim.as_str().ok_or_else(|| invalid("Expected a claim hash"))?;
+                    let owner = owner.as_str().ok_or_else(|| invalid("Expected an account"))?;
+                    Ok((H256(parse_hex(claim)?), parse_account(owner)?))
+                })?;
+        }
+        if let Some(sudo) = json_genesis.get("sudo") {
+            genesis.sudo.key = match field(sudo, "key")? {
+                Value::Null => None,
+                key => {
+                    let key = key.as_str().ok_or_else(|| invalid("Expected an account"))?;
+                    Some(parse_account(key)?)
+                },
+            };
+        }
+        check_genesis(&genesis)?;
+        Ok(Self {
+            name: str_field(value, "name")?.to_string(),
+            id: str_field(value, "id")?.to_string(),
+            genesis,
+        })
+    }
+}
+
+// Check what building the genesis would reject, so an invalid chain spec is reported when it is
+// read.
+fn check_genesis(genesis: &RuntimeGenesisConfig) -> Result<(), Error> {
+    let mut accounts = BTreeSet::new();
+    let mut total_issuance: types::Balance = 0;
+    for (who, amount) in &genesis.balances.balances {
+        if !accounts.insert(who) {
+            return Err(invalid(format!("Duplicate account in the genesis balances: {who}")))
+        }
+        total_issuance = total_issuance
+            .checked_add(*amount)
+            .ok_or_else(|| invalid("The total issuance of the genesis balances overflows"))?;
+    }
+    let mut claims = BTreeSet::new();
+    for (claim, _) in &genesis.proof_of_existence.claims {
+        if !claims.insert(claim) {
+            return Err(invalid(format!("Duplicate claim in the genesis: {claim}")))
+        }
+    }
+    Ok(())
+}
+
+// Read an array of `[a, b]` pairs.
+fn pairs<T>(
+    value: &Value,
+    parse: impl Fn(&Value, &Value) -> Result<T, Error>,
+) -> Result<Vec<T>, Error> {
+    let entries = value.as_array().ok_or_else(|| invalid("Expected an array of pairs"))?;
+    entries
+        .iter()
+        .map(|entry| match entry.as_array().map(Vec::as_slice) {
+            Some([a, b]) => parse(a, b),
+            _ => Err(invalid(format!("Expected a pair: {entry}"))),
+        })
+        .collect()
+}
+
+// JSON numbers can't hold every `u128`, so large balances are written as strings.
+fn balance_to_json(amount: types::Balance) -> Value {
+    match u64::try_from(amount) {
+        Ok(amount) => json!(amount),
+        Err(_) => json!(amount.to_string()),
+    }
+}
+
+fn balance_from_json(value: &Value) -> Result<types::Balance, Error> {
+    let amount = match value {
+        Value::Number(number) => number.as_u64().map(types::Balance::from),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    };
+    amount.ok_or_else(|| invalid(format!("Invalid balance: {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::support::Hasher;
+
+    #[test]
+    fn chain_spec_json_roundtrip() {
+        let mut spec = ChainSpec::development();
+        spec.genesis.system.block_number = 7;
+        spec.genesis.balances.balances.push((Keyring::Charlie.public(), u128::MAX - 2_000));
+        let doc = types::ContentHasher::hash(b"doc");
+        spec.genesis.proof_of_existence.claims.push((doc, Keyring::Bob.public()));
+        let json = spec.to_json();
+        assert_eq!(ChainSpec::from_json(&json).unwrap().to_json(), json);
+
+        // Test accounts can be written by name, and missing pallets use their default genesis.
+        let value = json!({
+            "name": "Local",
+            "id": "local",
+            "genesis": {
+                "balances": { "balances": [["bob", 50], ["charlie", "70"]] },
+                "proof_of_existence": { "claims": [[doc.to_string(), "charlie"]] },
+                "sudo": { "key": "bob" },
+            },
+        });
+        let runtime = ChainSpec::from_json(&value).unwrap().build_genesis().unwrap();
+        assert_eq!(runtime.system.block_number(), 0);
+        assert_eq!(runtime.balances.balance(&Keyring::Bob.public()), 50);
+        assert_eq!(runtime.balances.balance(&Keyring::Charlie.public()), 70);
+        assert_eq!(runtime.balances.balance(&Keyring::Alice.public()), 0);
+        assert_eq!(runtime.proof_of_existence.get_claim(&doc), Some(Keyring::Charlie.public()));
+        assert_eq!(runtime.sudo.key(), Some(Keyring::Bob.public()));
+
+        // Without a sudo key, it is written as null.
+        spec.genesis.sudo.key = None;
+        let json = spec.to_json();
+        assert_eq!(json["genesis"]["sudo"]["key"], Value::Null);
+        assert_eq!(ChainSpec::from_json(&json).unwrap().to_json(), json);
+
+        assert!(ChainSpec::from_json(&json!({ "name": "Local", "id": "local" })).is_err());
+        let bad_pair = json!({
+            "name": "Local",
+            "id": "local",
+            "genesis": { "balances": { "balances": [["bob"]] } },
+        });
+        assert!(ChainSpec::from_json(&bad_pair).is_err());
+    }
+
+    #[test]
+    fn invalid_genesis_is_rejected() {
+        let spec = |genesis| json!({ "name": "Local", "id": "local", "genesis": genesis });
+        let error = |genesis| ChainSpec::from_json(&spec(genesis)).err().map(|e| e.to_string());
+
+        let max = u128::MAX.to_string();
+        let overflow = json!({ "balances": { "balances": [["alice", max], ["bob", 5]] } });
+        assert_eq!(
+            error(overflow),
+            Some("The total issuance of the genesis balances overflows".to_string())
+        );
+        let duplicate_account = json!({ "balances": { "balances": [["bob", 5], ["bob", 7]] } });
+        assert_eq!(
+            error(duplicate_account),
+            Some(format!("Duplicate account in the genesis balances: {}", Keyring::Bob.public()))
+        );
+        let doc = types::ContentHasher::hash(b"doc");
+        let claims = [[doc.to_string(), "bob".to_string()], [doc.to_string(), "alice".to_string()]];
+        let duplicate_claim = json!({ "proof_of_existence": { "claims": claims } });
+        assert_eq!(error(duplicate_claim), Some(format!("Duplicate claim in the genesis: {doc}")));
+
+        // Building the genesis fails too, rather than panicking, for chain specs which are not
+        // read from JSON.
+        let mut spec = ChainSpec::development();
+        spec.genesis.balances.balances.push((Keyring::Charlie.public(), u128::MAX));
+        assert_eq!(
+            spec.build_genesis().err().map(|e| e.to_string()),
+            Some("Invalid genesis: Module error 1 in pallet 1: Overflow".to_string())
+        );
+    }
+
+    #[test]
+    fn genesis_hash_depends_on_the_genesis_state() {
+        let spec = ChainSpec::development();
+        let genesis_hash = spec.build_genesis().unwrap().system.genesis_hash();
+        assert_eq!(spec.build_genesis().unwrap().system.genesis_hash(), genesis_hash);
+        assert_ne!(genesis_hash, Default::default());
+
+        let mut other = ChainSpec::development();
+        other.genesis.balances.balances[0].1 = 101;
+        assert_ne!(other.build_genesis().unwrap().system.genesis_hash(), genesis_hash);
+    }
+}
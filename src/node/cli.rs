@@ -0,0 +1,341 @@
+use super::json::parse_hex;
+use super::{ChainSpec, Error, Node, parse_account, read_blocks, write_blocks};
+use crate::support::H256;
+use crate::support::keyring::Keyring;
+use crate::{Runtime, RuntimeCall, balances, proof_of_existence, sudo, types};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// How to use the node from the command line.
+pub const USAGE: &str = "\
+Usage: rust-class-state-machine [OPTIONS] <COMMAND>
+
+Commands:
+  demo                     Build, sign and import a few demo blocks
+  import <FILE>            Import blocks from a `.json` or a binary file
+  export-blocks <FILE>     Export the imported blocks to a `.json` or a binary file
+  build-spec               Print the chain spec as JSON
+  query balance <ACCOUNT>  Print the balance of an account
+  query claim <HASH>       Print the owner of the claim on a content hash
+  hash-file <FILE>         Print the hash a file is claimed with
+  verify-file <FILE>       Print whether a file is claimed, and by whom
+  export-state             Print the state as JSON
+  help                     Print this message
+
+Options:
+  --base-path <DIR>        Where the node stores its blocks and state [default: chain-data]
+  --chain <FILE>           The JSON chain spec to use [default: the development chain spec]
+
+Accounts are hex public keys, or the names of the test accounts: alice, bob and charlie.";
+
+/// The options and the command given on the command line.
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub base_path: PathBuf,
+    pub chain: Option<PathBuf>,
+    pub command: Command,
+}
+
+/// The commands of the node. See `USAGE`.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Demo,
+    Import(PathBuf),
+    ExportBlocks(PathBuf),
+    BuildSpec,
+    QueryBalance(String),
+    QueryClaim(String),
+    HashFile(PathBuf),
+    VerifyFile(PathBuf),
+    ExportState,
+    Help,
+}
+
+impl Cli {
+    /// Parse the command line arguments, without the name of the binary.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Error> {
+        let mut base_path = PathBuf::from("chain-data");
+        let mut chain = None;
+        let mut words = vec![];
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| usage(format!("Missing value for {arg}")));
+            match arg.as_str() {
+                "--base-path" => base_path = value()?.into(),
+                "--chain" => chain = Some(value()?.into()),
+                "-h" | "--help" => words = vec!["help".to_string()],
+                _ if arg.starts_with("--") => return Err(usage(format!("Unknown option {arg}"))),
+                _ => words.push(arg),
+            }
+        }
+
+        let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+        let command = match words.as_slice() {
+            ["demo"] => Command::Demo,
+            ["import", file] => Command::Import(file.into()),
+            ["export-blocks", file] => Command::ExportBlocks(file.into()),
+            ["build-spec"] => Command::BuildSpec,
+            ["query", "balance", account] => Command::QueryBalance(account.to_string()),
+            ["query", "claim", hash] => Command::QueryClaim(hash.to_string()),
+            ["hash-file", file] => Command::HashFile(file.into()),
+            ["verify-file", file] => Command::VerifyFile(file.into()),
+            ["export-state"] => Command::ExportState,
+            ["help"] => Command::Help,
+            [] => return Err(usage("Missing command")),
+            _ => return Err(usage(format!("Invalid command: {}", words.join(" ")))),
+        };
+        Ok(Self { base_path, chain, command })
+    }
+}
+
+fn usage(message: impl Into<String>) -> Error {
+    Error::Invalid(format!("{}\n\n{USAGE}", message.into()))
+}
+
+/// Run a command of the node.
+pub fn run(cli: Cli) -> Result<(), Error> {
+    let spec = match &cli.chain {
+        Some(path) => ChainSpec::from_json(&serde_json::from_slice(&std::fs::read(path)?)?)?,
+        None => ChainSpec::development(),
+    };
+
+    match cli.command {
+        Command::Help => println!("{USAGE}"),
+        Command::BuildSpec => println!("{}", serde_json::to_string_pretty(&spec.to_json())?),
+        Command::Demo => {
+            let mut node = Node::open(&cli.base_path, &spec)?;
+            for extrinsics in demo_extrinsics(&node) {
+                let block = node.runtime.build_block(extrinsics)?;
+                import(&mut node, block)?;
+            }
+        },
+        Command::Import(path) => {
+            let mut node = Node::open(&cli.base_path, &spec)?;
+            for block in read_blocks(&path)? {
+                import(&mut node, block)?;
+            }
+        },
+        Command::ExportBlocks(path) => {
+            let node = Node::open(&cli.base_path, &spec)?;
+            write_blocks(&path, node.blocks())?;
+            println!("Exported {} blocks to {}", node.blocks().len(), path.display());
+        },
+        Command::QueryBalance(account) => {
+            let node = Node::open(&cli.base_path, &spec)?;
+            println!("{}", node.runtime.balances.balance(&parse_account(&account)?));
+        },
+        Command::QueryClaim(hash) => {
+            let hash = H256(parse_hex(&hash)?);
+            let node = Node::open(&cli.base_path, &spec)?;
+            println!("{}", describe_claim(&node, &hash));
+        },
+        Command::HashFile(path) => println!("{}", hash_file(&path)?),
+        Command::VerifyFile(path) => {
+            let hash = hash_file(&path)?;
+            let node = Node::open(&cli.base_path, &spec)?;
+            println!("{}: {}", path.display(), describe_claim(&node, &hash));
+        },
+        Command::ExportState => {
+            let node = Node::open(&cli.base_path, &spec)?;
+            println!("{}", serde_json::to_string_pretty(&export_state(&node))?);
+        },
+    }
+    Ok(())
+}
+
+// Import a block, and print the events it deposited.
+fn import(node: &mut Node, block: types::Block) -> Result<(), Error> {
+    let block_number = block.header.block_number;
+    let block_hash = block.header.hash::<types::Hashing>();
+    node.import_block(block)?;
+    let weight = node.runtime.system.block_weight();
+    println!("Imported block {block_number} with hash {block_hash} and weight {weight}");
+    for record in node.runtime.system.events() {
+        println!("Block {block_number} {:?}: {:?}", record.phase, record.event);
+    }
+    Ok(())
+}
+
+// Hash a file the way claims are made, off chain. Only the hash of a document is ever sent to
+// the chain.
+fn hash_file(path: &Path) -> Result<types::ContentHash, Error> {
+    Ok(proof_of_existence::Pallet::<Runtime>::hash_content(&std::fs::read(path)?))
+}
+
+// Who owns the claim on `hash`, and whether it is still active after the last imported block.
+fn describe_claim(node: &Node, hash: &types::ContentHash) -> String {
+    let now = node.runtime.system.block_number();
+    match node.runtime.proof_of_existence.claim_info(hash) {
+        None => format!("No claim for {hash}"),
+        Some(info) => match info.expires {
+            Some(expires) if !info.is_active(&now) => {
+                format!("Claimed by {}, expired after block {expires}", info.owner)
+            },
+            Some(expires) => format!("Claimed by {} until block {expires}", info.owner),
+            None => format!("Claimed by {}", info.owner),
+        },
+    }
+}
+
+// The state of all the pallets, in a readable form.
+fn export_state(node: &Node) -> serde_json::Value {
+    let runtime = &node.runtime;
+    let nonces = runtime.system.nonce.iter().map(|(who, nonce)| (who.to_string(), json!(nonce)));
+    let balances = runtime
+        .balances
+        .balances()
+        .map(|(who, amount)| (who.to_string(), json!(amount.to_string())));
+    let claims = runtime
+        .proof_of_existence
+        .claims()
+        .map(|(claim, owner)| (claim.to_string(), json!(owner.to_string())));
+    json!({
+        "genesis_hash": runtime.system.genesis_hash().to_string(),
+        "block_number": runtime.system.block_number(),
+        "best_hash": runtime.system.parent_hash().to_string(),
+        "state_root": runtime.state_root().to_string(),
+        "system": { "nonces": serde_json::Map::from_iter(nonces) },
+        "balances": {
+            "total_issuance": runtime.balances.total_issuance().to_string(),
+            "balances": serde_json::Map::from_iter(balances),
+        },
+        "proof_of_existence": { "claims": serde_json::Map::from_iter(claims) },
+        "sudo": { "key": runtime.sudo.key().as_ref().map(ToString::to_string) },
+    })
+}
+
+// A few blocks of extrinsics signed by our test accounts, using their current nonces.
+fn demo_extrinsics(node: &Node) -> Vec<Vec<types::Extrinsic>> {
+    let genesis_hash = node.runtime.system.genesis_hash();
+    let mut nonces = [Keyring::Alice, Keyring::Bob]
+        .map(|who| (who, node.runtime.system.account_nonce(&who.public())));
+    let mut sign = |who: Keyring, call: RuntimeCall| {
+        let (_, nonce) = nonces.iter_mut().find(|(account, _)| *account == who).unwrap();
+        *nonce += 1;
+        types::Extrinsic::new_signed_by(&who.pair(), call, *nonce - 1, &genesis_hash)
+    };
+    let transfer = |to: Keyring, amount| {
+        RuntimeCall::balances(balances::Call::transfer { to: to.public(), amount })
+    };
+    let hash =
+        |content: &str| proof_of_existence::Pallet::<Runtime>::hash_content(content.as_bytes());
+    let create_claim = |content: &str| {
+        RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+            claim: hash(content),
+            memo: None,
+            expires: None,
+        })
+    };
+    let revoke_claim = |content: &str| {
+        RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
+            claim: hash(content),
+        })
+    };
+
+    // Alice is the sudo key of the development chain, and can set the balance of Charlie.
+    let set_balance = RuntimeCall::balances(balances::Call::force_set_balance {
+        who: Keyring::Charlie.public(),
+        amount: 500,
+    });
+    let sudo = RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(set_balance) });
+
+    vec![
+        vec![
+            sign(Keyring::Alice, transfer(Keyring::Bob, 30)),
+            sign(Keyring::Alice, transfer(Keyring::Charlie, 20)),
+            sign(Keyring::Alice, sudo),
+        ],
+        vec![
+            sign(Keyring::Alice, create_claim("Hello, world!")),
+            sign(Keyring::Bob, create_claim("Hello, world!")),
+        ],
+        vec![
+            sign(Keyring::Alice, revoke_claim("Hello, world!")),
+            sign(Keyring::Bob, create_claim("Hello, world!")),
+        ],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::support::Hasher;
+
+    fn parse(args: &str) -> Result<Cli, Error> {
+        Cli::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        let cli = parse("query balance alice").unwrap();
+        assert_eq!(cli.command, Command::QueryBalance("alice".to_string()));
+        assert_eq!(cli.base_path, PathBuf::from("chain-data"));
+        assert_eq!(cli.chain, None);
+
+        let cli = parse("--base-path /tmp/node import blocks.json --chain spec.json").unwrap();
+        assert_eq!(cli.command, Command::Import("blocks.json".into()));
+        assert_eq!(cli.base_path, PathBuf::from("/tmp/node"));
+        assert_eq!(cli.chain, Some("spec.json".into()));
+
+        assert_eq!(parse("--help").unwrap().command, Command::Help);
+        assert!(parse("").is_err());
+        assert!(parse("query").is_err());
+        assert!(parse("import").is_err());
+        assert!(parse("demo --base-path").is_err());
+        assert!(parse("demo --verbose").is_err());
+        assert_eq!(
+            parse("verify-file doc.pdf").unwrap().command,
+            Command::VerifyFile("doc.pdf".into())
+        );
+    }
+
+    #[test]
+    fn demo_blocks_can_be_exported_and_imported() {
+        let dir = std::env::temp_dir().join(format!("cli-demo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cli = |args: &str| {
+            let mut cli = parse(args).unwrap();
+            cli.base_path = dir.join(&cli.base_path);
+            if let Command::ExportBlocks(path) | Command::Import(path) = &mut cli.command {
+                *path = dir.join(&path);
+            }
+            run(cli)
+        };
+
+        // Running the demo twice works, since the nonces are read from the state.
+        cli("--base-path a demo").unwrap();
+        cli("--base-path a demo").unwrap();
+        cli("--base-path a export-blocks blocks.json").unwrap();
+        cli("--base-path b import blocks.json").unwrap();
+
+        let a = Node::open(&dir.join("a"), &ChainSpec::development()).unwrap();
+        let b = Node::open(&dir.join("b"), &ChainSpec::development()).unwrap();
+        assert_eq!(a.blocks().len(), 6);
+        assert_eq!(a.blocks(), b.blocks());
+        assert_eq!(export_state(&a), export_state(&b));
+        // Bob received two transfers of 30, paid for creating a claim four times, and reserved
+        // the deposit of the claim Bob holds.
+        let bob = Keyring::Bob.public();
+        assert_eq!(b.runtime.balances.balance(&bob), 1_000 + 60 - 4 * 20 - 10);
+        assert_eq!(b.runtime.balances.reserved_balance(&bob), 10);
+        assert_eq!(
+            b.runtime.proof_of_existence.verify(b"Hello, world!", b.runtime.system.block_number()),
+            Some(Keyring::Bob.public())
+        );
+        // Files are claimed by their hash, which is checked against the state.
+        let file = dir.join("hello.txt");
+        std::fs::write(&file, "Hello, world!").unwrap();
+        let hash = hash_file(&file).unwrap();
+        assert_eq!(hash, types::ContentHasher::hash(b"Hello, world!"));
+        assert_eq!(describe_claim(&b, &hash), format!("Claimed by {}", Keyring::Bob.public()));
+        let other = types::ContentHasher::hash(b"Goodbye");
+        assert_eq!(describe_claim(&b, &other), format!("No claim for {other}"));
+        assert_eq!(b.runtime.balances.balance(&Keyring::Charlie.public()), 500);
+        assert!(b.runtime.balances.total_issuance_is_consistent());
+
+        // Blocks can't be imported twice.
+        assert!(matches!(cli("--base-path b import blocks.json"), Err(Error::Dispatch(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use super::Error;
+use crate::support::{Decode, Encode, H256};
+use crate::types;
+use serde_json::{Value, json};
+
+// Blocks are written in JSON with a readable header. The extrinsics are written as their hex
+// encoding, since they are signed and can't be edited by hand anyway:
+// `[{ "header": { "parent_hash": "0x..", "block_number": 1, .. }, "extrinsics": ["0x.."] }]`
+pub fn blocks_to_json(blocks: &[types::Block]) -> Value {
+    let blocks = blocks.iter().map(|block| {
+        let header = &block.header;
+        json!({
+            "header": {
+                "parent_hash": header.parent_hash.to_string(),
+                "block_number": header.block_number,
+                "state_root": header.state_root.to_string(),
+                "extrinsics_root": header.extrinsics_root.to_string(),
+            },
+            "extrinsics": block.extrinsics.iter().map(|e| to_hex(&e.encode())).collect::<Vec<_>>(),
+        })
+    });
+    Value::Array(blocks.collect())
+}
+
+pub fn blocks_from_json(value: &Value) -> Result<Vec<types::Block>, Error> {
+    let blocks = value.as_array().ok_or_else(|| invalid("Expected an array of blocks"))?;
+    blocks.iter().map(block_from_json).collect()
+}
+
+fn block_from_json(value: &Value) -> Result<types::Block, Error> {
+    let header = field(value, "header")?;
+    let block_number = field(header, "block_number")?
+        .as_u64()
+        .and_then(|number| types::BlockNumber::try_from(number).ok())
+        .ok_or_else(|| invalid("Invalid block number"))?;
+    let header = types::Header {
+        parent_hash: H256(parse_hex(str_field(header, "parent_hash")?)?),
+        block_number,
+        state_root: H256(parse_hex(str_field(header, "state_root")?)?),
+        extrinsics_root: H256(parse_hex(str_field(header, "extrinsics_root")?)?),
+    };
+    let extrinsics = field(value, "extrinsics")?
+        .as_array()
+        .ok_or_else(|| invalid("Expected an array of extrinsics"))?
+        .iter()
+        .map(|extrinsic| {
+            let hex = extrinsic.as_str().ok_or_else(|| invalid("Expected a hex extrinsic"))?;
+            Ok(types::Extrinsic::decode_all(&hex_to_bytes(hex)?)?)
+        })
+        .collect::<Result<_, Error>>()?;
+    Ok(types::Block { header, extrinsics })
+}
+
+/// Get the field `name` of a JSON object.
+pub fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, Error> {
+    value.get(name).ok_or_else(|| invalid(format!("Missing field `{name}`")))
+}
+
+/// Get the field `name` of a JSON object, which must be a string.
+pub fn str_field<'a>(value: &'a Value, name: &str) -> Result<&'a str, Error> {
+    field(value, name)?.as_str().ok_or_else(|| invalid(format!("Field `{name}` is not a string")))
+}
+
+/// Encode bytes in hex, with a `0x` prefix.
+pub fn to_hex(bytes: &[u8]) -> String {
+    let hex = bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+    format!("0x{hex}")
+}
+
+/// Decode hex into bytes. The `0x` prefix is optional.
+pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, Error> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(invalid(format!("Invalid hex: {hex}")))
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid(format!("Invalid hex: {hex}")))
+}
+
+/// Decode hex into a fixed number of bytes, like a hash or a public key.
+pub fn parse_hex<const N: usize>(hex: &str) -> Result<[u8; N], Error> {
+    hex_to_bytes(hex)?.try_into().map_err(|_| invalid(format!("Expected {N} bytes: {hex}")))
+}
+
+/// An error for some input which does not have the expected format.
+pub fn invalid(message: impl Into<String>) -> Error {
+    Error::Invalid(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_works() {
+        assert_eq!(to_hex(&[0, 1, 0xab]), "0x0001ab");
+        assert_eq!(hex_to_bytes("0x0001ab").unwrap(), vec![0, 1, 0xab]);
+        assert_eq!(hex_to_bytes("0001AB").unwrap(), vec![0, 1, 0xab]);
+        assert!(hex_to_bytes("0x123").is_err());
+        assert!(hex_to_bytes("0xzz").is_err());
+        assert_eq!(parse_hex::<2>("0x0102").unwrap(), [1, 2]);
+        assert!(parse_hex::<2>("0x01").is_err());
+    }
+}
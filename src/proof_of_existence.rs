@@ -1,74 +1,953 @@
-use std::collections::BTreeMap;
 use core::fmt::Debug;
-use crate::support::DispatchResult;
+use crate::system::ensure_signed;
+use crate::support::{
+    BalanceStatus, BuildGenesisConfig, Currency, Decode, DispatchResult, Encode,
+    ExistenceRequirement, H256, Hasher, Hooks, ProofError, ReservableCurrency, Storage,
+    StorageDoubleMap, StorageMap, StorageProof, StorageValue, Weight,
+};
+use num::traits::{CheckedAdd, CheckedSub, One, Zero};
 
-pub trait Config: crate::system::Config {
+/// The configuration trait for the Proof of Existence Module.
+///
+/// Claims reserve a deposit in the currency, and record the block they were created in, so the
+/// runtime must give access to the currency and to the system pallet as well as to this pallet.
+pub trait Config:
+    crate::system::Config
+    + AsRef<crate::system::Pallet<Self>>
+    + AsMut<Pallet<Self>>
+    + AsMut<Self::Currency>
+    + Sized
+{
+    /// The hash of the content of a claim. Only the hash is stored on chain, never the content.
+    type ContentHash: Debug + Ord + Clone + Encode + Decode;
 
-    type Content: Debug + Ord;
+    /// The hashing function turning some content into the `ContentHash` it is claimed with.
+    type ContentHasher: Hasher<Output = Self::ContentHash>;
 
+    /// The currency in which the fees and deposits of the claims are paid, usually the balances
+    /// pallet.
+    type Currency: ReservableCurrency<Self::AccountId>;
+
+    /// The deposit reserved from the balance of the owner of each claim, until it is revoked or
+    /// it expires.
+    const CLAIM_DEPOSIT: BalanceOf<Self>;
+
+    /// The fee burned from the balance of the owner of a claim when renewing it. It can be zero.
+    const RENEWAL_FEE: BalanceOf<Self>;
+
+    /// The maximum length of the memo attached to a claim, in bytes.
+    const MAX_MEMO_LENGTH: u32;
+
+    /// The maximum number of expired claims pruned at the end of each block, within the weight the
+    /// block has left. The others are pruned in the next blocks.
+    const MAX_EXPIRED_PER_BLOCK: u32;
 }
 
+/// The weight of reading the claims which expire in a block, to prune them.
+pub const READ_EXPIRIES_WEIGHT: Weight = 10;
+/// The weight of pruning an expired claim, and returning its deposit.
+pub const EXPIRE_CLAIM_WEIGHT: Weight = 50;
+
+/// The balance of the currency used for the deposits.
+pub type BalanceOf<T> =
+    <<T as Config>::Currency as Currency<<T as crate::system::Config>::AccountId>>::Balance;
+
+/// The record of a claim on some content.
+#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
+pub struct ClaimInfo<AccountId, Balance, BlockNumber> {
+    /// The account which owns the claim.
+    pub owner: AccountId,
+    /// The deposit reserved from the balance of the owner for the claim.
+    pub deposit: Balance,
+    /// The block in which the claim was created.
+    pub created: BlockNumber,
+    /// The last block in which the claim is active, if it expires.
+    pub expires: Option<BlockNumber>,
+    /// A note attached to the claim when it was created.
+    pub memo: Option<Vec<u8>>,
+}
+
+impl<AccountId, Balance, BlockNumber: Ord> ClaimInfo<AccountId, Balance, BlockNumber> {
+    /// Whether the claim is still active in the block `now`.
+    pub fn is_active(&self, now: &BlockNumber) -> bool {
+        self.expires.as_ref().is_none_or(|expires| now <= expires)
+    }
+}
+
+/// The `ClaimInfo` of a runtime.
+pub type ClaimInfoOf<T> = ClaimInfo<
+    <T as crate::system::Config>::AccountId,
+    BalanceOf<T>,
+    <T as crate::system::Config>::BlockNumber,
+>;
+
+/// The record of each claim.
+#[macros::storage]
+type Claims<T: Config> = StorageMap<_, T::ContentHash, ClaimInfoOf<T>>;
+
+/// The claims owned by each account, which is the reverse index of `Claims`.
+#[macros::storage]
+type OwnedClaims<T: Config> = StorageDoubleMap<_, T::AccountId, T::ContentHash, ()>;
+
+/// The claims expiring at the end of each block, which are pruned from then on.
+#[macros::storage]
+type Expiries<T: Config> = StorageDoubleMap<_, T::BlockNumber, T::ContentHash, ()>;
+
+/// The first block whose expired claims may not all be pruned yet. The blocks before it have no
+/// claims left in `Expiries`.
+#[macros::storage]
+type ExpiryCursor<T: Config> = StorageValue<_, T::BlockNumber>;
+
 #[derive(Debug)]
 pub struct Pallet<T: Config> {
-        claims: BTreeMap<T::Content, T::AccountId>,
+    claims: Claims<T>,
+    owned: OwnedClaims<T>,
+    expiries: Expiries<T>,
+    expiry_cursor: ExpiryCursor<T>,
+    // The events deposited by this pallet, which are moved to the system pallet by the runtime.
+    events: Vec<Event<T>>,
+}
+
+/// The initial state of the Proof of Existence Module.
+#[derive(Debug)]
+pub struct GenesisConfig<T: Config> {
+    /// The claims which exist at genesis, and their owners. No deposit is reserved for them, and
+    /// they never expire.
+    pub claims: Vec<(T::ContentHash, T::AccountId)>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+    fn default() -> Self {
+        Self { claims: Vec::new() }
+    }
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>, T::BlockNumber> for GenesisConfig<T> {
+    // Genesis claims are created in the genesis block `n`. Each content can only be claimed once,
+    // or the claims index of the first owner would be left behind.
+    fn build(&self, pallet: &mut Pallet<T>, n: T::BlockNumber) -> DispatchResult {
+        for (claim, owner) in &self.claims {
+            if pallet.claims.contains_key(claim) {
+                return Err(Error::AlreadyClaimed.into())
+            }
+            let info = ClaimInfo {
+                owner: owner.clone(),
+                deposit: BalanceOf::<T>::zero(),
+                created: n,
+                expires: None,
+                memo: None,
+            };
+            pallet.insert_claim(claim.clone(), info);
+        }
+        Ok(())
+    }
+}
+
+/// The events of the Proof of Existence Module.
+#[macros::event]
+#[allow(clippy::enum_variant_names)]
+pub enum Event<T: Config> {
+    /// `who` claimed the content with the hash `claim`.
+    ClaimCreated { who: T::AccountId, claim: T::ContentHash },
+    /// `who` revoked their claim on the content with the hash `claim`.
+    ClaimRevoked { who: T::AccountId, claim: T::ContentHash },
+    /// The claim on the content with the hash `claim` was transferred from `from` to `to`.
+    ClaimTransferred { from: T::AccountId, to: T::AccountId, claim: T::ContentHash },
+    /// `who` renewed their claim on `claim`, which now expires after the block `expires`, if
+    /// ever.
+    ClaimRenewed { who: T::AccountId, claim: T::ContentHash, expires: Option<T::BlockNumber> },
+    /// The claim of `who` on `claim` expired, and was pruned. Its deposit was returned.
+    ClaimExpired { who: T::AccountId, claim: T::ContentHash },
+}
+
+/// The errors which can be returned by the calls of the Proof of Existence Module.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// This content has already been claimed by someone.
+    AlreadyClaimed,
+    /// There is no claim for this content.
+    ClaimNotFound,
+    /// This content is claimed by someone else.
+    NotClaimOwner,
+    /// The claimant can't reserve the deposit of the claim, or pay the fee to renew it.
+    InsufficientBalance,
+    /// The memo is longer than `Config::MAX_MEMO_LENGTH`.
+    MemoTooLong,
+    /// The deposit of the claim can't be moved to the recipient, which must be an existing
+    /// account.
+    InvalidRecipient,
+    /// The claim has expired, and can only be revoked.
+    ClaimExpired,
+    /// The expiry must be after the current block, and renewing a claim must extend it.
+    InvalidExpiry,
 }
 
 impl<T: Config> Pallet<T> {
-    pub fn new() -> Self {
-        Self {claims: BTreeMap::new() }
+    /// Create a new instance of the proof of existence module, in the storage shared by all the
+    /// pallets.
+    pub fn new(storage: &Storage) -> Self {
+        Self {
+            claims: Claims::<T>::new(storage),
+            owned: OwnedClaims::<T>::new(storage),
+            expiries: Expiries::<T>::new(storage),
+            expiry_cursor: ExpiryCursor::<T>::new(storage),
+            events: Vec::new(),
+        }
     }
 
-    pub fn get_claim(&self, claim: &T::Content) -> Option<&T::AccountId> {
+    /// Deposit an event of this pallet.
+    pub fn deposit_event(&mut self, event: Event<T>) {
+        self.events.push(event);
+    }
+
+    /// Take all the events deposited by this pallet since the last call.
+    pub fn take_events(&mut self) -> Vec<Event<T>> {
+        core::mem::take(&mut self.events)
+    }
+
+    /// The hash some content is claimed with. This is how clients turn a document or a file into
+    /// a claim, off chain.
+    pub fn hash_content(content: &[u8]) -> T::ContentHash {
+        T::ContentHasher::hash(content)
+    }
+
+    /// The owner of the claim on `claim`, even if it has expired.
+    pub fn get_claim(&self, claim: &T::ContentHash) -> Option<T::AccountId> {
+        self.claims.get(claim).map(|info| info.owner)
+    }
+
+    /// The full record of a claim, if it exists.
+    pub fn claim_info(&self, claim: &T::ContentHash) -> Option<ClaimInfoOf<T>> {
         self.claims.get(claim)
     }
+
+    /// Prove the record of the claim on `claim`, or that there is none, to a light client which
+    /// only holds the state root of the block.
+    pub fn prove_claim(&self, claim: &T::ContentHash) -> StorageProof {
+        self.claims.prove(claim)
+    }
+
+    /// Check a proof made by `prove_claim` against the state root of a block, and return the
+    /// record of the claim it proves, if any. The claim may have expired: the light client checks
+    /// its expiry against the number of the block.
+    pub fn verify_claim(
+        state_root: &H256,
+        claim: &T::ContentHash,
+        proof: &[Vec<u8>],
+    ) -> Result<Option<ClaimInfoOf<T>>, ProofError> {
+        Claims::<T>::verify(state_root, claim, proof)
+    }
+
+    /// Whether there is an active claim on `claim` in the block `now`.
+    pub fn is_active(&self, claim: &T::ContentHash, now: T::BlockNumber) -> bool {
+        self.claims.get(claim).is_some_and(|info| info.is_active(&now))
+    }
+
+    /// The owner of the active claim on `content` in the block `now`, if it was notarized.
+    pub fn verify(&self, content: &[u8], now: T::BlockNumber) -> Option<T::AccountId> {
+        let info = self.claims.get(&Self::hash_content(content))?;
+        info.is_active(&now).then_some(info.owner)
+    }
+
+    /// Iterate over all the claims and their owners, in order.
+    pub fn claims(&self) -> impl Iterator<Item = (T::ContentHash, T::AccountId)> + use<T> {
+        self.claims.iter().map(|(claim, info)| (claim, info.owner))
+    }
+
+    /// Iterate over the claims which are active in the block `now`, in order.
+    pub fn active_claims(
+        &self,
+        now: T::BlockNumber,
+    ) -> impl Iterator<Item = (T::ContentHash, ClaimInfoOf<T>)> + use<T> {
+        self.claims.iter().filter(move |(_, info)| info.is_active(&now))
+    }
+
+    /// Iterate over the claims which have expired by the block `now`, but were not pruned yet, in
+    /// order.
+    pub fn expired_claims(
+        &self,
+        now: T::BlockNumber,
+    ) -> impl Iterator<Item = (T::ContentHash, ClaimInfoOf<T>)> + use<T> {
+        self.claims.iter().filter(move |(_, info)| !info.is_active(&now))
+    }
+
+    /// Iterate over the claims owned by `who`, in order.
+    pub fn claims_of(&self, who: &T::AccountId) -> impl Iterator<Item = T::ContentHash> + use<T> {
+        self.owned.iter_prefix(who).map(|(claim, ())| claim)
+    }
+
+    // Remove an expired claim, and return its deposit to its owner.
+    fn expire_claim(runtime: &mut T, claim: T::ContentHash) {
+        let pallet = Self::pallet_mut(runtime);
+        let Some(info) = pallet.remove_claim(&claim) else { return };
+        pallet.deposit_event(Event::ClaimExpired { who: info.owner.clone(), claim });
+        Self::currency_mut(runtime).unreserve(&info.owner, info.deposit);
+    }
+
+    // Store a claim, and add it to the indexes of its owner and its expiry.
+    fn insert_claim(&mut self, claim: T::ContentHash, info: ClaimInfoOf<T>) {
+        self.owned.insert(&info.owner, &claim, ());
+        if let Some(expires) = &info.expires {
+            self.expiries.insert(expires, &claim, ());
+        }
+        self.claims.insert(&claim, info);
+    }
+
+    // Remove a claim, and remove it from the indexes of its owner and its expiry.
+    fn remove_claim(&mut self, claim: &T::ContentHash) -> Option<ClaimInfoOf<T>> {
+        let info = self.claims.take(claim)?;
+        self.owned.remove(&info.owner, claim);
+        if let Some(expires) = &info.expires {
+            self.expiries.remove(expires, claim);
+        }
+        Some(info)
+    }
+
+    // The claim on `claim`, if the caller owns it.
+    fn owned_claim(
+        &self,
+        caller: &T::AccountId,
+        claim: &T::ContentHash,
+    ) -> Result<ClaimInfoOf<T>, Error> {
+        let info = self.claims.get(claim).ok_or(Error::ClaimNotFound)?;
+        if *caller != info.owner {
+            return Err(Error::NotClaimOwner)
+        }
+        Ok(info)
+    }
+
+    // The pallet, the currency and the system pallet are all held by the runtime.
+    fn pallet_mut(runtime: &mut T) -> &mut Self {
+        runtime.as_mut()
+    }
+
+    fn currency_mut(runtime: &mut T) -> &mut T::Currency {
+        runtime.as_mut()
+    }
+
+    fn block_number(runtime: &T) -> T::BlockNumber {
+        AsRef::<crate::system::Pallet<T>>::as_ref(runtime).block_number()
+    }
+}
+
+impl<T: Config> Hooks<T, T::BlockNumber> for Pallet<T> {
+    // Prune the claims which expired by the block `n`, at most `Config::MAX_EXPIRED_PER_BLOCK` of
+    // them and as many as the remaining weight allows, and return their deposits. Only the blocks
+    // from the cursor to `n` are read, and the cursor stops at the first block with claims left,
+    // whose backlog is pruned in the next blocks.
+    fn on_idle(runtime: &mut T, n: T::BlockNumber, remaining_weight: Weight) -> Weight {
+        let mut block = Self::pallet_mut(runtime).expiry_cursor.get().unwrap_or_else(Zero::zero);
+        let mut weight: Weight = 0;
+        let mut pruned = 0;
+        while block <= n
+            && pruned < T::MAX_EXPIRED_PER_BLOCK
+            && weight.saturating_add(READ_EXPIRIES_WEIGHT) <= remaining_weight
+        {
+            weight += READ_EXPIRIES_WEIGHT;
+            let mut expired = Self::pallet_mut(runtime).expiries.iter_prefix(&block).peekable();
+            while let Some((claim, ())) = expired.next_if(|_| {
+                pruned < T::MAX_EXPIRED_PER_BLOCK
+                    && weight.saturating_add(EXPIRE_CLAIM_WEIGHT) <= remaining_weight
+            }) {
+                Self::expire_claim(runtime, claim);
+                weight += EXPIRE_CLAIM_WEIGHT;
+                pruned += 1;
+            }
+            if expired.peek().is_some() {
+                break
+            }
+            match block.checked_add(&One::one()) {
+                Some(next) => block = next,
+                None => break,
+            }
+        }
+        Self::pallet_mut(runtime).expiry_cursor.put(block);
+        weight
+    }
 }
 
 #[macros::call]
 impl<T: Config> Pallet<T> {
-    pub fn create_claim(&mut self, caller:T::AccountId, claim: T::Content) -> DispatchResult {
-        if self.claims.contains_key(&claim) {
-            return Err("This content has already been claimed by someone");
+    /// Claim the content with the hash `claim` for the caller, reserving `Config::CLAIM_DEPOSIT`
+    /// from their balance. The claim expires after the block `expires`, if set. The `memo` is
+    /// kept with the claim.
+    ///
+    /// A claim which has expired, but was not pruned yet, can be claimed again.
+    #[call_index(0)]
+    #[weight(200)]
+    pub fn create_claim(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        claim: T::ContentHash,
+        memo: Option<Vec<u8>>,
+        expires: Option<T::BlockNumber>,
+    ) -> DispatchResult {
+        let caller = ensure_signed(origin)?;
+        if memo.as_ref().is_some_and(|memo| memo.len() > T::MAX_MEMO_LENGTH as usize) {
+            return Err(Error::MemoTooLong.into())
         }
-            self.claims.insert(claim, caller);
-            Ok(())
+        let now = Self::block_number(runtime);
+        if expires.is_some_and(|expires| expires <= now) {
+            return Err(Error::InvalidExpiry.into())
+        }
+        match Self::pallet_mut(runtime).claims.get(&claim) {
+            Some(info) if info.is_active(&now) => return Err(Error::AlreadyClaimed.into()),
+            Some(_) => Self::expire_claim(runtime, claim.clone()),
+            None => {},
+        }
+        // The errors of the currency belong to another pallet, so they are reported as ours.
+        let deposit = T::CLAIM_DEPOSIT;
+        Self::currency_mut(runtime)
+            .reserve(&caller, deposit)
+            .map_err(|_| Error::InsufficientBalance)?;
+        let info = ClaimInfo { owner: caller.clone(), deposit, created: now, expires, memo };
+        let pallet = Self::pallet_mut(runtime);
+        pallet.insert_claim(claim.clone(), info);
+        pallet.deposit_event(Event::ClaimCreated { who: caller, claim });
+        Ok(())
     }
 
-    pub fn revoke_claim(&mut self, caller: T::AccountId, claim: T::Content) -> DispatchResult {
-        let owner = self.get_claim(&claim).ok_or("claim does not exist")?;
-        if caller != *owner{
-            return Err("This content is owned by someone else")
+    /// Revoke the claim of the caller on `claim`, and return its deposit. Expired claims can be
+    /// revoked before they are pruned.
+    #[call_index(1)]
+    #[weight(100)]
+    pub fn revoke_claim(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        claim: T::ContentHash,
+    ) -> DispatchResult {
+        let caller = ensure_signed(origin)?;
+        let pallet = Self::pallet_mut(runtime);
+        pallet.owned_claim(&caller, &claim)?;
+        let info = pallet.remove_claim(&claim).ok_or(Error::ClaimNotFound)?;
+        pallet.deposit_event(Event::ClaimRevoked { who: caller.clone(), claim });
+        Self::currency_mut(runtime).unreserve(&caller, info.deposit);
+        Ok(())
+    }
+
+    /// Transfer the active claim of the caller on `claim` to `to`. The deposit of the claim is
+    /// moved to the reserved balance of `to`, which gets it back when revoking the claim.
+    #[call_index(2)]
+    #[weight(150)]
+    pub fn transfer_claim(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        claim: T::ContentHash,
+        to: T::AccountId,
+    ) -> DispatchResult {
+        let caller = ensure_signed(origin)?;
+        let now = Self::block_number(runtime);
+        let pallet = Self::pallet_mut(runtime);
+        if !pallet.owned_claim(&caller, &claim)?.is_active(&now) {
+            return Err(Error::ClaimExpired.into())
         }
-        self.claims.remove(&claim);
-    Ok(())
+        let mut info = pallet.remove_claim(&claim).ok_or(Error::ClaimNotFound)?;
+        let remaining = Self::currency_mut(runtime)
+            .repatriate_reserved(&caller, &to, info.deposit, BalanceStatus::Reserved)
+            .map_err(|_| Error::InvalidRecipient)?;
+        // The deposit can only have been partly moved if it was slashed in the meantime.
+        info.deposit = info.deposit.checked_sub(&remaining).unwrap_or_else(Zero::zero);
+        info.owner = to.clone();
+        let pallet = Self::pallet_mut(runtime);
+        pallet.insert_claim(claim.clone(), info);
+        pallet.deposit_event(Event::ClaimTransferred { from: caller, to, claim });
+        Ok(())
+    }
+
+    /// Extend the lifetime of the active claim of the caller on `claim`, until the block
+    /// `expires`, or forever. The caller pays `Config::RENEWAL_FEE`.
+    #[call_index(3)]
+    #[weight(100)]
+    pub fn renew_claim(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        claim: T::ContentHash,
+        expires: Option<T::BlockNumber>,
+    ) -> DispatchResult {
+        let caller = ensure_signed(origin)?;
+        let now = Self::block_number(runtime);
+        let pallet = Self::pallet_mut(runtime);
+        let info = pallet.owned_claim(&caller, &claim)?;
+        if !info.is_active(&now) {
+            return Err(Error::ClaimExpired.into())
+        }
+        // A claim which never expires can't be extended.
+        let extended = match (info.expires, expires) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(current), Some(expires)) => expires > current,
+        };
+        if !extended {
+            return Err(Error::InvalidExpiry.into())
+        }
+        let fee = T::RENEWAL_FEE;
+        if !fee.is_zero() {
+            Self::currency_mut(runtime)
+                .burn_from(&caller, fee, ExistenceRequirement::KeepAlive)
+                .map_err(|_| Error::InsufficientBalance)?;
+        }
+        let pallet = Self::pallet_mut(runtime);
+        let mut info = pallet.remove_claim(&claim).ok_or(Error::ClaimNotFound)?;
+        info.expires = expires;
+        pallet.insert_claim(claim.clone(), info);
+        pallet.deposit_event(Event::ClaimRenewed { who: caller, claim, expires });
+        Ok(())
     }
-    
 }
 
 #[cfg(test)]
 mod test {
-    struct TestConfig;
+    use super::{EXPIRE_CLAIM_WEIGHT, Error, Event, READ_EXPIRIES_WEIGHT};
+    use crate::support::{
+        BalanceStatus, Currency, DispatchError, DispatchResult, ExistenceRequirement,
+        ReservableCurrency, Storage,
+    };
+    use crate::support::{BlakeTwo256, BuildGenesisConfig, H256, Hasher, Hooks, Weight};
+    use crate::system::RawOrigin;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    type Pallet = super::Pallet<TestRuntime>;
+
+    // A currency keeping the free and reserved balances of the accounts, without any of the rules
+    // of the balances pallet.
+    #[derive(Debug, Default)]
+    struct MockCurrency {
+        free: BTreeMap<String, u64>,
+        reserved: BTreeMap<String, u64>,
+    }
+
+    impl Currency<String> for MockCurrency {
+        type Balance = u64;
+
+        fn minimum_balance() -> u64 {
+            0
+        }
+
+        fn total_issuance(&self) -> u64 {
+            self.free.values().chain(self.reserved.values()).sum()
+        }
+
+        fn free_balance(&self, who: &String) -> u64 {
+            self.free.get(who).copied().unwrap_or(0)
+        }
+
+        fn transfer(
+            &mut self,
+            from: &String,
+            to: &String,
+            amount: u64,
+            existence: ExistenceRequirement,
+        ) -> DispatchResult {
+            self.burn_from(from, amount, existence)?;
+            self.deposit_creating(to, amount)
+        }
+
+        fn burn_from(
+            &mut self,
+            who: &String,
+            amount: u64,
+            _existence: ExistenceRequirement,
+        ) -> DispatchResult {
+            let free = self.free_balance(who).checked_sub(amount).ok_or("insufficient balance")?;
+            self.free.insert(who.clone(), free);
+            Ok(())
+        }
+
+        fn deposit_creating(&mut self, who: &String, amount: u64) -> DispatchResult {
+            *self.free.entry(who.clone()).or_default() += amount;
+            Ok(())
+        }
+    }
+
+    impl ReservableCurrency<String> for MockCurrency {
+        fn reserved_balance(&self, who: &String) -> u64 {
+            self.reserved.get(who).copied().unwrap_or(0)
+        }
+
+        fn reserve(&mut self, who: &String, amount: u64) -> DispatchResult {
+            self.burn_from(who, amount, ExistenceRequirement::KeepAlive)?;
+            *self.reserved.entry(who.clone()).or_default() += amount;
+            Ok(())
+        }
+
+        fn unreserve(&mut self, who: &String, amount: u64) -> u64 {
+            let actual = amount.min(self.reserved_balance(who));
+            *self.reserved.entry(who.clone()).or_default() -= actual;
+            *self.free.entry(who.clone()).or_default() += actual;
+            amount - actual
+        }
+
+        fn slash_reserved(&mut self, who: &String, amount: u64) -> Result<u64, DispatchError> {
+            let actual = amount.min(self.reserved_balance(who));
+            *self.reserved.entry(who.clone()).or_default() -= actual;
+            Ok(amount - actual)
+        }
+
+        fn repatriate_reserved(
+            &mut self,
+            slashed: &String,
+            beneficiary: &String,
+            amount: u64,
+            status: BalanceStatus,
+        ) -> Result<u64, DispatchError> {
+            let remaining = self.slash_reserved(slashed, amount)?;
+            let balances = match status {
+                BalanceStatus::Free => &mut self.free,
+                BalanceStatus::Reserved => &mut self.reserved,
+            };
+            *balances.entry(beneficiary.clone()).or_default() += amount - remaining;
+            Ok(remaining)
+        }
+    }
+
+    struct TestRuntime {
+        system: crate::system::Pallet<TestRuntime>,
+        poe: Pallet,
+        currency: MockCurrency,
+    }
+
+    impl TestRuntime {
+        fn new() -> Self {
+            let mut currency = MockCurrency::default();
+            for who in ["femi", "nath", "aliyu"] {
+                currency.free.insert(who.to_string(), 100);
+            }
+            currency.free.insert("tunde".to_string(), 5);
+            let storage = Storage::new();
+            Self {
+                system: crate::system::Pallet::new(&storage),
+                poe: Pallet::new(&storage),
+                currency,
+            }
+        }
+    }
+
+    impl AsRef<crate::system::Pallet<TestRuntime>> for TestRuntime {
+        fn as_ref(&self) -> &crate::system::Pallet<TestRuntime> {
+            &self.system
+        }
+    }
 
-    impl super::Config for TestConfig {
-        type Content = String;
+    impl AsMut<Pallet> for TestRuntime {
+        fn as_mut(&mut self) -> &mut Pallet {
+            &mut self.poe
+        }
+    }
+
+    impl AsMut<MockCurrency> for TestRuntime {
+        fn as_mut(&mut self) -> &mut MockCurrency {
+            &mut self.currency
+        }
     }
 
-    impl crate::system::Config for TestConfig {
+    impl super::Config for TestRuntime {
+        type ContentHash = H256;
+        type ContentHasher = BlakeTwo256;
+        type Currency = MockCurrency;
+        const CLAIM_DEPOSIT: u64 = 10;
+        const RENEWAL_FEE: u64 = 5;
+        const MAX_MEMO_LENGTH: u32 = 8;
+        const MAX_EXPIRED_PER_BLOCK: u32 = 2;
+    }
+
+    impl crate::system::Config for TestRuntime {
         type AccountId = String;
+        type RuntimeOrigin = crate::system::RawOrigin<String>;
         type BlockNumber = u32;
         type Nonce = u32;
+        type Hash = crate::support::H256;
+        type Hashing = crate::support::BlakeTwo256;
+        type RuntimeEvent = crate::system::Event;
+        const MAX_BLOCK_WEIGHT: crate::support::Weight = 1_000;
+    }
+
+    fn signed(who: &str) -> RawOrigin<String> {
+        RawOrigin::Signed(who.to_string())
+    }
+
+    fn hash(content: &str) -> H256 {
+        Pallet::hash_content(content.as_bytes())
     }
 
     #[test]
     fn basic_proof_of_existence() {
-        let mut poe = super::Pallet::<TestConfig>::new();
-        assert_eq!(poe.get_claim(&"Hello, world".to_string()), None);
-        assert_eq!(poe.create_claim("femi".to_string(), "Hello, world".to_string()), Ok(()));
-        assert_eq!(poe.get_claim(&"Hello, world".to_string()), Some(&"femi".to_string()));
-        assert_eq!(
-            poe.create_claim("nath".to_string(), "Hello, world".to_string()),
-            Err("This content has already been claimed by someone")
-    );
-        assert_eq!(poe.revoke_claim("femi".to_string(), "Hello, world".to_string()), Ok(()));
-        assert_eq!(poe.create_claim("aliyu".to_string(), "Hello, world".to_string()), Ok(()));
-    }
-}
\ No newline at end of file
+        let mut runtime = TestRuntime::new();
+        let runtime = &mut runtime;
+        let hello = hash("Hello, world");
+        assert_eq!(runtime.poe.get_claim(&hello), None);
+        assert_eq!(Pallet::create_claim(runtime, signed("femi"), hello, None, None), Ok(()));
+        assert_eq!(runtime.poe.get_claim(&hello), Some("femi".to_string()));
+        assert_eq!(
+            Pallet::create_claim(runtime, signed("nath"), hello, None, None),
+            Err(Error::AlreadyClaimed.into())
+        );
+        assert_eq!(
+            Pallet::revoke_claim(runtime, signed("nath"), hello),
+            Err(Error::NotClaimOwner.into())
+        );
+        assert_eq!(Pallet::revoke_claim(runtime, signed("femi"), hello), Ok(()));
+        assert_eq!(
+            Pallet::revoke_claim(runtime, signed("femi"), hello),
+            Err(Error::ClaimNotFound.into())
+        );
+        assert_eq!(Pallet::create_claim(runtime, signed("aliyu"), hello, None, None), Ok(()));
+        // Claims belong to an account, so they can only be made from a signed origin.
+        assert_eq!(
+            Pallet::create_claim(runtime, RawOrigin::Root, hello, None, None),
+            Err(DispatchError::BadOrigin)
+        );
+        assert_eq!(
+            runtime.poe.take_events(),
+            vec![
+                Event::ClaimCreated { who: "femi".to_string(), claim: hello },
+                Event::ClaimRevoked { who: "femi".to_string(), claim: hello },
+                Event::ClaimCreated { who: "aliyu".to_string(), claim: hello },
+            ]
+        );
+    }
+
+    #[test]
+    fn genesis_claims() {
+        let mut runtime = TestRuntime::new();
+        let (femi, nath) = ("femi".to_string(), "nath".to_string());
+        let (a, b) = (hash("a"), hash("b"));
+
+        let genesis = super::GenesisConfig::<TestRuntime> { claims: vec![(a, femi.clone())] };
+        assert_eq!(genesis.build(&mut runtime.poe, 7), Ok(()));
+        let info = runtime.poe.claim_info(&a).unwrap();
+        assert_eq!(
+            (info.owner, info.deposit, info.created, info.expires),
+            (femi.clone(), 0, 7, None)
+        );
+
+        // The same content can't be claimed twice.
+        let genesis = super::GenesisConfig::<TestRuntime> {
+            claims: vec![(b, nath.clone()), (a, nath.clone())],
+        };
+        assert_eq!(genesis.build(&mut runtime.poe, 7), Err(Error::AlreadyClaimed.into()));
+        assert_eq!(runtime.poe.get_claim(&a), Some(femi.clone()));
+        assert_eq!(runtime.poe.claims_of(&femi).collect::<Vec<_>>(), vec![a]);
+    }
+
+    #[test]
+    fn claims_are_made_on_content_hashes() {
+        let mut runtime = TestRuntime::new();
+        let runtime = &mut runtime;
+        let document = b"The quick brown fox jumps over the lazy dog";
+
+        // The hasher is part of the configuration.
+        let claim = Pallet::hash_content(document);
+        assert_eq!(claim, BlakeTwo256::hash(document));
+        assert_eq!(Pallet::create_claim(runtime, signed("femi"), claim, None, None), Ok(()));
+        assert_eq!(runtime.poe.verify(document, 0), Some("femi".to_string()));
+        assert_eq!(runtime.poe.verify(b"The quick brown fox", 0), None);
+    }
+
+    #[test]
+    fn claims_reserve_a_deposit() {
+        let mut runtime = TestRuntime::new();
+        let runtime = &mut runtime;
+        let (femi, tunde) = ("femi".to_string(), "tunde".to_string());
+        let doc = hash("doc");
+
+        assert_eq!(Pallet::create_claim(runtime, signed("femi"), doc, None, None), Ok(()));
+        assert_eq!(runtime.poe.claim_info(&doc).map(|info| info.deposit), Some(10));
+        assert_eq!(runtime.currency.free_balance(&femi), 90);
+        assert_eq!(runtime.currency.reserved_balance(&femi), 10);
+
+        // The deposit is returned when the claim is revoked.
+        assert_eq!(Pallet::revoke_claim(runtime, signed("femi"), doc), Ok(()));
+        assert_eq!(runtime.currency.free_balance(&femi), 100);
+        assert_eq!(runtime.currency.reserved_balance(&femi), 0);
+
+        // Claims can't be made without a deposit.
+        assert_eq!(
+            Pallet::create_claim(runtime, signed("tunde"), doc, None, None),
+            Err(Error::InsufficientBalance.into())
+        );
+        assert_eq!(runtime.currency.free_balance(&tunde), 5);
+        assert_eq!(runtime.poe.claims().count(), 0);
+    }
+
+    #[test]
+    fn claims_can_be_transferred() {
+        let mut runtime = TestRuntime::new();
+        let runtime = &mut runtime;
+        let (femi, nath) = ("femi".to_string(), "nath".to_string());
+        let (doc, notes, memo) = (hash("doc"), hash("notes"), b"v1".to_vec());
+
+        runtime.system.inc_block_number().unwrap();
+        runtime.system.inc_block_number().unwrap();
+        assert_eq!(
+            Pallet::create_claim(runtime, signed("femi"), doc, Some(b"too long".repeat(2)), None),
+            Err(Error::MemoTooLong.into())
+        );
+        assert_eq!(
+            Pallet::create_claim(runtime, signed("femi"), doc, Some(memo.clone()), None),
+            Ok(())
+        );
+        assert_eq!(Pallet::create_claim(runtime, signed("femi"), notes, None, None), Ok(()));
+        let info = super::ClaimInfo {
+            owner: femi.clone(),
+            deposit: 10,
+            created: 2,
+            expires: None,
+            memo: Some(memo),
+        };
+        assert_eq!(runtime.poe.claim_info(&doc), Some(info.clone()));
+        assert_eq!(
+            runtime.poe.claims_of(&femi).collect::<BTreeSet<_>>(),
+            BTreeSet::from([doc, notes])
+        );
+
+        // Only the owner can transfer a claim, to an existing account.
+        assert_eq!(
+            Pallet::transfer_claim(runtime, signed("nath"), doc, nath.clone()),
+            Err(Error::NotClaimOwner.into())
+        );
+        assert_eq!(
+            Pallet::transfer_claim(runtime, signed("femi"), hash("unknown"), nath.clone()),
+            Err(Error::ClaimNotFound.into())
+        );
+        assert_eq!(Pallet::transfer_claim(runtime, signed("femi"), doc, nath.clone()), Ok(()));
+
+        // The claim keeps its metadata, and its deposit moves to the new owner.
+        assert_eq!(
+            runtime.poe.claim_info(&doc),
+            Some(super::ClaimInfo { owner: nath.clone(), ..info })
+        );
+        assert_eq!(runtime.poe.claims_of(&femi).collect::<Vec<_>>(), vec![notes]);
+        assert_eq!(runtime.poe.claims_of(&nath).collect::<Vec<_>>(), vec![doc]);
+        assert_eq!(runtime.currency.reserved_balance(&femi), 10);
+        assert_eq!(runtime.currency.reserved_balance(&nath), 10);
+        assert_eq!(runtime.currency.free_balance(&nath), 100);
+
+        assert_eq!(Pallet::revoke_claim(runtime, signed("nath"), doc), Ok(()));
+        assert_eq!(runtime.currency.free_balance(&nath), 110);
+        assert_eq!(runtime.poe.claims_of(&nath).count(), 0);
+        assert_eq!(
+            runtime.poe.take_events().last(),
+            Some(&Event::ClaimRevoked { who: nath.clone(), claim: doc })
+        );
+    }
+
+    #[test]
+    fn claims_expire_and_can_be_renewed() {
+        let mut runtime = TestRuntime::new();
+        let runtime = &mut runtime;
+        let femi = "femi".to_string();
+        let (a, b, c) = (hash("a"), hash("b"), hash("c"));
+
+        runtime.system.inc_block_number().unwrap();
+        assert_eq!(
+            Pallet::create_claim(runtime, signed("femi"), a, None, Some(1)),
+            Err(Error::InvalidExpiry.into())
+        );
+        for claim in [a, b, c] {
+            assert_eq!(Pallet::create_claim(runtime, signed("femi"), claim, None, Some(3)), Ok(()));
+        }
+        assert_eq!(runtime.currency.reserved_balance(&femi), 30);
+
+        // Renewing can only extend the lifetime of a claim, and costs a fee.
+        assert_eq!(
+            Pallet::renew_claim(runtime, signed("femi"), a, Some(3)),
+            Err(Error::InvalidExpiry.into())
+        );
+        assert_eq!(
+            Pallet::renew_claim(runtime, signed("nath"), a, Some(5)),
+            Err(Error::NotClaimOwner.into())
+        );
+        assert_eq!(Pallet::renew_claim(runtime, signed("femi"), a, Some(5)), Ok(()));
+        assert_eq!(runtime.currency.free_balance(&femi), 65);
+        assert_eq!(runtime.currency.total_issuance(), 300);
+
+        // Claims are active until the end of the block they expire in.
+        assert!(runtime.poe.is_active(&b, 3));
+        assert!(!runtime.poe.is_active(&b, 4));
+        assert_eq!(
+            runtime.poe.active_claims(4).map(|(claim, _)| claim).collect::<Vec<_>>(),
+            vec![a]
+        );
+        assert_eq!(runtime.poe.expired_claims(4).count(), 2);
+
+        // Expired claims can't be renewed or transferred, but they can be claimed again before
+        // they are pruned.
+        runtime.system.inc_block_number().unwrap();
+        runtime.system.inc_block_number().unwrap();
+        runtime.system.inc_block_number().unwrap();
+        assert_eq!(
+            Pallet::renew_claim(runtime, signed("femi"), b, None),
+            Err(Error::ClaimExpired.into())
+        );
+        assert_eq!(
+            Pallet::transfer_claim(runtime, signed("femi"), b, "nath".to_string()),
+            Err(Error::ClaimExpired.into())
+        );
+        assert_eq!(Pallet::create_claim(runtime, signed("nath"), b, None, None), Ok(()));
+        assert_eq!(runtime.poe.get_claim(&b), Some("nath".to_string()));
+
+        // The claims which expired by the end of a block are pruned, and their deposits are
+        // returned. Each block read and each claim pruned is charged.
+        runtime.poe.take_events();
+        let weight = 5 * READ_EXPIRIES_WEIGHT + EXPIRE_CLAIM_WEIGHT;
+        assert_eq!(Pallet::on_idle(runtime, 4, Weight::MAX), weight);
+        assert_eq!(runtime.poe.claims_of(&femi).collect::<BTreeSet<_>>(), BTreeSet::from([a]));
+        assert_eq!(
+            runtime.poe.take_events(),
+            vec![Event::ClaimExpired { who: femi.clone(), claim: c }]
+        );
+        assert_eq!(runtime.currency.reserved_balance(&femi), 10);
+        let weight = READ_EXPIRIES_WEIGHT + EXPIRE_CLAIM_WEIGHT;
+        assert_eq!(Pallet::on_idle(runtime, 5, Weight::MAX), weight);
+        assert_eq!(runtime.poe.claims_of(&femi).count(), 0);
+        assert_eq!(runtime.currency.free_balance(&femi), 95);
+        assert_eq!(runtime.poe.get_claim(&b), Some("nath".to_string()));
+
+        // A claim which never expires can't be extended, so no fee is paid to renew it.
+        let aliyu = "aliyu".to_string();
+        assert_eq!(Pallet::create_claim(runtime, signed("aliyu"), c, None, None), Ok(()));
+        for expires in [None, Some(10)] {
+            assert_eq!(
+                Pallet::renew_claim(runtime, signed("aliyu"), c, expires),
+                Err(Error::InvalidExpiry.into())
+            );
+        }
+        assert_eq!(runtime.currency.free_balance(&aliyu), 90);
+    }
+
+    #[test]
+    fn expired_claims_are_pruned_within_the_limits() {
+        let mut runtime = TestRuntime::new();
+        let runtime = &mut runtime;
+        let femi = "femi".to_string();
+        let claims = [hash("a"), hash("b"), hash("c"), hash("d")];
+
+        runtime.system.inc_block_number().unwrap();
+        for (claim, expires) in claims.iter().zip([2, 2, 2, 3]) {
+            let res = Pallet::create_claim(runtime, signed("femi"), *claim, None, Some(expires));
+            assert_eq!(res, Ok(()));
+        }
+        assert_eq!(Pallet::on_idle(runtime, 1, Weight::MAX), 2 * READ_EXPIRIES_WEIGHT);
+
+        // Nothing is pruned without the weight to read the expiries.
+        assert_eq!(Pallet::on_idle(runtime, 2, READ_EXPIRIES_WEIGHT - 1), 0);
+        assert_eq!(runtime.poe.claims_of(&femi).count(), 4);
+
+        // The weight left limits the claims pruned, and so does `Config::MAX_EXPIRED_PER_BLOCK`.
+        // The claims left are pruned in the next blocks, before the ones expiring later.
+        let weight = READ_EXPIRIES_WEIGHT + EXPIRE_CLAIM_WEIGHT;
+        assert_eq!(Pallet::on_idle(runtime, 2, weight + 1), weight);
+        assert_eq!(runtime.poe.claims_of(&femi).count(), 3);
+        let weight = READ_EXPIRIES_WEIGHT + 2 * EXPIRE_CLAIM_WEIGHT;
+        assert_eq!(Pallet::on_idle(runtime, 3, Weight::MAX), weight);
+        assert_eq!(runtime.poe.claims_of(&femi).collect::<Vec<_>>(), vec![claims[3]]);
+        let weight = 2 * READ_EXPIRIES_WEIGHT + EXPIRE_CLAIM_WEIGHT;
+        assert_eq!(Pallet::on_idle(runtime, 4, Weight::MAX), weight);
+        assert_eq!(runtime.poe.claims_of(&femi).count(), 0);
+        assert_eq!(runtime.currency.reserved_balance(&femi), 0);
+
+        // Once the backlog is pruned, only the current block is read.
+        assert_eq!(Pallet::on_idle(runtime, 5, Weight::MAX), READ_EXPIRIES_WEIGHT);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::support::{
+    BuildGenesisConfig, Decode, Dispatch, DispatchResult, DispatchResultWithPostInfo, Encode,
+    GetWeight, Hooks, Storage, StorageValue, Transactional, Weight, extract_actual_weight,
+    with_transaction,
+};
+use crate::system::{RawOrigin, ensure_signed};
+use core::fmt::Debug;
+
+/// The configuration trait for the Sudo Module.
+///
+/// The sudo key dispatches calls of the whole runtime, so the runtime itself must be able to
+/// dispatch them, in a transaction.
+pub trait Config:
+    crate::system::Config
+    + Dispatch<Origin = Self::RuntimeOrigin, Call = <Self as Config>::RuntimeCall>
+    + Transactional
+    + AsMut<Pallet<Self>>
+    + Sized
+{
+    /// The calls of the runtime, which the sudo key can dispatch.
+    type RuntimeCall: Clone + PartialEq + Debug + Encode + Decode + GetWeight;
+}
+
+// The weight of the sudo calls themselves, on top of the weight of the call they dispatch.
+const SUDO_WEIGHT: Weight = 100;
+
+/// The account allowed to make sudo calls, if any.
+#[macros::storage]
+type Key<T: Config> = StorageValue<_, T::AccountId>;
+
+/// This is the Sudo Module.
+/// It keeps a single key account, which can dispatch any call of the runtime as root.
+#[derive(Debug)]
+pub struct Pallet<T: Config> {
+    key: Key<T>,
+    // The events deposited by this pallet, which are moved to the system pallet by the runtime.
+    events: Vec<Event<T>>,
+}
+
+/// The initial state of the Sudo Module.
+#[derive(Debug)]
+pub struct GenesisConfig<T: Config> {
+    /// The sudo key at genesis. Without a key, sudo calls can never be made.
+    pub key: Option<T::AccountId>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+    fn default() -> Self {
+        Self { key: None }
+    }
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>, T::BlockNumber> for GenesisConfig<T> {
+    fn build(&self, pallet: &mut Pallet<T>, _n: T::BlockNumber) -> DispatchResult {
+        if let Some(key) = &self.key {
+            pallet.key.put(key.clone());
+        }
+        Ok(())
+    }
+}
+
+/// The events of the Sudo Module.
+#[macros::event]
+pub enum Event<T: Config> {
+    /// A call was dispatched as root by the sudo key, with this result.
+    Sudid { sudo_result: DispatchResult },
+    /// The sudo key was changed from `old` to `new`.
+    KeyChanged { old: Option<T::AccountId>, new: T::AccountId },
+    /// A call was dispatched on behalf of another account by the sudo key, with this result.
+    SudoAsDone { sudo_result: DispatchResult },
+}
+
+/// The errors which can be returned by the calls of the Sudo Module.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The caller is not the sudo key.
+    RequireSudo,
+}
+
+impl<T: Config> Pallet<T> {
+    /// Create a new instance of the sudo module, in the storage shared by all the pallets.
+    pub fn new(storage: &Storage) -> Self {
+        Self { key: Key::<T>::new(storage), events: Vec::new() }
+    }
+
+    /// Deposit an event of this pallet.
+    pub fn deposit_event(&mut self, event: Event<T>) {
+        self.events.push(event);
+    }
+
+    /// Take all the events deposited by this pallet since the last call.
+    pub fn take_events(&mut self) -> Vec<Event<T>> {
+        core::mem::take(&mut self.events)
+    }
+
+    /// Get the sudo key, if any.
+    pub fn key(&self) -> Option<T::AccountId> {
+        self.key.get()
+    }
+
+    // Check that `origin` is signed by the sudo key.
+    fn ensure_sudo(&self, origin: T::RuntimeOrigin) -> DispatchResult {
+        let who = ensure_signed(origin)?;
+        if self.key() != Some(who) {
+            return Err(Error::RequireSudo.into())
+        }
+        Ok(())
+    }
+
+    // Dispatch `call` from `origin` in its own transaction, so a failed call leaves no changes
+    // behind, even though the sudo call itself succeeds. Returns the result of the call, and the
+    // weight of the sudo call, including the weight the call actually used.
+    fn dispatch_nested(
+        runtime: &mut T,
+        origin: RawOrigin<T::AccountId>,
+        call: <T as Config>::RuntimeCall,
+    ) -> (DispatchResult, Weight) {
+        let weight = call.weight();
+        let res = with_transaction(runtime, |runtime| runtime.dispatch(origin.into(), call));
+        let actual_weight = extract_actual_weight(&res, weight).saturating_add(SUDO_WEIGHT);
+        (res.map(|_| ()).map_err(|e| e.error), actual_weight)
+    }
+}
+
+// The sudo pallet has no per-block logic.
+impl<T: Config> Hooks<T, T::BlockNumber> for Pallet<T> {}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+    /// Dispatch `call` with the root origin. Only the sudo key can do this.
+    /// The sudo call succeeds even if `call` fails, and its result is in the `Sudid` event.
+    #[call_index(0)]
+    #[weight(call.weight().saturating_add(SUDO_WEIGHT))]
+    // The call is boxed in the `Call` enum, since a `RuntimeCall` can't contain itself directly.
+    #[allow(clippy::boxed_local)]
+    pub fn sudo(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        call: Box<<T as Config>::RuntimeCall>,
+    ) -> DispatchResultWithPostInfo {
+        runtime.as_mut().ensure_sudo(origin)?;
+        let (sudo_result, actual_weight) = Self::dispatch_nested(runtime, RawOrigin::Root, *call);
+        runtime.as_mut().deposit_event(Event::Sudid { sudo_result });
+        Ok(Some(actual_weight).into())
+    }
+
+    /// Make `new` the sudo key. Only the current sudo key can do this.
+    #[call_index(1)]
+    #[weight(SUDO_WEIGHT)]
+    pub fn set_key(&mut self, origin: T::RuntimeOrigin, new: T::AccountId) -> DispatchResult {
+        self.ensure_sudo(origin)?;
+        let old = self.key.take();
+        self.key.put(new.clone());
+        self.deposit_event(Event::KeyChanged { old, new });
+        Ok(())
+    }
+
+    /// Dispatch `call` with the signed origin of `who`. Only the sudo key can do this.
+    /// The sudo call succeeds even if `call` fails, and its result is in the `SudoAsDone` event.
+    #[call_index(2)]
+    #[weight(call.weight().saturating_add(SUDO_WEIGHT))]
+    #[allow(clippy::boxed_local)]
+    pub fn sudo_as(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        who: T::AccountId,
+        call: Box<<T as Config>::RuntimeCall>,
+    ) -> DispatchResultWithPostInfo {
+        runtime.as_mut().ensure_sudo(origin)?;
+        let (sudo_result, actual_weight) =
+            Self::dispatch_nested(runtime, RawOrigin::Signed(who), *call);
+        runtime.as_mut().deposit_event(Event::SudoAsDone { sudo_result });
+        Ok(Some(actual_weight).into())
+    }
+}
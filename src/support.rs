@@ -1,4 +1,23 @@
+pub mod codec;
+pub mod crypto;
+mod currency;
+mod hashing;
+pub mod keyring;
+mod storage;
+mod transactional;
+
+pub use codec::{Compact, Decode, Encode};
+pub use crypto::Verify;
+pub use currency::{BalanceStatus, Currency, ExistenceRequirement, ReservableCurrency};
+pub use hashing::{BlakeTwo256, Hasher, H256, Sha256};
+pub use storage::{
+    FileBackend, ProofError, RecordLog, Storage, StorageDoubleMap, StorageMap, StoragePrefix,
+    StorageProof, StorageValue,
+};
+pub use transactional::{with_transaction, Transactional, TransactionalMap};
+
 // A representation of a block in our blockchain
+#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
 pub struct Block<Header, Extrinsic> {
     // The block header contains metadata about the block.
     pub header: Header,
@@ -7,26 +26,330 @@ pub struct Block<Header, Extrinsic> {
 }
 
 // Header struct that contains metadata of the block
-pub struct Header<BlockNumber> {
+#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
+pub struct Header<BlockNumber, Hash> {
+    // The hash of the header of the previous block, which links the blocks into a chain.
+    pub parent_hash: Hash,
     pub block_number: BlockNumber,
-    // pub parent_block_hash: String,
+    // The root of the trie of the runtime state after executing this block.
+    pub state_root: Hash,
+    // The hash of all the extrinsics included in this block.
+    pub extrinsics_root: Hash,
+}
+
+impl<BlockNumber: Encode, Hash: Encode> Header<BlockNumber, Hash> {
+    /// The hash of this header, which identifies the block.
+    pub fn hash<H: Hasher<Output = Hash>>(&self) -> Hash {
+        H::hash_of(self)
+    }
+}
+
+// Extrinsic struct that contains information about the transaction to execute.
+// Signed extrinsics prove who is calling, while unsigned extrinsics (like inherents) have no
+// caller. Unsigned extrinsics are encoded with a leading `0` byte, and signed ones with a `1`.
+#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
+pub struct Extrinsic<AccountId, Call, Signature, Nonce> {
+    // The signer of the extrinsic and their signature, if the extrinsic is signed.
+    pub signature: Option<ExtrinsicSignature<AccountId, Signature, Nonce>>,
+    pub call: Call,
+}
+
+// The signature part of a signed extrinsic.
+#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
+pub struct ExtrinsicSignature<AccountId, Signature, Nonce> {
+    pub signer: AccountId,
+    // The signature of the signed payload, see `Extrinsic::signed_payload`.
+    pub signature: Signature,
+    // The nonce of the signer at the time the extrinsic is applied.
+    pub nonce: Nonce,
 }
 
-// Extrinsic struct that contains information about the transaction to execute
-pub struct Extrinsic<Caller, Call> {
-    pub caller: Caller,
+// An extrinsic which signature has been checked, and can be applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckedExtrinsic<AccountId, Call, Nonce> {
+    // The signer and the nonce, if the extrinsic is signed.
+    pub signed: Option<(AccountId, Nonce)>,
     pub call: Call,
 }
 
+impl<AccountId, Call, Signature, Nonce> Extrinsic<AccountId, Call, Signature, Nonce>
+where
+    Call: Encode,
+    Nonce: Encode,
+    Signature: Verify<Signer = AccountId>,
+{
+    /// Create an unsigned extrinsic.
+    // Our demo in `main` only creates signed extrinsics.
+    #[allow(dead_code)]
+    pub fn new_unsigned(call: Call) -> Self {
+        Self { signature: None, call }
+    }
+
+    /// Create a signed extrinsic, from a signature of the signed payload.
+    pub fn new_signed(call: Call, signer: AccountId, signature: Signature, nonce: Nonce) -> Self {
+        Self { signature: Some(ExtrinsicSignature { signer, signature, nonce }), call }
+    }
+
+    /// The payload which is signed: the call, the nonce, and the hash of the genesis block. The
+    /// genesis hash makes sure the extrinsic can't be replayed on another chain.
+    pub fn signed_payload<Hash: Encode>(
+        call: &Call,
+        nonce: &Nonce,
+        genesis_hash: &Hash,
+    ) -> Vec<u8> {
+        (call, nonce, genesis_hash).encode()
+    }
+
+    /// Check the signature of the extrinsic, for the chain with this genesis hash.
+    /// Returns `None` if the signature is invalid.
+    pub fn check<Hash: Encode>(
+        self,
+        genesis_hash: &Hash,
+    ) -> Option<CheckedExtrinsic<AccountId, Call, Nonce>> {
+        let Some(ExtrinsicSignature { signer, signature, nonce }) = self.signature else {
+            return Some(CheckedExtrinsic { signed: None, call: self.call })
+        };
+        let payload = Self::signed_payload(&self.call, &nonce, genesis_hash);
+        if !signature.verify(&payload, &signer) {
+            return None
+        }
+        Some(CheckedExtrinsic { signed: Some((signer, nonce)), call: self.call })
+    }
+}
+
+impl<Call: Encode, Nonce: Encode> Extrinsic<crypto::Public, Call, crypto::Signature, Nonce> {
+    /// Create an extrinsic signed by `pair`, for the chain with this genesis hash.
+    pub fn new_signed_by<Hash: Encode>(
+        pair: &crypto::Pair,
+        call: Call,
+        nonce: Nonce,
+        genesis_hash: &Hash,
+    ) -> Self {
+        let signature = pair.sign(&Self::signed_payload(&call, &nonce, genesis_hash));
+        Self::new_signed(call, pair.public(), signature, nonce)
+    }
+}
+
 // Result of the runtime
-pub type DispatchResult = Result<(), &'static str>;
+pub type DispatchResult = Result<(), DispatchError>;
+
+/// The weight of a call: how much of the resources of a block it uses to execute.
+pub type Weight = u64;
+
+/// A trait for calls which declare their weight before they are dispatched.
+pub trait GetWeight {
+    /// The weight of the call. This is the most the call can use, and what its fee is based on.
+    fn weight(&self) -> Weight;
+}
+
+/// What is known about a call once it was dispatched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PostDispatchInfo {
+    /// The weight the call actually used, if it is less than the weight it declared. The unused
+    /// weight is refunded. `None` means the call used all of its declared weight.
+    pub actual_weight: Option<Weight>,
+}
+
+impl PostDispatchInfo {
+    /// The weight the call used, which is never more than the weight it declared.
+    pub fn calc_actual_weight(&self, declared: Weight) -> Weight {
+        self.actual_weight.map_or(declared, |actual| actual.min(declared))
+    }
+}
+
+// Calls returning a plain `DispatchResult` use all of their declared weight.
+impl From<()> for PostDispatchInfo {
+    fn from(_: ()) -> Self {
+        Self { actual_weight: None }
+    }
+}
+
+impl From<Option<Weight>> for PostDispatchInfo {
+    fn from(actual_weight: Option<Weight>) -> Self {
+        Self { actual_weight }
+    }
+}
+
+/// The result of a call which can report the weight it actually used.
+///
+/// Callable functions can return either this, or a plain `DispatchResult`.
+pub type DispatchResultWithPostInfo = Result<PostDispatchInfo, DispatchErrorWithPostInfo>;
+
+/// The weight a call actually used, given the result of its dispatch and its declared weight.
+pub fn extract_actual_weight(result: &DispatchResultWithPostInfo, declared: Weight) -> Weight {
+    let post_info = match result {
+        Ok(post_info) => post_info,
+        Err(error) => &error.post_info,
+    };
+    post_info.calc_actual_weight(declared)
+}
+
+/// A dispatch error, along with the weight the call used before it failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DispatchErrorWithPostInfo {
+    pub post_info: PostDispatchInfo,
+    pub error: DispatchError,
+}
+
+impl DispatchErrorWithPostInfo {
+    /// Attribute the error to the pallet at `index` in the runtime. See `DispatchError::in_pallet`.
+    pub fn in_pallet(self, index: u8) -> Self {
+        Self { post_info: self.post_info, error: self.error.in_pallet(index) }
+    }
+}
+
+// Any error can be returned from a call returning `DispatchResultWithPostInfo`, in which case the
+// call used all of its declared weight.
+impl<E: Into<DispatchError>> From<E> for DispatchErrorWithPostInfo {
+    fn from(error: E) -> Self {
+        Self { post_info: PostDispatchInfo::default(), error: error.into() }
+    }
+}
+
+/// The reason why dispatching a call failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchError {
+    /// An error declared in the `Error` enum of one of the pallets.
+    Module(ModuleError),
+    /// The call was dispatched from an origin which is not allowed to make it.
+    BadOrigin,
+    /// Some other error which does not belong to a specific pallet.
+    Other(&'static str),
+}
+
+/// A module-scoped error: which pallet raised it, and which variant of its `Error` enum it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleError {
+    /// The index of the pallet in the `Runtime` struct, `system` being `0`.
+    /// This is `None` until the error has been dispatched through the runtime.
+    pub index: Option<u8>,
+    /// The index of the variant in the pallet's `Error` enum.
+    pub error: u8,
+    /// The name of the variant, for debugging purposes.
+    pub message: Option<&'static str>,
+}
+
+impl DispatchError {
+    /// Attribute a module error to the pallet at `index` in the runtime.
+    /// Errors which already belong to a pallet are left untouched, so errors coming from nested
+    /// dispatches keep pointing at the pallet which raised them.
+    pub fn in_pallet(self, index: u8) -> Self {
+        match self {
+            DispatchError::Module(ModuleError { index: None, error, message }) => {
+                DispatchError::Module(ModuleError { index: Some(index), error, message })
+            },
+            other => other,
+        }
+    }
+}
+
+impl core::fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            DispatchError::Module(ModuleError { index, error, message }) => {
+                write!(f, "Module error {error}")?;
+                if let Some(index) = index {
+                    write!(f, " in pallet {index}")?;
+                }
+                if let Some(message) = message {
+                    write!(f, ": {message}")?;
+                }
+                Ok(())
+            },
+            DispatchError::BadOrigin => write!(f, "Bad origin"),
+            DispatchError::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl From<&'static str> for DispatchError {
+    fn from(message: &'static str) -> Self {
+        DispatchError::Other(message)
+    }
+}
+
+/// The genesis configuration of a pallet, which sets the initial state of the pallet before any
+/// block is executed.
+///
+/// The configuration usually comes from a chain spec, so it can be invalid, like two balances for
+/// the same account. Building it then fails, rather than leaving an inconsistent state.
+pub trait BuildGenesisConfig<Pallet, BlockNumber> {
+    /// Write the initial state into `pallet`, in the genesis block `n`.
+    fn build(&self, pallet: &mut Pallet, n: BlockNumber) -> DispatchResult;
+}
 
 // A trait for handling incoming extrinsics
 pub trait Dispatch {
-    // Who is calling the function.
-    type Caller; // these are to be defined by the implementor
+    // Who is calling the function: an account, the system itself, or nobody.
+    type Origin; // these are to be defined by the implementor
     // What function or transaction is being called.
     type Call;
 
-    fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> DispatchResult;
+    fn dispatch(&mut self, origin: Self::Origin, call: Self::Call) -> DispatchResultWithPostInfo;
+}
+
+/// A call of a pallet, which is dispatched on the whole `Runtime` rather than on its pallet only.
+///
+/// Most calls only change the state of their own pallet, but having the runtime lets calls like
+/// `sudo` dispatch other calls of the runtime.
+pub trait Dispatchable<Runtime> {
+    /// Who is calling the function.
+    type Origin;
+
+    fn dispatch(self, runtime: &mut Runtime, origin: Self::Origin) -> DispatchResultWithPostInfo;
+}
+
+/// How the runtime cleans up after the accounts reaped by its pallets.
+///
+/// The balances pallet reaps the accounts which fall below the existential deposit. After each
+/// call, the runtime removes what the other pallets keep for those accounts, like their nonce.
+pub trait OnReapedAccounts {
+    fn on_reaped_accounts(&mut self);
+}
+
+/// The per-block logic of a pallet, which the runtime runs on every block, outside of any
+/// extrinsic.
+///
+/// The runtime calls the hooks of every pallet in the order of the `Runtime` struct, and
+/// implements `Hooks` itself by doing so. Like calls, hooks take the whole `Runtime`, so they can
+/// reach other pallets. All of them do nothing by default.
+pub trait Hooks<Runtime, BlockNumber> {
+    /// Called at the start of the block `n`, before its extrinsics. Returns the weight it used,
+    /// which is taken from the block.
+    fn on_initialize(_runtime: &mut Runtime, _n: BlockNumber) -> Weight {
+        0
+    }
+
+    /// Called at the end of the block `n`, after its extrinsics and `on_idle`.
+    fn on_finalize(_runtime: &mut Runtime, _n: BlockNumber) {}
+
+    /// Called after the extrinsics of the block `n`, with the weight the block has left. Returns
+    /// the weight it used, which must not be more than `remaining_weight`.
+    fn on_idle(_runtime: &mut Runtime, _n: BlockNumber, _remaining_weight: Weight) -> Weight {
+        0
+    }
+
+    /// Called once when a new version of the runtime starts running on an existing chain, to
+    /// migrate the state. Returns the weight it used.
+    // Our node does not upgrade its runtime yet.
+    #[allow(dead_code)]
+    fn on_runtime_upgrade(_runtime: &mut Runtime) -> Weight {
+        0
+    }
+}
+
+/// How the runtime charges the fees of extrinsics.
+///
+/// The fee for the declared weight of a call is withdrawn before it is dispatched, and the part
+/// of it paid for unused weight is refunded after.
+pub trait ChargeTransaction {
+    /// Who pays the fees.
+    type AccountId;
+
+    /// Withdraw the fee for `weight` from `who`. The extrinsic is invalid if this fails.
+    fn withdraw_fee(&mut self, who: &Self::AccountId, weight: Weight) -> DispatchResult;
+
+    /// Refund the part of the fee withdrawn for `declared` weight which was not used, given that
+    /// the call used `actual` weight.
+    fn refund_fee(&mut self, who: &Self::AccountId, declared: Weight, actual: Weight);
 }
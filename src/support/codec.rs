@@ -0,0 +1,490 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A trait for types which can be encoded into a compact and deterministic binary format.
+///
+/// The encoding is the same for any two equal values, which makes it suitable for hashing.
+/// - Integers are encoded as fixed width little-endian bytes.
+/// - Collections and strings are prefixed with their length, as a `Compact` integer.
+/// - Options are prefixed with `0` for `None`, or `1` for `Some`.
+/// - Structs and tuples are the concatenation of their fields.
+pub trait Encode {
+    /// Append the encoding of `self` to `dest`.
+    fn encode_to(&self, dest: &mut Vec<u8>);
+
+    /// Encode `self` into a new vector of bytes.
+    fn encode(&self) -> Vec<u8> {
+        let mut dest = Vec::new();
+        self.encode_to(&mut dest);
+        dest
+    }
+}
+
+/// A trait for types which can be decoded from the binary format of `Encode`.
+///
+/// Decoding reads from the front of `input`, and advances it past the bytes which were used, so
+/// values can be decoded one after the other from the same buffer.
+///
+/// Types can nest values of their own type, like a call dispatching other calls, so the nesting
+/// depth of decoded values is limited: otherwise a long enough input could overflow the stack. Each
+/// type which contains other values implements `decode_with_depth_limit`, and passes the limit on
+/// to them.
+pub trait Decode: Sized {
+    /// Decode a value from the start of `input`.
+    fn decode(input: &mut &[u8]) -> Result<Self, Error>;
+
+    /// Decode a value from the start of `input`, with at most `depth_limit` levels of nested
+    /// structs and enums. Values which don't contain other values ignore the limit.
+    fn decode_with_depth_limit(depth_limit: u32, input: &mut &[u8]) -> Result<Self, Error> {
+        let _ = depth_limit;
+        Self::decode(input)
+    }
+
+    /// Decode a value from `input`, which must not contain any extra bytes.
+    fn decode_all(mut input: &[u8]) -> Result<Self, Error> {
+        let value = Self::decode(&mut input)?;
+        if !input.is_empty() {
+            return Err(Error("Input has extra bytes"))
+        }
+        Ok(value)
+    }
+}
+
+/// The nesting depth of structs and enums accepted by `Decode::decode`.
+pub const MAX_DEPTH: u32 = 256;
+
+/// Decrease the depth limit when entering a struct or an enum, or fail if it is reached.
+pub fn enter_nested(depth_limit: u32) -> Result<u32, Error> {
+    depth_limit.checked_sub(1).ok_or(Error("Maximum depth exceeded"))
+}
+
+/// The reason why some bytes could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error(pub &'static str);
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Could not decode: {}", self.0)
+    }
+}
+
+/// Take the next `len` bytes from `input`.
+pub fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if input.len() < len {
+        return Err(Error("Not enough bytes"))
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+/// Take the next byte from `input`.
+pub fn read_byte(input: &mut &[u8]) -> Result<u8, Error> {
+    Ok(read_bytes(input, 1)?[0])
+}
+
+/// A wrapper to encode integers with a variable length, so small numbers only use a few bytes.
+///
+/// The two lowest bits of the first byte tell how the number is encoded:
+/// - `0b00`: single byte mode, for values below `2^6`.
+/// - `0b01`: two bytes mode, for values below `2^14`.
+/// - `0b10`: four bytes mode, for values below `2^30`.
+/// - `0b11`: big integer mode, the upper six bits hold the number of bytes following, minus four.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compact(pub u128);
+
+impl Encode for Compact {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        let value = self.0;
+        if value < 1 << 6 {
+            dest.push((value as u8) << 2);
+        } else if value < 1 << 14 {
+            dest.extend_from_slice(&(((value as u16) << 2) | 0b01).to_le_bytes());
+        } else if value < 1 << 30 {
+            dest.extend_from_slice(&(((value as u32) << 2) | 0b10).to_le_bytes());
+        } else {
+            let bytes = value.to_le_bytes();
+            // We need at least four bytes, since smaller values use the other modes.
+            let len = bytes.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1).max(4);
+            dest.push((((len - 4) as u8) << 2) | 0b11);
+            dest.extend_from_slice(&bytes[..len]);
+        }
+    }
+}
+
+impl Decode for Compact {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        let first = read_byte(input)?;
+        // Each mode must only be used for the values it is meant for, so there is a single valid
+        // encoding for each number.
+        let (value, min) = match first & 0b11 {
+            0b00 => return Ok(Compact((first >> 2) as u128)),
+            0b01 => {
+                let value = u16::from_le_bytes([first, read_byte(input)?]) >> 2;
+                (value as u128, 1 << 6)
+            },
+            0b10 => {
+                let mut bytes = [first, 0, 0, 0];
+                bytes[1..].copy_from_slice(read_bytes(input, 3)?);
+                ((u32::from_le_bytes(bytes) >> 2) as u128, 1 << 14)
+            },
+            _ => {
+                let len = (first >> 2) as usize + 4;
+                if len > 16 {
+                    return Err(Error("Compact integer is too large"))
+                }
+                let mut bytes = [0u8; 16];
+                bytes[..len].copy_from_slice(read_bytes(input, len)?);
+                // The last byte can't be zero, otherwise a shorter encoding exists.
+                if len > 4 && bytes[len - 1] == 0 {
+                    return Err(Error("Compact integer is not minimal"))
+                }
+                (u128::from_le_bytes(bytes), 1 << 30)
+            },
+        };
+        if value < min {
+            return Err(Error("Compact integer is not minimal"))
+        }
+        Ok(Compact(value))
+    }
+}
+
+// Read a length prefix, and check that it is not larger than the remaining input. Every item takes
+// at least one byte, so this avoids allocating huge vectors for malformed input.
+fn decode_len(input: &mut &[u8]) -> Result<usize, Error> {
+    let len = Compact::decode(input)?.0;
+    if len > input.len() as u128 {
+        return Err(Error("Length is larger than the input"))
+    }
+    Ok(len as usize)
+}
+
+macro_rules! impl_codec_for_integers {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl Encode for $t {
+                fn encode_to(&self, dest: &mut Vec<u8>) {
+                    dest.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $t {
+                fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+                    let bytes = read_bytes(input, core::mem::size_of::<$t>())?;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().expect("length is checked; qed")))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_integers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Encode for bool {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        dest.push(*self as u8);
+    }
+}
+
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        match read_byte(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error("Invalid boolean")),
+        }
+    }
+}
+
+impl Encode for () {
+    fn encode_to(&self, _dest: &mut Vec<u8>) {}
+}
+
+impl Decode for () {
+    fn decode(_input: &mut &[u8]) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl Encode for str {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        Compact(self.len() as u128).encode_to(dest);
+        dest.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        self.as_str().encode_to(dest);
+    }
+}
+
+impl Decode for String {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        let len = decode_len(input)?;
+        let bytes = read_bytes(input, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error("Invalid utf8 string"))
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        Compact(self.len() as u128).encode_to(dest);
+        for item in self {
+            item.encode_to(dest);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        self.as_slice().encode_to(dest);
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        Self::decode_with_depth_limit(MAX_DEPTH, input)
+    }
+
+    fn decode_with_depth_limit(depth_limit: u32, input: &mut &[u8]) -> Result<Self, Error> {
+        let len = decode_len(input)?;
+        (0..len).map(|_| T::decode_with_depth_limit(depth_limit, input)).collect()
+    }
+}
+
+// Fixed size arrays have a known length, so we don't need to prefix it.
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        for item in self {
+            item.encode_to(dest);
+        }
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        Self::decode_with_depth_limit(MAX_DEPTH, input)
+    }
+
+    fn decode_with_depth_limit(depth_limit: u32, input: &mut &[u8]) -> Result<Self, Error> {
+        let items = (0..N)
+            .map(|_| T::decode_with_depth_limit(depth_limit, input))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items.try_into().unwrap_or_else(|_| unreachable!("exactly `N` items are decoded; qed")))
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        match self {
+            None => dest.push(0),
+            Some(value) => {
+                dest.push(1);
+                value.encode_to(dest);
+            },
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        Self::decode_with_depth_limit(MAX_DEPTH, input)
+    }
+
+    fn decode_with_depth_limit(depth_limit: u32, input: &mut &[u8]) -> Result<Self, Error> {
+        match read_byte(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode_with_depth_limit(depth_limit, input)?)),
+            _ => Err(Error("Invalid option")),
+        }
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        (**self).encode_to(dest);
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        (**self).encode_to(dest);
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        Self::decode_with_depth_limit(MAX_DEPTH, input)
+    }
+
+    fn decode_with_depth_limit(depth_limit: u32, input: &mut &[u8]) -> Result<Self, Error> {
+        Ok(Box::new(T::decode_with_depth_limit(depth_limit, input)?))
+    }
+}
+
+// A `BTreeMap` is always sorted by key, so its encoding is deterministic.
+impl<K: Encode, V: Encode> Encode for BTreeMap<K, V> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        Compact(self.len() as u128).encode_to(dest);
+        for (key, value) in self {
+            key.encode_to(dest);
+            value.encode_to(dest);
+        }
+    }
+}
+
+// Keys must be strictly increasing, so each map has a single valid encoding.
+impl<K: Decode + Ord, V: Decode> Decode for BTreeMap<K, V> {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        Self::decode_with_depth_limit(MAX_DEPTH, input)
+    }
+
+    fn decode_with_depth_limit(depth_limit: u32, input: &mut &[u8]) -> Result<Self, Error> {
+        let len = decode_len(input)?;
+        let mut map = BTreeMap::new();
+        for _ in 0..len {
+            let key = K::decode_with_depth_limit(depth_limit, input)?;
+            if map.last_key_value().is_some_and(|(last, _)| *last >= key) {
+                return Err(Error("Map keys are not sorted"))
+            }
+            map.insert(key, V::decode_with_depth_limit(depth_limit, input)?);
+        }
+        Ok(map)
+    }
+}
+
+// A `BTreeSet` is always sorted, so it is encoded like a `Vec` of its items.
+impl<T: Encode> Encode for BTreeSet<T> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        Compact(self.len() as u128).encode_to(dest);
+        for item in self {
+            item.encode_to(dest);
+        }
+    }
+}
+
+// Items must be strictly increasing, so each set has a single valid encoding.
+impl<T: Decode + Ord> Decode for BTreeSet<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        Self::decode_with_depth_limit(MAX_DEPTH, input)
+    }
+
+    fn decode_with_depth_limit(depth_limit: u32, input: &mut &[u8]) -> Result<Self, Error> {
+        let len = decode_len(input)?;
+        let mut set = BTreeSet::new();
+        for _ in 0..len {
+            let item = T::decode_with_depth_limit(depth_limit, input)?;
+            if set.last().is_some_and(|last| *last >= item) {
+                return Err(Error("Set items are not sorted"))
+            }
+            set.insert(item);
+        }
+        Ok(set)
+    }
+}
+
+macro_rules! impl_codec_for_tuples {
+    ( $( ( $( $t:ident ),+ ) ),* ) => {
+        $(
+            #[allow(non_snake_case)]
+            impl<$( $t: Encode ),+> Encode for ( $( $t, )+ ) {
+                fn encode_to(&self, dest: &mut Vec<u8>) {
+                    let ( $( $t, )+ ) = self;
+                    $( $t.encode_to(dest); )+
+                }
+            }
+
+            impl<$( $t: Decode ),+> Decode for ( $( $t, )+ ) {
+                fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+                    Self::decode_with_depth_limit(MAX_DEPTH, input)
+                }
+
+                fn decode_with_depth_limit(
+                    depth_limit: u32,
+                    input: &mut &[u8],
+                ) -> Result<Self, Error> {
+                    Ok(( $( $t::decode_with_depth_limit(depth_limit, input)?, )+ ))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_tuples!((A), (A, B), (A, B, C), (A, B, C, D));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_encoding() {
+        assert_eq!(Compact(0).encode(), vec![0x00]);
+        assert_eq!(Compact(1).encode(), vec![0x04]);
+        assert_eq!(Compact(63).encode(), vec![0xfc]);
+        assert_eq!(Compact(64).encode(), vec![0x01, 0x01]);
+        assert_eq!(Compact(16383).encode(), vec![0xfd, 0xff]);
+        assert_eq!(Compact(16384).encode(), vec![0x02, 0x00, 0x01, 0x00]);
+        assert_eq!(Compact(1 << 30).encode(), vec![0x03, 0x00, 0x00, 0x00, 0x40]);
+        assert_eq!(Compact(1 << 32).encode(), vec![0x07, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn encoding_works() {
+        assert_eq!(42u32.encode(), vec![42, 0, 0, 0]);
+        assert_eq!("abc".to_string().encode(), vec![12, b'a', b'b', b'c']);
+        assert_eq!(vec![1u8, 2].encode(), vec![8, 1, 2]);
+        assert_eq!(Some(1u8).encode(), vec![1, 1]);
+        assert_eq!(None::<u8>.encode(), vec![0]);
+        assert_eq!((1u8, true).encode(), vec![1, 1]);
+
+        let map = BTreeMap::from([(2u8, 20u8), (1u8, 10u8)]);
+        assert_eq!(map.encode(), vec![8, 1, 10, 2, 20]);
+        assert_eq!(BTreeSet::from([2u8, 1u8]).encode(), vec![8, 1, 2]);
+    }
+
+    fn roundtrip<T: Encode + Decode + PartialEq + core::fmt::Debug>(value: T) {
+        assert_eq!(T::decode_all(&value.encode()), Ok(value));
+    }
+
+    #[test]
+    fn decoding_works() {
+        for value in [0, 1, 63, 64, 16383, 16384, 1 << 30, 1 << 32, u128::MAX] {
+            roundtrip(Compact(value));
+        }
+        roundtrip(42u32);
+        roundtrip(-7i64);
+        roundtrip(true);
+        roundtrip("abc".to_string());
+        roundtrip(vec![1u16, 2, 3]);
+        roundtrip([7u8; 4]);
+        roundtrip(Some(1u8));
+        roundtrip(None::<u8>);
+        roundtrip((1u8, "a".to_string(), false));
+        roundtrip(BTreeMap::from([(2u8, 20u8), (1u8, 10u8)]));
+        roundtrip(BTreeSet::from([2u8, 1u8]));
+
+        // Decoding advances the input past the decoded value.
+        let mut input = &[1, 0, 2, 0][..];
+        assert_eq!(u16::decode(&mut input), Ok(1));
+        assert_eq!(input, &[2, 0]);
+    }
+
+    #[test]
+    fn decoding_rejects_invalid_input() {
+        assert_eq!(u32::decode_all(&[1, 0, 0]), Err(Error("Not enough bytes")));
+        assert_eq!(u8::decode_all(&[1, 0]), Err(Error("Input has extra bytes")));
+        assert_eq!(bool::decode_all(&[2]), Err(Error("Invalid boolean")));
+        assert_eq!(Option::<u8>::decode_all(&[2, 0]), Err(Error("Invalid option")));
+        assert_eq!(String::decode_all(&[4, 0xff]), Err(Error("Invalid utf8 string")));
+        assert_eq!(Vec::<u8>::decode_all(&[0xfc]), Err(Error("Length is larger than the input")));
+        // The same number can't be encoded in two ways.
+        let not_minimal = Err(Error("Compact integer is not minimal"));
+        assert_eq!(Compact::decode_all(&[0x01, 0x00]), not_minimal);
+        assert_eq!(Compact::decode_all(&[0x07, 0x00, 0x00, 0x00, 0x00, 0x00]), not_minimal);
+        assert_eq!(
+            BTreeMap::<u8, u8>::decode_all(&[8, 2, 20, 1, 10]),
+            Err(Error("Map keys are not sorted"))
+        );
+        assert_eq!(BTreeSet::<u8>::decode_all(&[8, 1, 1]), Err(Error("Set items are not sorted")));
+    }
+}
@@ -0,0 +1,110 @@
+use super::{BlakeTwo256, Hasher};
+use ed25519_dalek::{Signer, Verifier};
+
+/// A trait for signatures which can be verified against the account of the signer.
+pub trait Verify {
+    /// The account which signed the message.
+    type Signer;
+
+    /// Check that `self` is a valid signature of `message` by `signer`.
+    fn verify(&self, message: &[u8], signer: &Self::Signer) -> bool;
+}
+
+/// An ed25519 public key, which we use to identify accounts.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, macros::Encode, macros::Decode)]
+pub struct Public(pub [u8; 32]);
+
+/// An ed25519 signature.
+#[derive(Clone, Copy, PartialEq, Eq, macros::Encode, macros::Decode)]
+pub struct Signature(pub [u8; 64]);
+
+/// An ed25519 key pair, which can sign messages.
+#[derive(Clone)]
+pub struct Pair(ed25519_dalek::SigningKey);
+
+impl Pair {
+    /// Create a key pair from a 32 bytes secret seed.
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        Pair(ed25519_dalek::SigningKey::from_bytes(seed))
+    }
+
+    /// Create a key pair deterministically from a phrase, by hashing it into a seed.
+    ///
+    /// This is only meant for testing: anyone who knows the phrase knows the secret key.
+    pub fn from_phrase(phrase: &str) -> Self {
+        Self::from_seed(&BlakeTwo256::hash(phrase.as_bytes()).0)
+    }
+
+    /// The public key of this pair.
+    pub fn public(&self) -> Public {
+        Public(self.0.verifying_key().to_bytes())
+    }
+
+    /// Sign a message.
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        Signature(self.0.sign(message).to_bytes())
+    }
+}
+
+impl Verify for Signature {
+    type Signer = Public;
+
+    fn verify(&self, message: &[u8], signer: &Public) -> bool {
+        // Not every 32 bytes are a valid public key, and those can't have signed anything.
+        let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(&signer.0) else { return false };
+        key.verify(message, &ed25519_dalek::Signature::from_bytes(&self.0)).is_ok()
+    }
+}
+
+// Keys and signatures are much easier to read in hex.
+fn fmt_hex(bytes: &[u8], f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    write!(f, "0x")?;
+    for byte in bytes {
+        write!(f, "{byte:02x}")?;
+    }
+    Ok(())
+}
+
+impl core::fmt::Display for Public {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt_hex(&self.0, f)
+    }
+}
+
+impl core::fmt::Debug for Public {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt_hex(&self.0, f)
+    }
+}
+
+impl core::fmt::Debug for Signature {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fmt_hex(&self.0, f)
+    }
+}
+
+// Never print the secret key.
+impl core::fmt::Debug for Pair {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Pair({})", self.public())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let pair = Pair::from_phrase("alice");
+        let other = Pair::from_phrase("bob");
+        assert_eq!(pair.public(), Pair::from_phrase("alice").public());
+        assert_ne!(pair.public(), other.public());
+
+        let signature = pair.sign(b"hello");
+        assert!(signature.verify(b"hello", &pair.public()));
+        assert!(!signature.verify(b"hello!", &pair.public()));
+        assert!(!signature.verify(b"hello", &other.public()));
+        assert!(!Signature([0; 64]).verify(b"hello", &pair.public()));
+    }
+}
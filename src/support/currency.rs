@@ -0,0 +1,94 @@
+use super::{Decode, DispatchResult, Encode};
+use core::fmt::Debug;
+use num::traits::{CheckedAdd, CheckedSub, Zero};
+
+/// Whether an account may be reaped when balance is withdrawn from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExistenceRequirement {
+    /// The withdrawal fails rather than reap the account.
+    KeepAlive,
+    /// The account is reaped if it falls below the minimum balance.
+    AllowDeath,
+}
+
+/// Where reserved balance is moved to by `ReservableCurrency::repatriate_reserved`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceStatus {
+    /// The free balance of the beneficiary.
+    Free,
+    /// The reserved balance of the beneficiary.
+    Reserved,
+}
+
+/// A currency held by accounts, which other pallets can move around without knowing which pallet
+/// keeps the balances.
+///
+/// A currency is a value, usually the balances pallet, which pallets reach through the runtime, as
+/// only the pallet keeping the balances knows how they are stored.
+pub trait Currency<AccountId> {
+    /// The balance of an account.
+    type Balance: Zero + CheckedSub + CheckedAdd + Ord + Copy + Debug + Encode + Decode;
+
+    /// The minimum balance an account must keep to exist.
+    fn minimum_balance() -> Self::Balance;
+
+    /// The total amount of the currency held by all the accounts.
+    fn total_issuance(&self) -> Self::Balance;
+
+    /// The balance of `who` which can be used, if it is not locked.
+    fn free_balance(&self, who: &AccountId) -> Self::Balance;
+
+    /// Move `amount` from the free balance of `from` to the free balance of `to`.
+    fn transfer(
+        &mut self,
+        from: &AccountId,
+        to: &AccountId,
+        amount: Self::Balance,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult;
+
+    /// Destroy `amount` from the free balance of `who`, and from the total issuance.
+    fn burn_from(
+        &mut self,
+        who: &AccountId,
+        amount: Self::Balance,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult;
+
+    /// Create `amount` in the free balance of `who`, and add it to the total issuance.
+    fn deposit_creating(&mut self, who: &AccountId, amount: Self::Balance) -> DispatchResult;
+}
+
+/// A currency where part of the balance of an account can be set aside, like for a deposit.
+///
+/// Reserved balance can't be used by its owner, but it still belongs to them until it is
+/// unreserved, slashed or moved to another account.
+pub trait ReservableCurrency<AccountId>: Currency<AccountId> {
+    /// The balance of `who` which is reserved.
+    fn reserved_balance(&self, who: &AccountId) -> Self::Balance;
+
+    /// Move `amount` from the free balance of `who` to their reserved balance.
+    fn reserve(&mut self, who: &AccountId, amount: Self::Balance) -> DispatchResult;
+
+    /// Move up to `amount` from the reserved balance of `who` back to their free balance.
+    /// Returns the part of `amount` which was not reserved.
+    fn unreserve(&mut self, who: &AccountId, amount: Self::Balance) -> Self::Balance;
+
+    /// Destroy up to `amount` from the reserved balance of `who`, and from the total issuance.
+    /// Returns the part of `amount` which was not reserved.
+    fn slash_reserved(
+        &mut self,
+        who: &AccountId,
+        amount: Self::Balance,
+    ) -> Result<Self::Balance, super::DispatchError>;
+
+    /// Move up to `amount` from the reserved balance of `slashed` to the free or reserved balance
+    /// of `beneficiary`. Returns the part of `amount` which was not reserved.
+    fn repatriate_reserved(
+        &mut self,
+        slashed: &AccountId,
+        beneficiary: &AccountId,
+        amount: Self::Balance,
+        status: BalanceStatus,
+    ) -> Result<Self::Balance, super::DispatchError>;
+}
@@ -0,0 +1,295 @@
+use super::Encode;
+
+/// A trait for hashing functions, which turn any amount of bytes into a fixed size output.
+pub trait Hasher {
+    /// The output of the hashing function.
+    type Output;
+
+    /// Hash some bytes.
+    fn hash(data: &[u8]) -> Self::Output;
+
+    /// Hash the encoding of some value.
+    fn hash_of<E: Encode + ?Sized>(value: &E) -> Self::Output {
+        Self::hash(&value.encode())
+    }
+}
+
+/// A 256-bit hash.
+#[derive(
+    Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, macros::Encode, macros::Decode,
+)]
+pub struct H256(pub [u8; 32]);
+
+impl AsRef<[u8]> for H256 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Hashes are much easier to read in hex.
+impl core::fmt::Display for H256 {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "0x")?;
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for H256 {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+/// The BLAKE2b hashing function, with a 256-bit output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlakeTwo256;
+
+impl Hasher for BlakeTwo256 {
+    type Output = H256;
+
+    fn hash(data: &[u8]) -> H256 {
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&blake2b(32, data));
+        H256(out)
+    }
+}
+
+/// The SHA-256 hashing function, which is what most tools use to fingerprint files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sha256;
+
+impl Hasher for Sha256 {
+    type Output = H256;
+
+    fn hash(data: &[u8]) -> H256 {
+        H256(sha256(data))
+    }
+}
+
+// The initialization vector of BLAKE2b, the same as the one of SHA-512.
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+// The message word permutations used in each round of BLAKE2b.
+const BLAKE2B_SIGMA: [[usize; 16]; 12] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+];
+
+/// Hash `data` with BLAKE2b (RFC 7693), without a key, into `out_len` bytes.
+///
+/// `out_len` must be between 1 and 64.
+pub fn blake2b(out_len: usize, data: &[u8]) -> Vec<u8> {
+    assert!((1..=64).contains(&out_len), "invalid BLAKE2b output length");
+
+    let mut h = BLAKE2B_IV;
+    // Parameter block: no key, fanout and depth of 1, and the output length.
+    h[0] ^= 0x01010000 ^ out_len as u64;
+
+    // All blocks but the last one are compressed as they come. The last block is padded with
+    // zeros, and is always compressed, even if the data is empty.
+    let mut offset = 0;
+    while data.len() - offset > 128 {
+        let block: &[u8; 128] = data[offset..offset + 128].try_into().expect("block is 128 bytes");
+        offset += 128;
+        blake2b_compress(&mut h, block, offset as u128, false);
+    }
+    let mut last = [0u8; 128];
+    last[..data.len() - offset].copy_from_slice(&data[offset..]);
+    blake2b_compress(&mut h, &last, data.len() as u128, true);
+
+    h.iter().flat_map(|word| word.to_le_bytes()).take(out_len).collect()
+}
+
+// The compression function of BLAKE2b, mixing a block of 128 bytes into the state `h`.
+// `counter` is the total number of bytes hashed so far, including this block.
+fn blake2b_compress(h: &mut [u64; 8], block: &[u8; 128], counter: u128, last: bool) {
+    let mut m = [0u64; 16];
+    for (i, word) in m.iter_mut().enumerate() {
+        *word = u64::from_le_bytes(block[i * 8..i * 8 + 8].try_into().expect("word is 8 bytes"));
+    }
+
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= counter as u64;
+    v[13] ^= (counter >> 64) as u64;
+    if last {
+        v[14] = !v[14];
+    }
+
+    for s in BLAKE2B_SIGMA {
+        blake2b_mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        blake2b_mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        blake2b_mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        blake2b_mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        blake2b_mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        blake2b_mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        blake2b_mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        blake2b_mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+// The mixing function `G` of BLAKE2b.
+fn blake2b_mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+// The initial hash value of SHA-256: the first 32 bits of the fractional parts of the square
+// roots of the first 8 primes.
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// The round constants of SHA-256: the first 32 bits of the fractional parts of the cube roots of
+// the first 64 primes.
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Hash `data` with SHA-256 (FIPS 180-4).
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    // The data is padded with a single `1` bit, zeros, and its length in bits, to a multiple of
+    // 64 bytes.
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    padded.resize((padded.len() + 8).next_multiple_of(64) - 8, 0);
+    padded.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    let mut h = SHA256_H;
+    for block in padded.chunks_exact(64) {
+        sha256_compress(&mut h, block.try_into().expect("block is 64 bytes"));
+    }
+
+    let mut out = [0u8; 32];
+    for (chunk, word) in out.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+// The compression function of SHA-256, mixing a block of 64 bytes into the state `h`.
+fn sha256_compress(h: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for i in 0..16 {
+        w[i] = u32::from_be_bytes(block[i * 4..i * 4 + 4].try_into().expect("word is 4 bytes"));
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn blake2b_test_vectors() {
+        assert_eq!(
+            hex(&blake2b(64, b"abc")),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+        assert_eq!(
+            BlakeTwo256::hash(b"").to_string(),
+            "0x0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+        );
+        // Data filling exactly one block, and data spanning several blocks.
+        assert_eq!(
+            hex(&blake2b(32, &[0xab; 128])),
+            "e28dbbbacc7cafa062f8c043bf25ec6043bfa25fb32ab91881e09c0a300290d2"
+        );
+        assert_eq!(
+            hex(&blake2b(32, &[0xab; 300])),
+            "2a5e8e68fa2411e915a353ab9e3f23aea5fa4db80aff0134f82eea790745e7c5"
+        );
+    }
+
+    #[test]
+    fn sha256_test_vectors() {
+        assert_eq!(
+            Sha256::hash(b"").to_string(),
+            "0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // The padding of this message doesn't fit in its last block.
+        assert_eq!(
+            hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex(&sha256(&[b'a'; 1000])),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+}
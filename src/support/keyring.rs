@@ -0,0 +1,39 @@
+use super::crypto::{Pair, Public};
+
+/// Well known test accounts, with deterministic keys.
+///
+/// Their secret keys are public knowledge, so they must only be used for testing and demos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyring {
+    Alice,
+    Bob,
+    Charlie,
+}
+
+impl Keyring {
+    /// The lowercase name of the account, which is also the phrase its keys are derived from.
+    pub fn name(self) -> &'static str {
+        match self {
+            Keyring::Alice => "alice",
+            Keyring::Bob => "bob",
+            Keyring::Charlie => "charlie",
+        }
+    }
+
+    /// Find a test account by its name, ignoring the case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Keyring::Alice, Keyring::Bob, Keyring::Charlie]
+            .into_iter()
+            .find(|keyring| keyring.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The key pair of the account.
+    pub fn pair(self) -> Pair {
+        Pair::from_phrase(self.name())
+    }
+
+    /// The public key of the account, which is also its account id.
+    pub fn public(self) -> Public {
+        self.pair().public()
+    }
+}
@@ -0,0 +1,567 @@
+mod backend;
+mod trie;
+
+use super::hashing::blake2b;
+use super::{Decode, Encode, H256, Transactional, TransactionalMap};
+use core::marker::PhantomData;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+pub use backend::{Backend, Changes, FileBackend, InMemoryBackend, RecordLog};
+pub use trie::{ProofError, StorageProof, verify_proof};
+use trie::Trie;
+
+/// The key-value store holding the state of all the pallets of a runtime.
+///
+/// Every pallet keeps a handle to the same store, which its storage items read and write through.
+/// Cloning a `Storage` gives another handle to the same store. Changes can be made in nested
+/// transactions, like with a `TransactionalMap`.
+///
+/// The changes are kept in memory, on top of the state of a `Backend`, until they are flushed to
+/// it, usually once a whole block is executed. The storage also keeps a `Trie` of its current
+/// state, which the state root is the root of.
+#[derive(Clone)]
+pub struct Storage(Rc<RefCell<StorageInner>>);
+
+struct StorageInner {
+    backend: Box<dyn Backend>,
+    // The changes which were not flushed to the backend yet. A `None` value means that the key
+    // was removed.
+    changes: TransactionalMap<Vec<u8>, Option<Vec<u8>>>,
+    // The trie of the current state, including the changes of the open transactions.
+    trie: Trie,
+    // The keys changed in each open transaction, which the trie is updated for when it is
+    // reverted.
+    touched: Vec<BTreeSet<Vec<u8>>>,
+}
+
+impl StorageInner {
+    // Note that `key` changed, in the current transaction if any.
+    fn touch(&mut self, key: &[u8]) {
+        if let Some(touched) = self.touched.last_mut() {
+            touched.insert(key.to_vec());
+        }
+    }
+}
+
+impl Storage {
+    /// Create a new empty store, kept in memory.
+    pub fn new() -> Self {
+        Self::with_backend(InMemoryBackend::default())
+    }
+
+    /// Create a store on top of the state of `backend`.
+    pub fn with_backend(backend: impl Backend + 'static) -> Self {
+        let trie = Trie::from_entries(backend.iter_prefix(&[]));
+        let inner = StorageInner {
+            backend: Box::new(backend),
+            changes: TransactionalMap::new(),
+            trie,
+            touched: Vec::new(),
+        };
+        Self(Rc::new(RefCell::new(inner)))
+    }
+
+    /// Get the value stored at `key`, if any.
+    pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let inner = self.0.borrow();
+        match inner.changes.get(key) {
+            Some(value) => value.clone(),
+            None => inner.backend.get(key),
+        }
+    }
+
+    /// Check if a value is stored at `key`.
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Store `value` at `key`.
+    pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        let mut inner = self.0.borrow_mut();
+        inner.touch(&key);
+        inner.trie.insert(&key, value.clone());
+        inner.changes.insert(key, Some(value));
+    }
+
+    /// Remove the value stored at `key`, if any.
+    pub fn remove(&mut self, key: &[u8]) {
+        let mut inner = self.0.borrow_mut();
+        inner.touch(key);
+        inner.trie.remove(key);
+        inner.changes.insert(key.to_vec(), None);
+    }
+
+    /// Get all the keys starting with `prefix`, and their values, in order.
+    pub fn iter_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let inner = self.0.borrow();
+        let mut entries = inner.backend.iter_prefix(prefix).into_iter().collect::<BTreeMap<_, _>>();
+        for (key, value) in inner.changes.iter().filter(|(key, _)| key.starts_with(prefix)) {
+            match value {
+                Some(value) => entries.insert(key.clone(), value.clone()),
+                None => entries.remove(key),
+            };
+        }
+        entries.into_iter().collect()
+    }
+
+    /// Remove all the keys starting with `prefix`.
+    pub fn remove_prefix(&mut self, prefix: &[u8]) {
+        for (key, _) in self.iter_prefix(prefix) {
+            self.remove(&key);
+        }
+    }
+
+    /// The root of the trie of the current state, including the changes of the open transactions.
+    pub fn root(&self) -> H256 {
+        self.0.borrow_mut().trie.root()
+    }
+
+    /// Prove the value stored at `key` in the current state, or that there is none, to anyone
+    /// holding its root.
+    pub fn prove(&self, key: &[u8]) -> StorageProof {
+        self.0.borrow_mut().trie.prove(key)
+    }
+
+    /// Write the changes made so far to the backend, in a single batch.
+    ///
+    /// This must not be called while a transaction is open, as its changes would be written even
+    /// if it is reverted later.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        let mut inner = self.0.borrow_mut();
+        let changes = inner.changes.iter().map(|(key, value)| (key.clone(), value.clone()));
+        let changes = changes.collect::<Changes>();
+        inner.backend.commit(changes)?;
+        inner.changes = TransactionalMap::new();
+        Ok(())
+    }
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transactional for Storage {
+    fn start_transaction(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.changes.start_transaction();
+        inner.touched.push(BTreeSet::new());
+    }
+
+    fn commit_transaction(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.changes.commit_transaction();
+        let touched = inner.touched.pop().unwrap_or_default();
+        if let Some(parent) = inner.touched.last_mut() {
+            parent.extend(touched);
+        }
+    }
+
+    // The trie is brought back to the values the changed keys have once the changes are reverted.
+    fn rollback_transaction(&mut self) {
+        let touched = {
+            let mut inner = self.0.borrow_mut();
+            inner.changes.rollback_transaction();
+            inner.touched.pop().unwrap_or_default()
+        };
+        for key in touched {
+            let value = self.get(&key);
+            let mut inner = self.0.borrow_mut();
+            match value {
+                Some(value) => inner.trie.insert(&key, value),
+                None => inner.trie.remove(&key),
+            }
+        }
+    }
+}
+
+impl core::fmt::Debug for Storage {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_map().entries(self.iter_prefix(&[])).finish()
+    }
+}
+
+/// The names a storage item is stored under. The `#[macros::storage]` attribute implements it for
+/// every storage item it declares.
+pub trait StoragePrefix {
+    /// The path of the module declaring the item. Its last segment is the name of the pallet.
+    const PALLET: &'static str;
+    /// The name of the item.
+    const ITEM: &'static str;
+
+    /// The prefix of all the keys of the item: the hash of the name of the pallet, followed by the
+    /// hash of the name of the item.
+    fn prefix() -> Vec<u8> {
+        let pallet = Self::PALLET.rsplit("::").next().unwrap_or(Self::PALLET);
+        [hash_128(pallet.as_bytes()), hash_128(Self::ITEM.as_bytes())].concat()
+    }
+}
+
+// A short hash, which is enough to keep apart the names of the pallets and of their items.
+fn hash_128(data: &[u8]) -> Vec<u8> {
+    blake2b(16, data)
+}
+
+// Append the key of a map to `dest`: the hash of its encoding, followed by the encoding itself.
+// Hashing spreads the keys evenly, and keeping the encoding lets us decode the keys back when
+// iterating over the map.
+fn hash_128_concat(key: &impl Encode, dest: &mut Vec<u8>) {
+    let encoded = key.encode();
+    dest.extend(hash_128(&encoded));
+    dest.extend(encoded);
+}
+
+// Decode a key appended by `hash_128_concat` from the start of `input`.
+fn decode_hash_128_concat<K: Decode>(input: &mut &[u8]) -> Option<K> {
+    *input = input.get(16..)?;
+    K::decode(input).ok()
+}
+
+// A value which can't be decoded is treated as missing. Only its own item writes under its key,
+// so this never happens unless the storage is corrupted.
+fn decode_value<V: Decode>(value: Vec<u8>) -> Option<V> {
+    V::decode_all(&value).ok()
+}
+
+// Debug print the names a storage item of the given kind is stored under.
+fn debug_item<Prefix: StoragePrefix>(
+    kind: &str,
+    f: &mut core::fmt::Formatter,
+) -> core::fmt::Result {
+    f.debug_struct(kind).field("pallet", &Prefix::PALLET).field("item", &Prefix::ITEM).finish()
+}
+
+/// A single value in storage, like the total issuance of a currency.
+pub struct StorageValue<Prefix, V> {
+    storage: Storage,
+    _marker: PhantomData<(Prefix, V)>,
+}
+
+impl<Prefix: StoragePrefix, V: Encode + Decode> StorageValue<Prefix, V> {
+    /// Create a handle to the value, in `storage`.
+    pub fn new(storage: &Storage) -> Self {
+        Self { storage: storage.clone(), _marker: PhantomData }
+    }
+
+    /// Get the value, if it is set.
+    pub fn get(&self) -> Option<V> {
+        self.storage.get(&Prefix::prefix()).and_then(decode_value)
+    }
+
+    /// Set the value.
+    pub fn put(&mut self, value: V) {
+        self.storage.insert(Prefix::prefix(), value.encode());
+    }
+
+    /// Remove the value, and return it if it was set.
+    pub fn take(&mut self) -> Option<V> {
+        let value = self.get();
+        self.storage.remove(&Prefix::prefix());
+        value
+    }
+}
+
+// A storage item is only a handle to the storage, which holds the values, so we debug print the
+// names it is stored under.
+impl<Prefix: StoragePrefix, V> core::fmt::Debug for StorageValue<Prefix, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        debug_item::<Prefix>("StorageValue", f)
+    }
+}
+
+/// A map in storage, from keys of type `K` to values of type `V`.
+///
+/// Each value is stored under its own key in the storage, so reading or writing one value does not
+/// touch the others.
+pub struct StorageMap<Prefix, K, V> {
+    storage: Storage,
+    _marker: PhantomData<(Prefix, K, V)>,
+}
+
+impl<Prefix, K, V> StorageMap<Prefix, K, V>
+where
+    Prefix: StoragePrefix,
+    K: Encode + Decode + Ord,
+    V: Encode + Decode,
+{
+    /// Create a handle to the map, in `storage`.
+    pub fn new(storage: &Storage) -> Self {
+        Self { storage: storage.clone(), _marker: PhantomData }
+    }
+
+    // The key in the storage of the value stored at `key`.
+    fn storage_key(key: &K) -> Vec<u8> {
+        let mut storage_key = Prefix::prefix();
+        hash_128_concat(key, &mut storage_key);
+        storage_key
+    }
+
+    /// Get the value stored at `key`, if any.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.storage.get(&Self::storage_key(key)).and_then(decode_value)
+    }
+
+    /// Check if a value is stored at `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.storage.contains_key(&Self::storage_key(key))
+    }
+
+    /// Store `value` at `key`.
+    pub fn insert(&mut self, key: &K, value: V) {
+        self.storage.insert(Self::storage_key(key), value.encode());
+    }
+
+    /// Remove the value stored at `key`, if any.
+    pub fn remove(&mut self, key: &K) {
+        self.storage.remove(&Self::storage_key(key));
+    }
+
+    /// Remove the value stored at `key`, and return it if any.
+    pub fn take(&mut self, key: &K) -> Option<V> {
+        let value = self.get(key);
+        self.remove(key);
+        value
+    }
+
+    /// Prove the value stored at `key`, or that there is none, to anyone holding the state root.
+    pub fn prove(&self, key: &K) -> StorageProof {
+        self.storage.prove(&Self::storage_key(key))
+    }
+
+    /// Check a proof made by `prove` against `state_root`, and return the value it proves is
+    /// stored at `key`, if any.
+    pub fn verify(state_root: &H256, key: &K, proof: &[Vec<u8>]) -> Result<Option<V>, ProofError> {
+        let value = verify_proof(state_root, &Self::storage_key(key), proof)?;
+        Ok(value.and_then(decode_value))
+    }
+
+    /// Iterate over all the keys and values of the map, in the order of the keys.
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + use<Prefix, K, V> {
+        let prefix = Prefix::prefix();
+        let entries = self.storage.iter_prefix(&prefix).into_iter().filter_map(|(key, value)| {
+            let key = decode_hash_128_concat(&mut &key[prefix.len()..])?;
+            Some((key, decode_value(value)?))
+        });
+        // The keys are stored in the order of their hashes, so we sort them back.
+        entries.collect::<BTreeMap<_, _>>().into_iter()
+    }
+}
+
+impl<Prefix: StoragePrefix, K, V> core::fmt::Debug for StorageMap<Prefix, K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        debug_item::<Prefix>("StorageMap", f)
+    }
+}
+
+/// A map in storage with two keys, from `K1` and `K2` to values of type `V`.
+///
+/// The values sharing the same first key can be iterated over, or removed, together. This is how
+/// we keep a set of items per account, for example.
+pub struct StorageDoubleMap<Prefix, K1, K2, V> {
+    storage: Storage,
+    _marker: PhantomData<(Prefix, K1, K2, V)>,
+}
+
+impl<Prefix, K1, K2, V> StorageDoubleMap<Prefix, K1, K2, V>
+where
+    Prefix: StoragePrefix,
+    K1: Encode + Decode + Ord,
+    K2: Encode + Decode + Ord,
+    V: Encode + Decode,
+{
+    /// Create a handle to the map, in `storage`.
+    pub fn new(storage: &Storage) -> Self {
+        Self { storage: storage.clone(), _marker: PhantomData }
+    }
+
+    // The prefix of the keys in the storage of all the values stored under `k1`.
+    fn storage_prefix(k1: &K1) -> Vec<u8> {
+        let mut prefix = Prefix::prefix();
+        hash_128_concat(k1, &mut prefix);
+        prefix
+    }
+
+    // The key in the storage of the value stored at `(k1, k2)`.
+    fn storage_key(k1: &K1, k2: &K2) -> Vec<u8> {
+        let mut storage_key = Self::storage_prefix(k1);
+        hash_128_concat(k2, &mut storage_key);
+        storage_key
+    }
+
+    /// Get the value stored at `(k1, k2)`, if any.
+    // Our pallets only read the values of a double map by their first key so far.
+    #[allow(dead_code)]
+    pub fn get(&self, k1: &K1, k2: &K2) -> Option<V> {
+        self.storage.get(&Self::storage_key(k1, k2)).and_then(decode_value)
+    }
+
+    /// Check if a value is stored at `(k1, k2)`.
+    #[allow(dead_code)]
+    pub fn contains_key(&self, k1: &K1, k2: &K2) -> bool {
+        self.storage.contains_key(&Self::storage_key(k1, k2))
+    }
+
+    /// Store `value` at `(k1, k2)`.
+    pub fn insert(&mut self, k1: &K1, k2: &K2, value: V) {
+        self.storage.insert(Self::storage_key(k1, k2), value.encode());
+    }
+
+    /// Remove the value stored at `(k1, k2)`, if any.
+    pub fn remove(&mut self, k1: &K1, k2: &K2) {
+        self.storage.remove(&Self::storage_key(k1, k2));
+    }
+
+    /// Remove all the values stored under `k1`.
+    pub fn remove_prefix(&mut self, k1: &K1) {
+        self.storage.remove_prefix(&Self::storage_prefix(k1));
+    }
+
+    /// Iterate over the second keys and the values stored under `k1`, in the order of the keys.
+    pub fn iter_prefix(&self, k1: &K1) -> impl Iterator<Item = (K2, V)> + use<Prefix, K1, K2, V> {
+        let prefix = Self::storage_prefix(k1);
+        let entries = self.storage.iter_prefix(&prefix).into_iter().filter_map(|(key, value)| {
+            let k2 = decode_hash_128_concat(&mut &key[prefix.len()..])?;
+            Some((k2, decode_value(value)?))
+        });
+        entries.collect::<BTreeMap<_, _>>().into_iter()
+    }
+}
+
+impl<Prefix: StoragePrefix, K1, K2, V> core::fmt::Debug for StorageDoubleMap<Prefix, K1, K2, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        debug_item::<Prefix>("StorageDoubleMap", f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::support::with_transaction;
+
+    #[macros::storage]
+    type Total = StorageValue<_, u64>;
+
+    #[macros::storage]
+    type Balances = StorageMap<_, String, u64>;
+
+    #[macros::storage]
+    type Locks = StorageDoubleMap<_, String, [u8; 4], u64>;
+
+    #[test]
+    fn items_share_the_storage() {
+        let storage = Storage::new();
+        let (mut total, mut balances) = (Total::new(&storage), Balances::new(&storage));
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+
+        total.put(30);
+        balances.insert(&bob, 20);
+        balances.insert(&alice, 10);
+        assert_eq!(total.get(), Some(30));
+        assert_eq!(balances.get(&alice), Some(10));
+        assert!(!balances.contains_key(&"charlie".to_string()));
+
+        // Another handle sees the same values, and maps iterate in the order of their keys.
+        let balances = Balances::new(&storage);
+        assert_eq!(balances.iter().collect::<Vec<_>>(), vec![(alice, 10), (bob, 20)]);
+
+        // Each key is prefixed by the hashes of the names of the pallet and of the item.
+        let prefix = [hash_128(b"tests"), hash_128(b"Balances")].concat();
+        assert_eq!(BalancesPrefix::prefix(), prefix);
+        assert_eq!(storage.iter_prefix(&prefix).len(), 2);
+        let total_key = TotalPrefix::prefix();
+        assert_eq!(storage.iter_prefix(&total_key), vec![(total_key.clone(), 30u64.encode())]);
+    }
+
+    #[test]
+    fn double_maps_work() {
+        let storage = Storage::new();
+        let mut locks = Locks::new(&storage);
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+
+        locks.insert(&alice, b"vest", 10);
+        locks.insert(&alice, b"stak", 20);
+        locks.insert(&bob, b"vest", 30);
+        assert_eq!(locks.get(&alice, b"vest"), Some(10));
+        assert!(!locks.contains_key(&bob, b"stak"));
+        assert_eq!(
+            locks.iter_prefix(&alice).collect::<Vec<_>>(),
+            vec![(*b"stak", 20), (*b"vest", 10)]
+        );
+
+        locks.remove_prefix(&alice);
+        assert_eq!(locks.iter_prefix(&alice).count(), 0);
+        assert_eq!(locks.iter_prefix(&bob).collect::<Vec<_>>(), vec![(*b"vest", 30)]);
+        locks.remove(&bob, b"vest");
+        assert_eq!(locks.iter_prefix(&bob).count(), 0);
+    }
+
+    #[test]
+    fn items_are_transactional() {
+        let mut storage = Storage::new();
+        let mut total = Total::new(&storage);
+        total.put(1);
+
+        let res: Result<(), ()> = with_transaction(&mut storage, |_| {
+            total.put(2);
+            Err(())
+        });
+        assert_eq!(res, Err(()));
+        assert_eq!(total.get(), Some(1));
+
+        let res: Result<(), ()> = with_transaction(&mut storage, |_| {
+            assert_eq!(total.take(), Some(1));
+            Ok(())
+        });
+        assert_eq!(res, Ok(()));
+        assert_eq!(total.get(), None);
+    }
+
+    #[test]
+    fn root_follows_the_transactions() {
+        let mut storage = Storage::new();
+        let mut balances = Balances::new(&storage);
+        balances.insert(&"alice".to_string(), 10);
+        let root = storage.root();
+
+        storage.start_transaction();
+        balances.insert(&"alice".to_string(), 20);
+        storage.start_transaction();
+        balances.remove(&"alice".to_string());
+        balances.insert(&"bob".to_string(), 30);
+        storage.commit_transaction();
+        assert_ne!(storage.root(), root);
+        storage.rollback_transaction();
+        assert_eq!(storage.root(), root);
+
+        // The root is the same as the root of a trie built from the state.
+        storage.start_transaction();
+        balances.insert(&"bob".to_string(), 30);
+        storage.commit_transaction();
+        assert_eq!(storage.root(), Trie::from_entries(storage.iter_prefix(&[])).root());
+    }
+
+    #[test]
+    fn changes_are_flushed_to_the_backend() {
+        let mut backend = InMemoryBackend::default();
+        let alice = "alice".to_string();
+        backend.commit(vec![(TotalPrefix::prefix(), Some(30u64.encode()))]).unwrap();
+        let mut storage = Storage::with_backend(backend);
+        let (mut total, mut balances) = (Total::new(&storage), Balances::new(&storage));
+
+        // The changes are read on top of the state of the backend.
+        assert_eq!(total.get(), Some(30));
+        balances.insert(&alice, 10);
+        total.take();
+        assert_eq!(storage.iter_prefix(&[]), vec![(Balances::storage_key(&alice), 10u64.encode())]);
+
+        storage.flush().unwrap();
+        assert_eq!(total.get(), None);
+        assert_eq!(balances.get(&alice), Some(10));
+        let inner = storage.0.borrow();
+        assert_eq!(inner.changes.iter().count(), 0);
+        assert_eq!(inner.backend.iter_prefix(&[]).len(), 1);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::support::hashing::blake2b;
+use crate::support::{Decode, Encode};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// A batch of changes to a backend: the new value of each key, or `None` if it was removed.
+pub type Changes = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
+/// Where the committed state of a `Storage` is kept.
+///
+/// A backend only sees the changes once they are committed, in batches. The changes made during a
+/// block are kept by the storage until the whole block is executed.
+pub trait Backend {
+    /// Get the value stored at `key`, if any.
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+    /// Get all the keys starting with `prefix`, and their values, in order.
+    fn iter_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)>;
+    /// Apply a batch of changes. Either all the changes are applied or none are, even if the
+    /// process is killed while they are written.
+    fn commit(&mut self, changes: Changes) -> std::io::Result<()>;
+}
+
+/// A backend keeping the state in memory, which is lost when the process exits.
+#[derive(Debug, Default)]
+pub struct InMemoryBackend {
+    values: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl InMemoryBackend {
+    // Apply the changes to the values.
+    fn apply(&mut self, changes: Changes) {
+        for (key, value) in changes {
+            match value {
+                Some(value) => self.values.insert(key, value),
+                None => self.values.remove(&key),
+            };
+        }
+    }
+}
+
+impl Backend for InMemoryBackend {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.values.get(key).cloned()
+    }
+
+    fn iter_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let entries = self.values.range(prefix.to_vec()..);
+        let entries = entries.take_while(|(key, _)| key.starts_with(prefix));
+        entries.map(|(key, value)| (key.clone(), value.clone())).collect()
+    }
+
+    fn commit(&mut self, changes: Changes) -> std::io::Result<()> {
+        self.apply(changes);
+        Ok(())
+    }
+}
+
+// The length of the checksum closing each record of a log.
+const CHECKSUM_LEN: usize = 16;
+
+/// A backend keeping the state in a `RecordLog` of the batches of changes committed to it, and a
+/// copy of the state in memory.
+#[derive(Debug)]
+pub struct FileBackend {
+    log: RecordLog,
+    state: InMemoryBackend,
+}
+
+impl FileBackend {
+    /// Open the log at `path`, creating it if it does not exist, and read the state back from it.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let (log, records) = RecordLog::open(path)?;
+        let mut state = InMemoryBackend::default();
+        for record in records {
+            // A record with a valid checksum which can't be decoded means the log is corrupted.
+            let changes = Changes::decode_all(&record).map_err(|error| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{error}"))
+            })?;
+            state.apply(changes);
+        }
+        Ok(Self { log, state })
+    }
+}
+
+impl Backend for FileBackend {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.state.get(key)
+    }
+
+    fn iter_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.state.iter_prefix(prefix)
+    }
+
+    fn commit(&mut self, changes: Changes) -> std::io::Result<()> {
+        self.log.append(&changes.encode())?;
+        self.state.apply(changes);
+        Ok(())
+    }
+}
+
+/// An append-only file of records, which is never left with a partly written record.
+///
+/// Each record is written as its length as a little endian `u32`, its bytes, and a checksum of
+/// them. A record is only read back if it is complete and its checksum matches, so a record which
+/// was being written when the process was killed is ignored, and removed from the file when it is
+/// opened again. Only the last record can be partly written: a bad record followed by others means
+/// the file is corrupted, and it is not opened.
+#[derive(Debug)]
+pub struct RecordLog {
+    file: File,
+    // The length of the valid records of the file.
+    len: u64,
+}
+
+impl RecordLog {
+    /// Open the log at `path`, creating it if it does not exist, and read its records back, in
+    /// order.
+    pub fn open(path: &Path) -> std::io::Result<(Self, Vec<Vec<u8>>)> {
+        let exists = path.exists();
+        let bytes = if exists { std::fs::read(path)? } else { Vec::new() };
+        let mut input = &bytes[..];
+        let mut records = Vec::new();
+        while let Some(record) = read_record(&mut input) {
+            records.push(record.to_vec());
+        }
+        let len = (bytes.len() - input.len()) as u64;
+        if is_corrupted(input) {
+            let message = format!("corrupted record at offset {len} of {}", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message))
+        }
+
+        // Drop the partial record at the end of the log, if any, so the next records are
+        // appended right after the valid ones.
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.set_len(len)?;
+        file.sync_all()?;
+        if !exists {
+            sync_parent_dir(path)?;
+        }
+        Ok((Self { file, len }, records))
+    }
+
+    /// Append `record` to the log, and wait until it is on disk.
+    pub fn append(&mut self, record: &[u8]) -> std::io::Result<()> {
+        let mut bytes = (record.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(record);
+        bytes.extend(blake2b(CHECKSUM_LEN, record));
+
+        let written = self.file.write_all(&bytes).and_then(|()| self.file.sync_data());
+        if let Err(error) = written {
+            // Drop what was written of the record, so it does not hide the next ones.
+            let _ = self.file.set_len(self.len);
+            return Err(error)
+        }
+        self.len += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+// Read the next record from the start of `input`, if it is complete and its checksum matches.
+fn read_record<'a>(input: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let (len, rest) = input.split_first_chunk::<4>()?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len + CHECKSUM_LEN {
+        return None
+    }
+    let (record, rest) = rest.split_at(len);
+    let (checksum, rest) = rest.split_at(CHECKSUM_LEN);
+    if blake2b(CHECKSUM_LEN, record) != checksum {
+        return None
+    }
+    *input = rest;
+    Some(record)
+}
+
+// Whether the bytes after the last valid record are something else than a record which was
+// being written when the process was killed. Records are written one at a time, so such a record
+// ends the log. If the bytes hold a complete record, and more after it, they are corrupted.
+fn is_corrupted(rest: &[u8]) -> bool {
+    let Some((len, rest)) = rest.split_first_chunk::<4>() else { return false };
+    let len = u32::from_le_bytes(*len) as usize;
+    rest.len() > len.saturating_add(CHECKSUM_LEN)
+}
+
+// Wait until the entry of the file at `path` in its directory is on disk, so the file is not lost
+// with the directory if the system crashes.
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    File::open(dir.unwrap_or(Path::new(".")))?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("backend-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn put(key: &str, value: &str) -> (Vec<u8>, Option<Vec<u8>>) {
+        (key.into(), Some(value.into()))
+    }
+
+    #[test]
+    fn in_memory_backend_works() {
+        let mut backend = InMemoryBackend::default();
+        backend.commit(vec![put("a1", "x"), put("a2", "y"), put("b1", "z")]).unwrap();
+        backend.commit(vec![(b"a2".to_vec(), None)]).unwrap();
+        assert_eq!(backend.get(b"a1"), Some(b"x".to_vec()));
+        assert_eq!(backend.get(b"a2"), None);
+        assert_eq!(backend.iter_prefix(b"a"), vec![(b"a1".to_vec(), b"x".to_vec())]);
+        assert_eq!(backend.iter_prefix(b"").len(), 2);
+    }
+
+    #[test]
+    fn file_backend_recovers_from_a_partial_record() {
+        let path = test_path("partial");
+        let mut backend = FileBackend::open(&path).unwrap();
+        backend.commit(vec![put("alice", "100"), put("bob", "50")]).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        backend.commit(vec![put("alice", "90"), put("bob", "60")]).unwrap();
+        drop(backend);
+
+        // The process was killed while the second record was written.
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len + 10).unwrap();
+
+        let mut backend = FileBackend::open(&path).unwrap();
+        assert_eq!(backend.get(b"alice"), Some(b"100".to_vec()));
+        assert_eq!(backend.get(b"bob"), Some(b"50".to_vec()));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+
+        // The next records are appended after the valid ones.
+        backend.commit(vec![(b"bob".to_vec(), None)]).unwrap();
+        let backend = FileBackend::open(&path).unwrap();
+        assert_eq!(backend.iter_prefix(b""), vec![(b"alice".to_vec(), b"100".to_vec())]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn file_backend_ignores_a_corrupted_last_record() {
+        let path = test_path("corrupted");
+        let mut backend = FileBackend::open(&path).unwrap();
+        backend.commit(vec![put("alice", "100")]).unwrap();
+        drop(backend);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+
+        let backend = FileBackend::open(&path).unwrap();
+        assert_eq!(backend.get(b"alice"), None);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn file_backend_rejects_a_corrupted_record_before_others() {
+        let path = test_path("corrupted-middle");
+        let mut backend = FileBackend::open(&path).unwrap();
+        backend.commit(vec![put("alice", "100")]).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len() as usize;
+        backend.commit(vec![put("bob", "50")]).unwrap();
+        drop(backend);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[len - 1] ^= 1;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let error = FileBackend::open(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        // The valid records after the corrupted one are kept.
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+        std::fs::remove_file(path).unwrap();
+    }
+}
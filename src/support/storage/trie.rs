@@ -0,0 +1,357 @@
+use crate::support::{BlakeTwo256, Decode, Encode, H256, Hasher};
+use std::collections::BTreeMap;
+
+/// A proof of the value stored at a key, or of its absence: the encoded nodes of the trie along
+/// the path of the key, from the root.
+pub type StorageProof = Vec<Vec<u8>>;
+
+/// Why a storage proof was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofError {
+    /// The proof ends before it reaches the key.
+    Incomplete,
+    /// A node of the proof is not the one its parent, or the state root, commits to.
+    InvalidNode,
+    /// The proof has more nodes than the path of the key.
+    UnusedNodes,
+}
+
+/// A Merkle Patricia trie over the keys and values of the storage, which the state root is the
+/// root hash of.
+///
+/// The trie can prove the value stored at a key, or its absence, to anyone holding its root only,
+/// with `prove` and `verify_proof`.
+///
+/// Keys are split into nibbles, and each node of the trie has up to 16 children, one per nibble.
+/// A chain of nodes with a single child and no value is collapsed into one node, so the shape of
+/// the trie, and its root, only depend on the keys and values it holds.
+///
+/// The hash of each node is kept until a key below it changes, so only the nodes along the paths
+/// of the changed keys are hashed again.
+#[derive(Debug, Clone, Default)]
+pub struct Trie {
+    root: Node,
+}
+
+// A node of the trie, and the nodes below it.
+#[derive(Debug, Clone, Default)]
+struct Node {
+    // The nibbles of the key from the parent of the node, after the nibble of its child index.
+    partial: Vec<u8>,
+    // The value stored at the key ending at this node, if any.
+    value: Option<Vec<u8>>,
+    children: BTreeMap<u8, Node>,
+    // The hash of the node, unless something below it changed since it was computed.
+    hash: Option<H256>,
+}
+
+// What the hash of a node commits to. Its children are only referred to by their hashes.
+#[derive(macros::Encode)]
+struct EncodedNode<'a> {
+    partial: &'a [u8],
+    value: Option<&'a [u8]>,
+    children: Vec<(u8, H256)>,
+}
+
+// A node of a proof, which decodes an `EncodedNode`.
+#[derive(macros::Decode)]
+struct ProofNode {
+    partial: Vec<u8>,
+    value: Option<Vec<u8>>,
+    children: Vec<(u8, H256)>,
+}
+
+impl Trie {
+    /// Create a trie holding `entries`.
+    pub fn from_entries(entries: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>) -> Self {
+        let mut trie = Self::default();
+        for (key, value) in entries {
+            trie.insert(&key, value);
+        }
+        trie
+    }
+
+    /// Store `value` at `key`.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+        self.root.insert(&nibbles(key), value);
+    }
+
+    /// Remove the value stored at `key`, if any.
+    pub fn remove(&mut self, key: &[u8]) {
+        if self.root.remove(&nibbles(key)) {
+            self.root.normalize();
+        }
+    }
+
+    /// The root hash of the trie. The root of an empty trie is the hash of an empty node.
+    pub fn root(&mut self) -> H256 {
+        self.root.hash()
+    }
+
+    /// Prove the value stored at `key`, or that there is none.
+    pub fn prove(&mut self, key: &[u8]) -> StorageProof {
+        self.root();
+        let nibbles = nibbles(key);
+        let (mut node, mut path) = (&self.root, &nibbles[..]);
+        let mut proof = vec![node.encode()];
+        while let Some((nibble, rest)) =
+            path.strip_prefix(node.partial.as_slice()).and_then(|rest| rest.split_first())
+        {
+            let Some(child) = node.children.get(nibble) else { break };
+            proof.push(child.encode());
+            (node, path) = (child, rest);
+        }
+        proof
+    }
+}
+
+/// Check a proof made by `Trie::prove` against the root of a trie, and return the value it proves
+/// is stored at `key`, or `None` if it proves there is none.
+pub fn verify_proof(
+    root: &H256,
+    key: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<Option<Vec<u8>>, ProofError> {
+    let nibbles = nibbles(key);
+    let (mut hash, mut path) = (*root, &nibbles[..]);
+    let mut nodes = proof.iter();
+    let value = loop {
+        let encoded = nodes.next().ok_or(ProofError::Incomplete)?;
+        if BlakeTwo256::hash(encoded) != hash {
+            return Err(ProofError::InvalidNode)
+        }
+        let node = ProofNode::decode_all(encoded).map_err(|_| ProofError::InvalidNode)?;
+        let Some(rest) = path.strip_prefix(node.partial.as_slice()) else { break None };
+        let Some((nibble, rest)) = rest.split_first() else { break node.value };
+        let child = node.children.iter().find(|(index, _)| index == nibble);
+        let Some((_, child_hash)) = child else { break None };
+        (hash, path) = (*child_hash, rest);
+    };
+    if nodes.next().is_some() {
+        return Err(ProofError::UnusedNodes)
+    }
+    Ok(value)
+}
+
+impl Node {
+    fn insert(&mut self, path: &[u8], value: Vec<u8>) {
+        self.hash = None;
+        // Only the root of an empty trie is empty, and it takes the whole key.
+        if self.is_empty() {
+            self.partial = path.to_vec();
+            self.value = Some(value);
+            return
+        }
+        let common = common_prefix(&self.partial, path);
+        if common < self.partial.len() {
+            // The key leaves the path of this node: the node is split where they diverge.
+            let child = Node {
+                partial: self.partial[common + 1..].to_vec(),
+                value: self.value.take(),
+                children: core::mem::take(&mut self.children),
+                hash: None,
+            };
+            self.children.insert(self.partial[common], child);
+            self.partial.truncate(common);
+        }
+
+        let Some((&nibble, rest)) = path[common..].split_first() else {
+            self.value = Some(value);
+            return
+        };
+        match self.children.get_mut(&nibble) {
+            Some(child) => child.insert(rest, value),
+            None => {
+                let leaf = Node { partial: rest.to_vec(), value: Some(value), ..Node::default() };
+                self.children.insert(nibble, leaf);
+            },
+        }
+    }
+
+    // Remove the value at `path` below this node, and return whether it was there.
+    fn remove(&mut self, path: &[u8]) -> bool {
+        let Some(rest) = path.strip_prefix(self.partial.as_slice()) else { return false };
+        let removed = match rest.split_first() {
+            None => self.value.take().is_some(),
+            Some((nibble, rest)) => {
+                let Some(child) = self.children.get_mut(nibble) else { return false };
+                let removed = child.remove(rest);
+                if removed {
+                    child.normalize();
+                    if child.is_empty() {
+                        self.children.remove(nibble);
+                    }
+                }
+                removed
+            },
+        };
+        if removed {
+            self.hash = None;
+        }
+        removed
+    }
+
+    // Restore the shape of the trie after a removal: a node without a value and with a single
+    // child is merged with it, and an empty node has no partial key.
+    fn normalize(&mut self) {
+        if self.value.is_none() && self.children.len() == 1 {
+            let (nibble, child) = self.children.pop_first().expect("the node has one child");
+            self.partial.push(nibble);
+            self.partial.extend(child.partial);
+            self.value = child.value;
+            self.children = child.children;
+        }
+        if self.is_empty() {
+            self.partial.clear();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.value.is_none() && self.children.is_empty()
+    }
+
+    fn hash(&mut self) -> H256 {
+        if let Some(hash) = self.hash {
+            return hash
+        }
+        for child in self.children.values_mut() {
+            child.hash();
+        }
+        let hash = BlakeTwo256::hash(&self.encode());
+        self.hash = Some(hash);
+        hash
+    }
+
+    // The encoding of the node, once its children are hashed.
+    fn encode(&self) -> Vec<u8> {
+        let children = self.children.iter().map(|(nibble, child)| {
+            (*nibble, child.hash.expect("the children of a node are hashed before it"))
+        });
+        let encoded = EncodedNode {
+            partial: &self.partial,
+            value: self.value.as_deref(),
+            children: children.collect(),
+        };
+        encoded.encode()
+    }
+}
+
+// Split a key into its nibbles, the high one of each byte first.
+fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|byte| [byte >> 4, byte & 0xf]).collect()
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(keys: &[&str]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        keys.iter().map(|key| (key.as_bytes().to_vec(), (key.len() as u32).encode())).collect()
+    }
+
+    #[test]
+    fn root_only_depends_on_the_entries() {
+        let keys = ["alice", "alicia", "al", "bob", "b", "charlie"];
+        let mut trie = Trie::from_entries(entries(&keys));
+        let mut reversed = Trie::from_entries(entries(&keys).into_iter().rev());
+        assert_eq!(trie.root(), reversed.root());
+
+        // Removing keys gives the same trie as never inserting them.
+        for key in ["alicia", "b", "charlie"] {
+            trie.remove(key.as_bytes());
+        }
+        trie.remove(b"dave");
+        let mut fewer = Trie::from_entries(entries(&["alice", "al", "bob"]));
+        assert_eq!(trie.root(), fewer.root());
+
+        // The root commits to the values too.
+        let root = fewer.root();
+        fewer.insert(b"bob", vec![1]);
+        assert_ne!(fewer.root(), root);
+    }
+
+    #[test]
+    fn updates_match_building_the_trie_again() {
+        let mut trie = Trie::default();
+        let mut entries = BTreeMap::new();
+        // A simple pseudo random sequence, so the test is deterministic.
+        let mut seed = 7u32;
+        for _ in 0..500 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let key = [(seed >> 8) as u8 % 0x24, (seed >> 16) as u8 % 0x13];
+            let key = key[..1 + (seed >> 24) as usize % 2].to_vec();
+            if seed.is_multiple_of(3) {
+                trie.remove(&key);
+                entries.remove(&key);
+            } else {
+                trie.insert(&key, seed.encode());
+                entries.insert(key, seed.encode());
+            }
+            assert_eq!(trie.root(), Trie::from_entries(entries.clone()).root());
+        }
+    }
+
+    #[test]
+    fn proofs_work() {
+        let keys = ["alice", "alicia", "al", "bob", "charlie"];
+        let mut trie = Trie::from_entries(entries(&keys));
+        let root = trie.root();
+
+        for key in keys {
+            let proof = trie.prove(key.as_bytes());
+            let value = verify_proof(&root, key.as_bytes(), &proof);
+            assert_eq!(value, Ok(Some((key.len() as u32).encode())));
+        }
+        // Absent keys: one leaving the path of a node, one without a child for its next nibble,
+        // and one ending in the middle of a node.
+        for key in ["alan", "dave", "ali"] {
+            let proof = trie.prove(key.as_bytes());
+            assert_eq!(verify_proof(&root, key.as_bytes(), &proof), Ok(None));
+        }
+        let mut empty = Trie::default();
+        assert_eq!(verify_proof(&empty.root(), b"alice", &empty.prove(b"alice")), Ok(None));
+
+        // A proof only holds against the root it was made for, and for its key.
+        let mut proof = trie.prove(b"alice");
+        assert_eq!(verify_proof(&empty.root(), b"alice", &proof), Err(ProofError::InvalidNode));
+        assert_eq!(verify_proof(&root, b"alicia", &proof), Err(ProofError::InvalidNode));
+        let short = trie.prove(b"al");
+        assert_eq!(verify_proof(&root, b"alice", &short), Err(ProofError::Incomplete));
+        proof.push(proof[0].clone());
+        assert_eq!(verify_proof(&root, b"alice", &proof), Err(ProofError::UnusedNodes));
+        proof.pop();
+        proof.last_mut().unwrap()[0] ^= 1;
+        assert_eq!(verify_proof(&root, b"alice", &proof), Err(ProofError::InvalidNode));
+    }
+
+    #[test]
+    fn empty_trie_has_the_root_of_an_empty_node() {
+        let mut trie = Trie::default();
+        let empty = trie.root();
+        trie.insert(b"alice", vec![1]);
+        trie.insert(b"al", vec![2]);
+        assert_ne!(trie.root(), empty);
+        trie.remove(b"alice");
+        trie.remove(b"al");
+        assert_eq!(trie.root(), empty);
+    }
+
+    #[test]
+    fn updates_only_hash_the_changed_paths() {
+        let mut trie = Trie::from_entries(entries(&["alice", "bob"]));
+        trie.root();
+        trie.insert(b"alicia", vec![1]);
+
+        // The path of `bob` still has its hash, the root and the path of `alicia` don't.
+        assert!(trie.root.hash.is_none());
+        assert!(trie.root.children[&1].hash.is_none());
+        assert!(trie.root.children[&2].hash.is_some());
+        let mut fresh = Trie::from_entries(entries(&["alice", "bob"]));
+        fresh.insert(b"alicia", vec![1]);
+        assert_eq!(trie.root(), fresh.root());
+    }
+}
@@ -0,0 +1,250 @@
+use super::{Compact, Encode};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+/// A trait for storage which can be changed inside of a transaction.
+///
+/// Transactions can be nested: every call to `start_transaction` must be matched by exactly one
+/// call to either `commit_transaction` or `rollback_transaction`, which will close the most
+/// recently opened transaction.
+pub trait Transactional {
+    /// Open a new transaction. All changes from now on can be reverted at once.
+    fn start_transaction(&mut self);
+    /// Keep all the changes made in the current transaction, and close it.
+    fn commit_transaction(&mut self);
+    /// Revert all the changes made in the current transaction, and close it.
+    fn rollback_transaction(&mut self);
+}
+
+/// Execute `f` in a new transaction over `storage`.
+/// The changes made by `f` are committed if it returns `Ok`, and reverted if it returns `Err`.
+pub fn with_transaction<S, R, E>(
+    storage: &mut S,
+    f: impl FnOnce(&mut S) -> Result<R, E>,
+) -> Result<R, E>
+where
+    S: Transactional + ?Sized,
+{
+    storage.start_transaction();
+    let result = f(storage);
+    match result {
+        Ok(_) => storage.commit_transaction(),
+        Err(_) => storage.rollback_transaction(),
+    }
+    result
+}
+
+/// A `BTreeMap` which supports nested transactions.
+///
+/// Changes made while a transaction is open are kept in an overlay on top of the committed
+/// values, one overlay per open transaction. Reading always sees the latest changes.
+pub struct TransactionalMap<K, V> {
+    // The values which are not part of any open transaction.
+    committed: BTreeMap<K, V>,
+    // One overlay of changes per open transaction, the last one being the most recent.
+    // A `None` value means that the key was removed in that transaction.
+    overlays: Vec<BTreeMap<K, Option<V>>>,
+}
+
+impl<K: Ord + Clone, V: Clone> TransactionalMap<K, V> {
+    /// Create a new empty map.
+    pub fn new() -> Self {
+        Self { committed: BTreeMap::new(), overlays: Vec::new() }
+    }
+
+    /// Get the value stored at `key`, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        for overlay in self.overlays.iter().rev() {
+            if let Some(value) = overlay.get(key) {
+                return value.as_ref()
+            }
+        }
+        self.committed.get(key)
+    }
+
+    /// Check if a value is stored at `key`.
+    // The storage keeps removed keys as `None` values, and is the only user of the map for now.
+    #[allow(dead_code)]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Store `value` at `key`, returning the previous value if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let previous = self.get(&key).cloned();
+        match self.overlays.last_mut() {
+            Some(overlay) => {
+                overlay.insert(key, Some(value));
+            },
+            None => {
+                self.committed.insert(key, value);
+            },
+        }
+        previous
+    }
+
+    /// Remove the value stored at `key`, returning it if any.
+    #[allow(dead_code)]
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let previous = self.get(key).cloned();
+        match self.overlays.last_mut() {
+            Some(overlay) => {
+                overlay.insert(key.clone(), None);
+            },
+            None => {
+                self.committed.remove(key);
+            },
+        }
+        previous
+    }
+
+    /// Iterate over all the keys and values of the map, in order, including the changes of the
+    /// open transactions.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut merged: BTreeMap<&K, &V> = self.committed.iter().collect();
+        for overlay in &self.overlays {
+            for (key, value) in overlay {
+                match value {
+                    Some(value) => merged.insert(key, value),
+                    None => merged.remove(key),
+                };
+            }
+        }
+        merged.into_iter()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Transactional for TransactionalMap<K, V> {
+    fn start_transaction(&mut self) {
+        self.overlays.push(BTreeMap::new());
+    }
+
+    fn commit_transaction(&mut self) {
+        let Some(overlay) = self.overlays.pop() else { return };
+        match self.overlays.last_mut() {
+            // Nested transaction: the changes now belong to the parent transaction.
+            Some(parent) => parent.extend(overlay),
+            // Outermost transaction: the changes are written to the committed values.
+            None => {
+                for (key, value) in overlay {
+                    match value {
+                        Some(value) => self.committed.insert(key, value),
+                        None => self.committed.remove(&key),
+                    };
+                }
+            },
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.overlays.pop();
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for TransactionalMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// We only show the current view of the map, which is what users of the map care about.
+impl<K: Ord + Clone + core::fmt::Debug, V: Clone + core::fmt::Debug> core::fmt::Debug
+    for TransactionalMap<K, V>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// The encoding of the current view of the map, the same as the encoding of a `BTreeMap`.
+impl<K: Ord + Clone + Encode, V: Clone + Encode> Encode for TransactionalMap<K, V> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        let entries = self.iter().collect::<Vec<_>>();
+        Compact(entries.len() as u128).encode_to(dest);
+        for (key, value) in entries {
+            key.encode_to(dest);
+            value.encode_to(dest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_and_rollback() {
+        let mut map = TransactionalMap::<&str, u32>::new();
+        map.insert("alice", 100);
+
+        // Changes of a rolled back transaction are reverted.
+        map.start_transaction();
+        map.insert("alice", 50);
+        map.insert("bob", 50);
+        assert_eq!(map.get("alice"), Some(&50));
+        map.rollback_transaction();
+        assert_eq!(map.get("alice"), Some(&100));
+        assert_eq!(map.get("bob"), None);
+
+        // Changes of a committed transaction are kept.
+        map.start_transaction();
+        map.remove(&"alice");
+        map.insert("bob", 10);
+        assert!(!map.contains_key("alice"));
+        map.commit_transaction();
+        assert_eq!(map.get("alice"), None);
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&"bob", &10)]);
+    }
+
+    #[test]
+    fn nested_transactions() {
+        let mut map = TransactionalMap::<&str, u32>::new();
+
+        map.start_transaction();
+        map.insert("alice", 1);
+
+        // An inner transaction can be reverted without touching the outer one.
+        map.start_transaction();
+        map.insert("alice", 2);
+        map.insert("bob", 2);
+        map.rollback_transaction();
+        assert_eq!(map.get("alice"), Some(&1));
+        assert_eq!(map.get("bob"), None);
+
+        // A committed inner transaction is still reverted with the outer one.
+        map.start_transaction();
+        map.insert("bob", 3);
+        map.commit_transaction();
+        assert_eq!(map.get("bob"), Some(&3));
+        map.rollback_transaction();
+        assert_eq!(map.get("alice"), None);
+        assert_eq!(map.get("bob"), None);
+    }
+
+    #[test]
+    fn with_transaction_works() {
+        let mut map = TransactionalMap::<&str, u32>::new();
+
+        let res: Result<(), ()> = with_transaction(&mut map, |map| {
+            map.insert("alice", 1);
+            Err(())
+        });
+        assert_eq!(res, Err(()));
+        assert_eq!(map.get("alice"), None);
+
+        let res: Result<(), ()> = with_transaction(&mut map, |map| {
+            map.insert("alice", 1);
+            Ok(())
+        });
+        assert_eq!(res, Ok(()));
+        assert_eq!(map.get("alice"), Some(&1));
+    }
+}
@@ -1,50 +1,375 @@
-use std::collections::BTreeMap;
-use num::traits::{One, Zero};
-use core::ops::AddAssign;
+use num::traits::{CheckedAdd, One, Zero};
+use core::fmt::Debug;
+use crate::support::{
+    BuildGenesisConfig, Decode, DispatchError, Encode, Hasher, Hooks, Storage, StorageMap,
+    StorageValue, Transactional, Weight,
+};
 
 pub trait Config {
-    type AccountId: Ord + Clone;
-    type BlockNumber: Zero + One + AddAssign + Copy;
-    type Nonce: Zero + One + Copy;
+    type AccountId: Ord + Clone + Debug + Encode + Decode;
+    /// The origin of the calls of the runtime, which pallets turn back into a `RawOrigin` to check
+    /// who is calling.
+    type RuntimeOrigin: Clone + From<RawOrigin<Self::AccountId>> + Into<RawOrigin<Self::AccountId>>;
+    type BlockNumber: Zero
+        + One
+        + CheckedAdd
+        + Ord
+        + Copy
+        + Debug
+        + Encode
+        + Decode
+        + core::fmt::Display;
+    type Nonce: Zero + One + CheckedAdd + Ord + Copy + Encode + Decode;
+    /// The output of the hashing function, used for block hashes and roots.
+    type Hash: Copy + Eq + Default + Debug + Encode;
+    /// The hashing function used for block hashes and roots.
+    type Hashing: Hasher<Output = Self::Hash>;
+    /// The aggregated event type of the runtime, which the events of every pallet convert into.
+    type RuntimeEvent: Clone + From<Event>;
+    /// The maximum total weight of the extrinsics in a block.
+    const MAX_BLOCK_WEIGHT: Weight;
 }
 
+/// Who, or what, a call is dispatched from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawOrigin<AccountId> {
+    /// The system itself, which is allowed to make privileged calls.
+    Root,
+    /// The signer of a signed extrinsic.
+    Signed(AccountId),
+    /// An unsigned extrinsic, which has no signer.
+    None,
+}
+
+/// Check that `origin` is a signed origin, and return the account which signed.
+pub fn ensure_signed<AccountId>(
+    origin: impl Into<RawOrigin<AccountId>>,
+) -> Result<AccountId, DispatchError> {
+    match origin.into() {
+        RawOrigin::Signed(who) => Ok(who),
+        _ => Err(DispatchError::BadOrigin),
+    }
+}
+
+/// Check that `origin` is the root origin.
+pub fn ensure_root<AccountId>(
+    origin: impl Into<RawOrigin<AccountId>>,
+) -> Result<(), DispatchError> {
+    match origin.into() {
+        RawOrigin::Root => Ok(()),
+        _ => Err(DispatchError::BadOrigin),
+    }
+}
+
+/// The events of the system pallet.
+#[macros::event]
+pub enum Event {
+    /// An extrinsic was dispatched successfully.
+    ExtrinsicSuccess,
+    /// An extrinsic failed, and all of its changes were reverted.
+    ExtrinsicFailed { error: DispatchError },
+}
+
+/// The errors which can make an extrinsic, and the block including it, invalid.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The signature of the extrinsic does not match its signer.
+    BadSignature,
+    /// The call of an unsigned extrinsic failed. Unsigned extrinsics pay no fees, so they are
+    /// only allowed in a block if their call succeeds.
+    UnsignedNotAllowed,
+    /// The nonce of the extrinsic was already used by the signer.
+    StaleNonce,
+    /// The nonce of the extrinsic is ahead of the nonce of the signer.
+    FutureNonce,
+    /// The nonce of the signer can't be increased anymore.
+    NonceOverflow,
+    /// The block number can't be increased anymore.
+    BlockNumberOverflow,
+    /// The weight of the extrinsic does not fit in the block anymore.
+    ExhaustsResources,
+    /// The signer can't pay the fee of the extrinsic.
+    CannotPayFees,
+}
+
+/// The initial state of the system pallet.
+#[derive(Debug)]
+pub struct GenesisConfig<T: Config> {
+    /// The number of the genesis block. The first block built on top of it has the next number.
+    pub block_number: T::BlockNumber,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+    fn default() -> Self {
+        Self { block_number: T::BlockNumber::zero() }
+    }
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>, T::BlockNumber> for GenesisConfig<T> {
+    // The genesis block number is the one of this configuration.
+    fn build(&self, pallet: &mut Pallet<T>, _n: T::BlockNumber) -> Result<(), DispatchError> {
+        pallet.block_number.put(self.block_number);
+        Ok(())
+    }
+}
+
+/// The phase of the block execution in which an event was deposited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Before the first extrinsic of the block is applied.
+    Initialization,
+    /// While applying the extrinsic at this index in the block.
+    ApplyExtrinsic(u32),
+    /// After all the extrinsics of the block have been applied.
+    Finalization,
+}
+
+/// An event deposited in the current block, along with the phase in which it was deposited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventRecord<E> {
+    pub phase: Phase,
+    pub event: E,
+}
+
+/// The number of the current block.
+#[macros::storage]
+pub type Number<T: Config> = StorageValue<_, T::BlockNumber>;
+
+/// The nonce of each account, like `("femi", 10)`.
+#[macros::storage]
+pub type AccountNonce<T: Config> = StorageMap<_, T::AccountId, T::Nonce>;
 
 #[derive(Debug)]
 pub struct Pallet <T:Config> {
-    pub block_number: T::BlockNumber,            // 2^32 = 4.5 million
-    pub nonce: BTreeMap<T::AccountId, T::Nonce>, // <username, nonce_value> e.g. ("femi", 10)
+    pub block_number: Number<T>,
+    pub nonce: AccountNonce<T>,
+    // The storage shared by all the pallets of the runtime, where the items above are stored.
+    storage: Storage,
+    // The hash of the genesis block, which identifies the chain.
+    genesis_hash: T::Hash,
+    // The hash of the header of the last executed block.
+    parent_hash: T::Hash,
+    // The current phase of the block execution.
+    phase: Phase,
+    // The events deposited in the current block, in order.
+    events: Vec<EventRecord<T::RuntimeEvent>>,
+    // The total weight of the extrinsics applied in the current block.
+    block_weight: Weight,
+    // What we need to revert the values which are not in the storage, one per open transaction.
+    checkpoints: Vec<Checkpoint<T>>,
+}
+
+// The values of the pallet when a transaction was started.
+#[derive(Debug)]
+struct Checkpoint<T: Config> {
+    parent_hash: T::Hash,
+    block_weight: Weight,
+    // The number of events deposited in the current block.
+    events_len: usize,
+    // The events of the previous block, if they were cleared during this transaction.
+    cleared_events: Option<Vec<EventRecord<T::RuntimeEvent>>>,
 }
 
 impl<T:Config> Pallet<T>{
-    /// Create an instance of the pallet
-    pub fn new() -> Self {
+    /// Create an instance of the pallet, in the storage shared by all the pallets.
+    pub fn new(storage: &Storage) -> Self {
         Self {
-            block_number: T::BlockNumber::zero(),
-            nonce: BTreeMap::new(),
+            block_number: Number::<T>::new(storage),
+            nonce: AccountNonce::<T>::new(storage),
+            storage: storage.clone(),
+            genesis_hash: T::Hash::default(),
+            parent_hash: T::Hash::default(),
+            phase: Phase::Initialization,
+            events: Vec::new(),
+            block_weight: 0,
+            checkpoints: Vec::new(),
         }
     }
 
+    /// Get the storage shared by all the pallets of the runtime.
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
     /// Get the current block number
     pub fn block_number(&self) -> T::BlockNumber {
-        self.block_number
+        self.block_number.get().unwrap_or_else(T::BlockNumber::zero)
     }
 
     /// Increase the block number by one
-    pub fn inc_block_number(&mut self) {
-        self.block_number += T::BlockNumber::one();
+    pub fn inc_block_number(&mut self) -> Result<(), Error> {
+        let block_number = self
+            .block_number()
+            .checked_add(&T::BlockNumber::one())
+            .ok_or(Error::BlockNumberOverflow)?;
+        self.block_number.put(block_number);
+        Ok(())
+    }
+
+    /// Get the nonce of `who`, which is the number of extrinsics they have submitted so far.
+    /// This is the nonce their next extrinsic must use.
+    pub fn account_nonce(&self, who: &T::AccountId) -> T::Nonce {
+        self.nonce.get(who).unwrap_or_else(T::Nonce::zero)
+    }
+
+    /// Check that `nonce` is the nonce expected for the next extrinsic of `who`.
+    pub fn check_nonce(&self, who: &T::AccountId, nonce: T::Nonce) -> Result<(), Error> {
+        let expected = self.account_nonce(who);
+        if nonce < expected {
+            return Err(Error::StaleNonce)
+        }
+        if nonce > expected {
+            return Err(Error::FutureNonce)
+        }
+        Ok(())
     }
 
     /// Increase the nonce value of the caller `who`
-    pub fn inc_nonce(&mut self, who: &T::AccountId) {
+    pub fn inc_nonce(&mut self, who: &T::AccountId) -> Result<(), Error> {
         // Check for the nonce of `who`, and store. If it does not exist, set nonce to `0`
         // create new nonce => nonce + 1
         // store new nonce, with caller
-        let nonce = *self.nonce.get(who).unwrap_or(&T::Nonce::zero());
-        let new_nonce = nonce + T::Nonce::one();
-        self.nonce.insert(who.clone(), new_nonce);
+        let nonce = self.account_nonce(who);
+        let new_nonce = nonce.checked_add(&T::Nonce::one()).ok_or(Error::NonceOverflow)?;
+        self.nonce.insert(who, new_nonce);
+        Ok(())
+    }
+
+    /// Remove the nonce of `who`, once their account was reaped. If the account is created again,
+    /// its nonce starts from zero.
+    pub fn kill_account(&mut self, who: &T::AccountId) {
+        self.nonce.remove(who);
+    }
+
+    /// Get the hash of the genesis block, which signed extrinsics commit to.
+    pub fn genesis_hash(&self) -> T::Hash {
+        self.genesis_hash
+    }
+
+    /// Set the hash of the genesis block, once the genesis state is built. The genesis block is
+    /// also the parent of the first block.
+    pub fn set_genesis_hash(&mut self, hash: T::Hash) {
+        self.genesis_hash = hash;
+        self.parent_hash = hash;
+    }
+
+    /// Get the hash of the header of the last executed block.
+    pub fn parent_hash(&self) -> T::Hash {
+        self.parent_hash
+    }
+
+    /// Set the hash of the header of the last executed block.
+    pub fn set_parent_hash(&mut self, hash: T::Hash) {
+        self.parent_hash = hash;
+    }
+
+    /// Start a new block: clear the events and the weight of the previous block.
+    pub fn initialize_block(&mut self) {
+        self.reset_events();
+        self.block_weight = 0;
+    }
+
+    /// Get the total weight of the extrinsics applied in the current block.
+    pub fn block_weight(&self) -> Weight {
+        self.block_weight
+    }
+
+    /// Add `weight` to the weight of the current block, if it stays under `MAX_BLOCK_WEIGHT`.
+    pub fn register_weight(&mut self, weight: Weight) -> Result<(), Error> {
+        self.block_weight = self
+            .block_weight
+            .checked_add(weight)
+            .filter(|total| *total <= T::MAX_BLOCK_WEIGHT)
+            .ok_or(Error::ExhaustsResources)?;
+        Ok(())
+    }
+
+    /// Add `weight` to the weight of the current block, even above `MAX_BLOCK_WEIGHT`. The hooks
+    /// of the pallets run whatever the extrinsics of the block used.
+    pub fn register_mandatory_weight(&mut self, weight: Weight) {
+        self.block_weight = self.block_weight.saturating_add(weight);
+    }
+
+    /// Give back `weight` which an extrinsic registered but did not use.
+    pub fn refund_weight(&mut self, weight: Weight) {
+        self.block_weight = self.block_weight.saturating_sub(weight);
+    }
+
+    /// Clear the events of the previous block, and enter the initialization phase.
+    pub fn reset_events(&mut self) {
+        let cleared_events = core::mem::take(&mut self.events);
+        // Keep the cleared events around in case the transaction is reverted. If the events were
+        // already cleared in this transaction, the first list is the one we need.
+        if let Some(checkpoint) = self.checkpoints.last_mut() {
+            checkpoint.cleared_events.get_or_insert(cleared_events);
+        }
+        self.phase = Phase::Initialization;
+    }
+
+    /// Note that the extrinsic at `index` in the block is being applied.
+    pub fn note_extrinsic(&mut self, index: u32) {
+        self.phase = Phase::ApplyExtrinsic(index);
+    }
+
+    /// Note that all the extrinsics of the block have been applied.
+    pub fn note_finished_extrinsics(&mut self) {
+        self.phase = Phase::Finalization;
+    }
+
+    /// Deposit an event in the current block, recording the current phase.
+    pub fn deposit_event(&mut self, event: impl Into<T::RuntimeEvent>) {
+        self.events.push(EventRecord { phase: self.phase, event: event.into() });
+    }
+
+    /// Get all the events deposited in the current block.
+    pub fn events(&self) -> &[EventRecord<T::RuntimeEvent>] {
+        &self.events
+    }
+}
+
+// The system pallet opens the transactions of the storage shared by all the pallets.
+//
+// Events are only ever appended during a transaction, unless the block changes, so reverting them
+// is usually just truncating the list.
+impl<T: Config> Transactional for Pallet<T> {
+    fn start_transaction(&mut self) {
+        self.storage.start_transaction();
+        self.checkpoints.push(Checkpoint {
+            parent_hash: self.parent_hash,
+            block_weight: self.block_weight,
+            events_len: self.events.len(),
+            cleared_events: None,
+        });
+    }
+
+    fn commit_transaction(&mut self) {
+        self.storage.commit_transaction();
+        let Some(checkpoint) = self.checkpoints.pop() else { return };
+        // The parent transaction may need to restore the cleared events too.
+        if let (Some(parent), Some(cleared_events)) =
+            (self.checkpoints.last_mut(), checkpoint.cleared_events)
+        {
+            parent.cleared_events.get_or_insert(cleared_events);
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.storage.rollback_transaction();
+        let Some(checkpoint) = self.checkpoints.pop() else { return };
+        self.parent_hash = checkpoint.parent_hash;
+        self.block_weight = checkpoint.block_weight;
+        if let Some(cleared_events) = checkpoint.cleared_events {
+            self.events = cleared_events;
+        }
+        self.events.truncate(checkpoint.events_len);
     }
 }
 
+// The system pallet has no per-block logic of its own. The runtime manages the block number, the
+// events and the weight around the hooks of the other pallets.
+impl<T: Config> Hooks<T, T::BlockNumber> for Pallet<T> {}
+
 #[cfg(test)]
 mod tests {
     
@@ -52,8 +377,13 @@ mod tests {
 
     impl super::Config for TestConfig {
         type AccountId = String;
+        type RuntimeOrigin = RawOrigin<String>;
         type BlockNumber = u32;
         type Nonce = u32;
+        type Hash = crate::support::H256;
+        type Hashing = crate::support::BlakeTwo256;
+        type RuntimeEvent = Event;
+        const MAX_BLOCK_WEIGHT: Weight = 100;
     }
     use super::*;
 
@@ -61,20 +391,107 @@ mod tests {
     fn system_pallet_work() {
         // Arrange
         // create system pallet
-        let mut system = Pallet::<TestConfig>::new();
+        let mut system = Pallet::<TestConfig>::new(&Storage::new());
 
         // Act
         // increase current block number
-        system.inc_block_number();
+        system.inc_block_number().unwrap();
         // increase the nonce of a user - `Temi`
-        system.inc_nonce(&"Temi".to_string());
+        system.inc_nonce(&"Temi".to_string()).unwrap();
 
         // Assert
         // Check the block number (i.e. 1)
         assert_eq!(system.block_number(), 1);
         // Check the nonce of Temi (i.e. 1)
-        assert_eq!(system.nonce.get("Temi"), Some(&1));
+        assert_eq!(system.nonce.get(&"Temi".to_string()), Some(1));
         // Check the nonce of Faithful (i.e. 0)
-        assert_eq!(system.nonce.get("Faithful"), None);
+        assert_eq!(system.nonce.get(&"Faithful".to_string()), None);
+    }
+
+    #[test]
+    fn nonce_checks_work() {
+        let mut system = Pallet::<TestConfig>::new(&Storage::new());
+        let temi = "Temi".to_string();
+
+        assert_eq!(system.account_nonce(&temi), 0);
+        assert_eq!(system.check_nonce(&temi, 0), Ok(()));
+        assert_eq!(system.check_nonce(&temi, 1), Err(Error::FutureNonce));
+
+        system.inc_nonce(&temi).unwrap();
+        assert_eq!(system.account_nonce(&temi), 1);
+        assert_eq!(system.check_nonce(&temi, 0), Err(Error::StaleNonce));
+        assert_eq!(system.check_nonce(&temi, 1), Ok(()));
+    }
+
+    #[test]
+    fn increments_do_not_overflow() {
+        let mut system = Pallet::<TestConfig>::new(&Storage::new());
+        let temi = "Temi".to_string();
+
+        system.block_number.put(u32::MAX);
+        assert_eq!(system.inc_block_number(), Err(Error::BlockNumberOverflow));
+        assert_eq!(system.block_number(), u32::MAX);
+
+        system.nonce.insert(&temi, u32::MAX);
+        assert_eq!(system.inc_nonce(&temi), Err(Error::NonceOverflow));
+        assert_eq!(system.account_nonce(&temi), u32::MAX);
+    }
+
+    #[test]
+    fn block_weight_is_limited() {
+        let mut system = Pallet::<TestConfig>::new(&Storage::new());
+
+        system.register_weight(60).unwrap();
+        assert_eq!(system.register_weight(50), Err(Error::ExhaustsResources));
+        assert_eq!(system.register_weight(Weight::MAX), Err(Error::ExhaustsResources));
+        assert_eq!(system.block_weight(), 60);
+
+        system.refund_weight(20);
+        system.register_weight(60).unwrap();
+        assert_eq!(system.block_weight(), 100);
+
+        system.initialize_block();
+        assert_eq!(system.block_weight(), 0);
+    }
+
+    #[test]
+    fn events_work() {
+        let mut system = Pallet::<TestConfig>::new(&Storage::new());
+
+        system.deposit_event(Event::ExtrinsicSuccess);
+        system.note_extrinsic(0);
+        system.deposit_event(Event::ExtrinsicSuccess);
+        assert_eq!(
+            system.events(),
+            &[
+                EventRecord { phase: Phase::Initialization, event: Event::ExtrinsicSuccess },
+                EventRecord { phase: Phase::ApplyExtrinsic(0), event: Event::ExtrinsicSuccess },
+            ]
+        );
+
+        // Events deposited in a reverted transaction are removed.
+        system.note_extrinsic(1);
+        system.start_transaction();
+        system.deposit_event(Event::ExtrinsicFailed { error: DispatchError::Other("oops") });
+        system.rollback_transaction();
+        assert_eq!(system.events().len(), 2);
+
+        // Events are cleared at the start of the next block.
+        system.reset_events();
+        assert!(system.events().is_empty());
+
+        // Clearing the events can also be reverted.
+        system.deposit_event(Event::ExtrinsicSuccess);
+        system.start_transaction();
+        system.start_transaction();
+        system.reset_events();
+        system.deposit_event(Event::ExtrinsicSuccess);
+        system.commit_transaction();
+        assert_eq!(system.events().len(), 1);
+        system.rollback_transaction();
+        assert_eq!(
+            system.events(),
+            &[EventRecord { phase: Phase::Initialization, event: Event::ExtrinsicSuccess }]
+        );
     }
 }
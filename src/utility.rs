@@ -0,0 +1,193 @@
+use crate::support::{
+    BuildGenesisConfig, Decode, Dispatch, DispatchError, DispatchErrorWithPostInfo, DispatchResult,
+    DispatchResultWithPostInfo, Encode, GetWeight, Hooks, Storage, Transactional, Weight,
+    extract_actual_weight, with_transaction,
+};
+use core::fmt::Debug;
+use core::marker::PhantomData;
+
+/// The configuration trait for the Utility Module.
+///
+/// Batches dispatch calls of the whole runtime, so the runtime itself must be able to dispatch
+/// them, in a transaction.
+pub trait Config:
+    crate::system::Config
+    + Dispatch<Origin = Self::RuntimeOrigin, Call = <Self as Config>::RuntimeCall>
+    + Transactional
+    + AsMut<Pallet<Self>>
+    + Sized
+{
+    /// The calls of the runtime, which can be batched.
+    type RuntimeCall: Clone + PartialEq + Debug + Encode + Decode + GetWeight;
+}
+
+// The weight of dispatching each call of a batch, on top of the weight of the call itself.
+const ITEM_WEIGHT: Weight = 10;
+
+// The declared weight of a batch: the weight of all of its calls.
+fn batch_weight<Call: GetWeight>(calls: &[Call]) -> Weight {
+    calls
+        .iter()
+        .fold(0, |weight, call| weight.saturating_add(call.weight()).saturating_add(ITEM_WEIGHT))
+}
+
+/// This is the Utility Module.
+/// It lets an account dispatch several calls with a single extrinsic, paying a single nonce.
+#[derive(Debug)]
+pub struct Pallet<T: Config> {
+    // The events deposited by this pallet, which are moved to the system pallet by the runtime.
+    events: Vec<Event<T>>,
+}
+
+/// The initial state of the Utility Module. It has no state.
+#[derive(Debug)]
+pub struct GenesisConfig<T: Config>(PhantomData<T>);
+
+impl<T: Config> Default for GenesisConfig<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>, T::BlockNumber> for GenesisConfig<T> {
+    fn build(&self, _pallet: &mut Pallet<T>, _n: T::BlockNumber) -> DispatchResult {
+        Ok(())
+    }
+}
+
+/// The events of the Utility Module.
+#[macros::event]
+pub enum Event<T: Config> {
+    /// A call of a batch was dispatched successfully.
+    ItemCompleted,
+    /// A call of a `force_batch` failed with this error. Its changes were reverted.
+    ItemFailed { error: DispatchError },
+    /// A `batch` stopped at the call at `index`, which failed with this error.
+    BatchInterrupted { index: u32, error: DispatchError },
+    /// All the calls of a batch were dispatched successfully.
+    BatchCompleted,
+    /// All the calls of a `force_batch` were dispatched, but some of them failed.
+    BatchCompletedWithErrors,
+    // The events of the other pallets depend on the runtime, and the `RuntimeEvent` enum expects
+    // all of them to be generic. This variant is never deposited.
+    #[doc(hidden)]
+    __Ignore(PhantomData<T>),
+}
+
+impl<T: Config> Pallet<T> {
+    /// Create a new instance of the utility module. It keeps nothing in the storage.
+    pub fn new(_storage: &Storage) -> Self {
+        Self { events: Vec::new() }
+    }
+
+    /// Deposit an event of this pallet.
+    pub fn deposit_event(&mut self, event: Event<T>) {
+        self.events.push(event);
+    }
+
+    /// Take all the events deposited by this pallet since the last call.
+    pub fn take_events(&mut self) -> Vec<Event<T>> {
+        core::mem::take(&mut self.events)
+    }
+
+    // Dispatch `call` from `origin` in its own transaction, so a failed call leaves no changes
+    // behind. Returns the result of the call, and the weight it actually used, including the
+    // weight of dispatching it.
+    fn dispatch_item(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        call: <T as Config>::RuntimeCall,
+    ) -> (DispatchResult, Weight) {
+        let weight = call.weight();
+        let res = with_transaction(runtime, |runtime| runtime.dispatch(origin, call));
+        let actual_weight = extract_actual_weight(&res, weight).saturating_add(ITEM_WEIGHT);
+        (res.map(|_| ()).map_err(|e| e.error), actual_weight)
+    }
+}
+
+// The utility pallet has no per-block logic.
+impl<T: Config> Hooks<T, T::BlockNumber> for Pallet<T> {}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+    /// Dispatch `calls` in order, from the same origin, until one of them fails.
+    /// The calls before the failed one are kept, and the batch itself succeeds. Where it stopped
+    /// is in the `BatchInterrupted` event.
+    #[call_index(0)]
+    #[weight(batch_weight(calls))]
+    pub fn batch(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        calls: Vec<<T as Config>::RuntimeCall>,
+    ) -> DispatchResultWithPostInfo {
+        let mut weight: Weight = 0;
+        for (index, call) in (0..).zip(calls) {
+            let (res, call_weight) = Self::dispatch_item(runtime, origin.clone(), call);
+            weight = weight.saturating_add(call_weight);
+            if let Err(error) = res {
+                runtime.as_mut().deposit_event(Event::BatchInterrupted { index, error });
+                return Ok(Some(weight).into())
+            }
+            runtime.as_mut().deposit_event(Event::ItemCompleted);
+        }
+        runtime.as_mut().deposit_event(Event::BatchCompleted);
+        Ok(Some(weight).into())
+    }
+
+    /// Dispatch `calls` in order, from the same origin, all or nothing.
+    /// If one of them fails, the changes of all the calls are reverted, and the batch fails with
+    /// its error.
+    #[call_index(1)]
+    #[weight(batch_weight(calls))]
+    pub fn batch_all(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        calls: Vec<<T as Config>::RuntimeCall>,
+    ) -> DispatchResultWithPostInfo {
+        with_transaction(runtime, |runtime| {
+            let mut weight: Weight = 0;
+            for call in calls {
+                let call_weight = call.weight();
+                let res = runtime.dispatch(origin.clone(), call);
+                weight = weight
+                    .saturating_add(extract_actual_weight(&res, call_weight))
+                    .saturating_add(ITEM_WEIGHT);
+                if let Err(e) = res {
+                    let post_info = Some(weight).into();
+                    return Err(DispatchErrorWithPostInfo { post_info, error: e.error })
+                }
+                runtime.as_mut().deposit_event(Event::ItemCompleted);
+            }
+            runtime.as_mut().deposit_event(Event::BatchCompleted);
+            Ok(Some(weight).into())
+        })
+    }
+
+    /// Dispatch all of `calls` in order, from the same origin, even if some of them fail.
+    /// The changes of the failed calls are reverted, and their errors are in `ItemFailed` events.
+    #[call_index(2)]
+    #[weight(batch_weight(calls))]
+    pub fn force_batch(
+        runtime: &mut T,
+        origin: T::RuntimeOrigin,
+        calls: Vec<<T as Config>::RuntimeCall>,
+    ) -> DispatchResultWithPostInfo {
+        let mut weight: Weight = 0;
+        let mut has_errors = false;
+        for call in calls {
+            let (res, call_weight) = Self::dispatch_item(runtime, origin.clone(), call);
+            weight = weight.saturating_add(call_weight);
+            match res {
+                Ok(()) => runtime.as_mut().deposit_event(Event::ItemCompleted),
+                Err(error) => {
+                    has_errors = true;
+                    runtime.as_mut().deposit_event(Event::ItemFailed { error });
+                },
+            }
+        }
+        let event =
+            if has_errors { Event::BatchCompletedWithErrors } else { Event::BatchCompleted };
+        runtime.as_mut().deposit_event(event);
+        Ok(Some(weight).into())
+    }
+}